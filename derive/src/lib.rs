@@ -0,0 +1,169 @@
+//!
+//! Custom derive for `gfx-mesh` vertex formats.
+//!
+//! `#[derive(VertexFormat)]` implements `Pod`, `AsVertexFormat` and `WithAttribute`
+//! for each field of a `#[repr(C)]` struct composed of `Attribute` types.
+//! Offsets and stride are computed from fields' sizes and the generated code fails to compile
+//! if the struct's layout contains padding.
+//! `#[repr(packed)]` is rejected: without padding `#[repr(C)]` layout is already tightly packed,
+//! while packed structs only add unaligned fields.
+//! Each attribute type may appear only once. Fields of the same type,
+//! even when spelled through different paths or aliases,
+//! fail to compile with conflicting `WithAttribute` implementations.
+//!
+#![deny(missing_docs)]
+#![deny(dead_code)]
+#![deny(unused_must_use)]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::{Data, DeriveInput, Fields, Index, Member, Meta, NestedMeta};
+
+/// Derive `Pod`, `AsVertexFormat` and `WithAttribute` for each field.
+#[proc_macro_derive(VertexFormat)]
+pub fn derive_vertex_format(input: TokenStream) -> TokenStream {
+    let input: DeriveInput =
+        syn::parse(input).expect("Failed to parse input of `#[derive(VertexFormat)]`");
+    expand(&input).into()
+}
+
+fn expand(input: &DeriveInput) -> TokenStream2 {
+    let name = &input.ident;
+
+    if !input.generics.params.is_empty() {
+        panic!(
+            "`VertexFormat` can't be derived for generic type `{}`",
+            name
+        );
+    }
+
+    let repr = repr_hints(input);
+    if !repr.iter().any(|hint| hint == "C") {
+        panic!(
+            "`VertexFormat` can be derived only for `#[repr(C)]` types. `{}` must be `#[repr(C)]`",
+            name
+        );
+    }
+    if repr.iter().any(|hint| hint == "packed") {
+        panic!(
+            "`VertexFormat` can't be derived for `#[repr(packed)]` type `{}`. Remove `packed`",
+            name
+        );
+    }
+
+    let fields = match input.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => fields
+                .named
+                .iter()
+                .map(|f| (Member::Named(f.ident.clone().unwrap()), f.ty.clone()))
+                .collect::<Vec<_>>(),
+            Fields::Unnamed(ref fields) => fields
+                .unnamed
+                .iter()
+                .enumerate()
+                .map(|(i, f)| (Member::Unnamed(Index::from(i)), f.ty.clone()))
+                .collect::<Vec<_>>(),
+            Fields::Unit => Vec::new(),
+        },
+        _ => panic!("`VertexFormat` can be derived only for structs"),
+    };
+
+    if fields.is_empty() {
+        panic!(
+            "`VertexFormat` can't be derived for `{}` without fields",
+            name
+        );
+    }
+
+    let members = fields.iter().map(|(m, _)| m).collect::<Vec<_>>();
+    let types = fields.iter().map(|(_, t)| t).collect::<Vec<_>>();
+
+    let with_attribute = (0..types.len())
+        .map(|i| {
+            let ty = types[i];
+            let preceding = &types[..i];
+            quote! {
+                impl ::gfx_mesh::WithAttribute<#ty> for #name {
+                    const ELEMENT: ::gfx_mesh::__derive::Element<::gfx_mesh::__derive::Format> =
+                        ::gfx_mesh::__derive::Element {
+                            offset: 0 #(+ <#preceding as ::gfx_mesh::Attribute>::SIZE)*,
+                            format: <#ty as ::gfx_mesh::__derive::AsFormat>::SELF,
+                        };
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // `quote!` consumes iterated values.
+    let names = types.clone();
+    let elements = types.clone();
+    let sizes = types.clone();
+    let field_sizes = types.clone();
+
+    quote! {
+        unsafe impl ::gfx_mesh::__derive::Pod for #name {}
+
+        impl ::gfx_mesh::AsVertexFormat for #name {
+            const VERTEX_FORMAT: ::gfx_mesh::VertexFormat<'static> = ::gfx_mesh::VertexFormat {
+                attributes: ::std::borrow::Cow::Borrowed(&[
+                    #(
+                        ::gfx_mesh::VertexAttribute {
+                            name: ::std::borrow::Cow::Borrowed(<#names as ::gfx_mesh::Attribute>::NAME),
+                            element: <Self as ::gfx_mesh::WithAttribute<#elements>>::ELEMENT,
+                        },
+                    )*
+                ]),
+                stride: 0 #(+ <#sizes as ::gfx_mesh::Attribute>::SIZE)*,
+                rate: 0,
+            };
+        }
+
+        #(#with_attribute)*
+
+        impl #name {
+            /// Fails to compile if any field's size differs from `Attribute::SIZE`
+            /// or if the struct contains padding.
+            #[doc(hidden)]
+            #[allow(dead_code)]
+            fn __gfx_mesh_assert_layout(vertex: #name) {
+                unsafe {
+                    #(
+                        let _: [u8; <#field_sizes as ::gfx_mesh::Attribute>::SIZE as usize] =
+                            ::std::mem::transmute(vertex.#members);
+                    )*
+                    let _: [u8; 0 #(+ <#types as ::gfx_mesh::Attribute>::SIZE as usize)*] =
+                        ::std::mem::transmute(vertex);
+                }
+            }
+        }
+    }
+}
+
+/// Collect hints of all `#[repr(..)]` attributes of the type.
+fn repr_hints(input: &DeriveInput) -> Vec<String> {
+    input
+        .attrs
+        .iter()
+        .filter_map(|attr| attr.parse_meta().ok())
+        .filter_map(|meta| match meta {
+            Meta::List(ref list) if list.ident == "repr" => Some(
+                list.nested
+                    .iter()
+                    .filter_map(|nested| match *nested {
+                        NestedMeta::Meta(ref meta) => Some(meta.name().to_string()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
@@ -0,0 +1,93 @@
+//! Built-in vertex formats re-expressed with `#[derive(VertexFormat)]`
+//! must have the same layout as the hand-written ones.
+
+#[macro_use]
+extern crate gfx_mesh;
+
+use std::mem::size_of;
+
+use gfx_mesh::{
+    AsVertexFormat, Color, HalfTexCoord, JointIndices, JointWeights, Normal, PackedColor,
+    PackedNormal, PackedTangent, Position, Tangent, TexCoord,
+};
+
+macro_rules! builtin {
+    ($test:ident, $builtin:ident { $($field:ident: $ty:ident),* }) => {
+        #[test]
+        fn $test() {
+            #[repr(C)]
+            #[derive(Clone, Copy, Debug, PartialEq, VertexFormat)]
+            struct Derived {
+                $($field: $ty,)*
+            }
+
+            assert_eq!(Derived::VERTEX_FORMAT, gfx_mesh::$builtin::VERTEX_FORMAT);
+            assert_eq!(size_of::<Derived>(), size_of::<gfx_mesh::$builtin>());
+        }
+    };
+}
+
+builtin!(
+    pos_color,
+    PosColor {
+        position: Position,
+        color: Color
+    }
+);
+builtin!(
+    pos_norm,
+    PosNorm {
+        position: Position,
+        normal: Normal
+    }
+);
+builtin!(
+    pos_tex,
+    PosTex {
+        position: Position,
+        tex_coord: TexCoord
+    }
+);
+builtin!(
+    pos_norm_tex,
+    PosNormTex {
+        position: Position,
+        normal: Normal,
+        tex_coord: TexCoord
+    }
+);
+builtin!(
+    pos_norm_tang_tex,
+    PosNormTangTex {
+        position: Position,
+        normal: Normal,
+        tangent: Tangent,
+        tex_coord: TexCoord
+    }
+);
+builtin!(
+    pos_color_packed,
+    PosColorPacked {
+        position: Position,
+        color: PackedColor
+    }
+);
+builtin!(
+    pos_norm_tang_tex_packed,
+    PosNormTangTexPacked {
+        position: Position,
+        normal: PackedNormal,
+        tangent: PackedTangent,
+        tex_coord: HalfTexCoord
+    }
+);
+builtin!(
+    pos_norm_tex_skin,
+    PosNormTexSkin {
+        position: Position,
+        normal: Normal,
+        tex_coord: TexCoord,
+        joint_indices: JointIndices,
+        joint_weights: JointWeights
+    }
+);
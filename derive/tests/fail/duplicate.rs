@@ -0,0 +1,15 @@
+#[macro_use]
+extern crate gfx_mesh;
+
+use gfx_mesh::Position;
+
+type Origin = gfx_mesh::Position;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, VertexFormat)]
+struct Vertex {
+    position: Position,
+    origin: Origin,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+#[macro_use]
+extern crate gfx_mesh;
+
+use gfx_mesh::{Normal, Position};
+
+#[derive(Clone, Copy, Debug, PartialEq, VertexFormat)]
+struct Vertex {
+    position: Position,
+    normal: Normal,
+}
+
+fn main() {}
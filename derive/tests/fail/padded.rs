@@ -0,0 +1,29 @@
+extern crate gfx_hal as hal;
+#[macro_use]
+extern crate gfx_mesh;
+
+use hal::format::{AsFormat, Format};
+use hal::memory::Pod;
+use gfx_mesh::{Attribute, Position};
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Flags(u16);
+
+impl AsFormat for Flags {
+    const SELF: Format = Format::R16Uint;
+}
+unsafe impl Pod for Flags {}
+impl Attribute for Flags {
+    const NAME: &'static str = "flags";
+    const SIZE: u32 = 2;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, VertexFormat)]
+struct Vertex {
+    flags: Flags,
+    position: Position,
+}
+
+fn main() {}
@@ -0,0 +1,51 @@
+extern crate gfx_hal as hal;
+#[macro_use]
+extern crate gfx_mesh;
+
+use gfx_mesh::{AsVertexFormat, Attribute, Color, Normal, Position, TexCoord, WithAttribute};
+use hal::format::AsFormat;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, VertexFormat)]
+struct Vertex {
+    position: Position,
+    color: Color,
+    normal: Normal,
+    tex_coord: TexCoord,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, VertexFormat)]
+struct Tuple(TexCoord, Position);
+
+fn main() {
+    let format = Vertex::VERTEX_FORMAT;
+    assert_eq!(format.stride, 48);
+    assert_eq!(format.stride as usize, std::mem::size_of::<Vertex>());
+    assert_eq!(format.rate, 0);
+
+    let names = format
+        .attributes
+        .iter()
+        .map(|attribute| &*attribute.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["position", "color", "normal", "tex_coord"]);
+
+    let offsets = format
+        .attributes
+        .iter()
+        .map(|attribute| attribute.element.offset)
+        .collect::<Vec<_>>();
+    assert_eq!(offsets, [0, 12, 28, 40]);
+
+    assert_eq!(<Vertex as WithAttribute<Normal>>::ELEMENT.offset, 28);
+    assert_eq!(
+        <Vertex as WithAttribute<Normal>>::ELEMENT.format,
+        Normal::SELF
+    );
+    assert_eq!(Vertex::attribute::<TexCoord>().offset, 40);
+
+    let format = Tuple::VERTEX_FORMAT;
+    assert_eq!(format.stride, TexCoord::SIZE + Position::SIZE);
+    assert_eq!(<Tuple as WithAttribute<Position>>::ELEMENT.offset, 8);
+}
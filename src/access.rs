@@ -0,0 +1,417 @@
+//!
+//! Typed access to attributes of vertex data in `MeshBuilder`.
+//!
+
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use hal::format::Format;
+
+use mesh::{check_buffer_size, IndexWidth, MeshBuilder, MeshBuilderError};
+use utils::{read_pod, write_pod};
+use vertex::Attribute;
+
+/// Error returned when attribute can't be accessed.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum AccessError {
+    /// No per-vertex buffer has attribute with the name.
+    #[fail(display = "Attribute \"{}\" not found", name)]
+    MissingAttribute {
+        /// Name of the attribute.
+        name: &'static str,
+    },
+
+    /// Attribute with the name is stored in different format.
+    #[fail(
+        display = "Attribute \"{}\" is stored as {:?} instead of {:?}",
+        name, found, expected
+    )]
+    FormatMismatch {
+        /// Name of the attribute.
+        name: &'static str,
+        /// Format of the attribute type.
+        expected: Format,
+        /// Format of the stored attribute.
+        found: Format,
+    },
+
+    /// Vertex buffer with the attribute is malformed.
+    #[fail(display = "Vertex data is invalid: {}", _0)]
+    InvalidVertices(#[cause] MeshBuilderError),
+
+    /// Number of values differs from number of vertices.
+    #[fail(
+        display = "Attribute \"{}\" has {} values while there are {} vertices",
+        name, found, expected
+    )]
+    CountMismatch {
+        /// Name of the attribute.
+        name: &'static str,
+        /// Number of vertices.
+        expected: usize,
+        /// Number of values.
+        found: usize,
+    },
+}
+
+/// Iterator over values of an attribute stored in vertex buffer.
+#[derive(Clone, Debug)]
+pub struct AttributeIter<'b, A> {
+    bytes: &'b [u8],
+    offset: usize,
+    stride: usize,
+    index: usize,
+    count: usize,
+    marker: PhantomData<A>,
+}
+
+impl<'b, A> Iterator for AttributeIter<'b, A>
+where
+    A: Attribute,
+{
+    type Item = A;
+
+    fn next(&mut self) -> Option<A> {
+        if self.index < self.count {
+            let value = read_pod(self.bytes, self.index * self.stride + self.offset);
+            self.index += 1;
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.count - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<'b, A> ExactSizeIterator for AttributeIter<'b, A> where A: Attribute {}
+
+/// Iterator over indices stored in `MeshBuilder`.
+/// Indices of any width are widened to `u32`.
+#[derive(Clone, Debug)]
+pub struct IndexIter<'b> {
+    bytes: &'b [u8],
+    width: IndexWidth,
+    index: usize,
+    count: usize,
+}
+
+impl<'b> IndexIter<'b> {
+    pub(crate) fn new(bytes: &'b [u8], width: IndexWidth) -> Self {
+        IndexIter {
+            bytes,
+            width,
+            index: 0,
+            count: bytes.len() / width.size(),
+        }
+    }
+}
+
+impl<'b> Iterator for IndexIter<'b> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.index < self.count {
+            let offset = self.index * self.width.size();
+            let value = match self.width {
+                IndexWidth::U8 => self.bytes[offset] as u32,
+                IndexWidth::U16 => read_pod::<u16>(self.bytes, offset) as u32,
+                IndexWidth::U32 => read_pod(self.bytes, offset),
+            };
+            self.index += 1;
+            Some(value)
+        } else {
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.count - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<'b> ExactSizeIterator for IndexIter<'b> {}
+
+/// Mutable accessor to values of an attribute stored in vertex buffer.
+///
+/// Values are copied in and out because attributes
+/// in interleaved buffers are not necessarily aligned.
+#[derive(Debug)]
+pub struct AttributeMut<'b, A> {
+    bytes: &'b mut [u8],
+    offset: usize,
+    stride: usize,
+    count: usize,
+    marker: PhantomData<A>,
+}
+
+impl<'b, A> AttributeMut<'b, A>
+where
+    A: Attribute,
+{
+    /// Number of vertices.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Check if there are no vertices.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Get value of the vertex.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> A {
+        assert!(index < self.count, "Vertex index is out of bounds");
+        read_pod(self.bytes, index * self.stride + self.offset)
+    }
+
+    /// Set value of the vertex.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, value: A) {
+        assert!(index < self.count, "Vertex index is out of bounds");
+        write_pod(self.bytes, index * self.stride + self.offset, value);
+    }
+
+    /// Replace value of every vertex with result of the function.
+    pub fn update<F>(&mut self, mut f: F)
+    where
+        F: FnMut(A) -> A,
+    {
+        for index in 0..self.count {
+            let value = f(self.get(index));
+            self.set(index, value);
+        }
+    }
+
+    /// Iterate over values.
+    pub fn iter<'c>(&'c self) -> AttributeIter<'c, A> {
+        AttributeIter {
+            bytes: self.bytes,
+            offset: self.offset,
+            stride: self.stride,
+            index: 0,
+            count: self.count,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Iterate over indices as `u32` whatever width they are stored with.
+    /// Returns `None` if there is no index buffer.
+    pub fn indices<'b>(&'b self) -> Option<IndexIter<'b>> {
+        self.indices
+            .as_ref()
+            .map(|&(ref indices, width)| IndexIter::new(indices, width))
+    }
+
+    /// Iterate over values of attribute `A` in per-vertex buffers.
+    /// Attribute is resolved by name and must be stored in the format of `A`.
+    /// Fails if the buffer with the attribute has zero stride
+    /// or size that is not multiple of the stride.
+    pub fn attribute<'b, A>(&'b self) -> Result<AttributeIter<'b, A>, AccessError>
+    where
+        A: Attribute,
+    {
+        let (index, offset) = self.locate_attribute::<A>()?;
+        let (ref vertices, ref format) = self.vertices[index];
+        let stride = format.stride as usize;
+        Ok(AttributeIter {
+            bytes: vertices,
+            offset,
+            stride,
+            index: 0,
+            count: vertices.len() / stride,
+            marker: PhantomData,
+        })
+    }
+
+    /// Get mutable accessor to values of attribute `A` in per-vertex buffers.
+    /// Attribute is resolved by name and must be stored in the format of `A`.
+    /// Borrowed vertex data is copied on first access.
+    /// Fails on the same conditions as `MeshBuilder::attribute`.
+    pub fn attribute_mut<'b, A>(&'b mut self) -> Result<AttributeMut<'b, A>, AccessError>
+    where
+        A: Attribute,
+    {
+        let (index, offset) = self.locate_attribute::<A>()?;
+        let (ref mut vertices, ref format) = self.vertices[index];
+        let stride = format.stride as usize;
+        let bytes = Cow::to_mut(vertices);
+        let count = bytes.len() / stride;
+        Ok(AttributeMut {
+            bytes,
+            offset,
+            stride,
+            count,
+            marker: PhantomData,
+        })
+    }
+
+    /// Overwrite values of attribute `A` or add them as new buffer if there is no such attribute.
+    /// Fails if number of values doesn't match number of vertices.
+    pub(crate) fn replace_attribute<A>(&mut self, values: Vec<A>) -> Result<(), AccessError>
+    where
+        A: Attribute + 'a,
+    {
+        let count = self.vertex_count().map_err(AccessError::InvalidVertices)?;
+        match count {
+            Some(count) if count as usize != values.len() => {
+                return Err(AccessError::CountMismatch {
+                    name: A::NAME,
+                    expected: count as usize,
+                    found: values.len(),
+                });
+            }
+            _ => {}
+        }
+
+        match self.attribute_mut::<A>() {
+            Ok(mut attribute) => {
+                for (index, value) in values.into_iter().enumerate() {
+                    attribute.set(index, value);
+                }
+                return Ok(());
+            }
+            Err(AccessError::MissingAttribute { .. }) => {}
+            Err(error) => return Err(error),
+        }
+        self.push_vertices(values);
+        Ok(())
+    }
+
+    /// Find index of per-vertex buffer and offset of the attribute `A` in it.
+    fn locate_attribute<A>(&self) -> Result<(usize, usize), AccessError>
+    where
+        A: Attribute,
+    {
+        let mut found = None;
+        for (index, (_, format)) in self.vertices.iter().enumerate() {
+            if format.is_per_instance() {
+                continue;
+            }
+            for attribute in format.attributes.iter() {
+                if attribute.name != A::NAME {
+                    continue;
+                }
+                if attribute.element.format == A::SELF {
+                    let (ref vertices, ref format) = self.vertices[index];
+                    check_buffer_size(index, vertices, format)
+                        .map_err(AccessError::InvalidVertices)?;
+                    return Ok((index, attribute.element.offset as usize));
+                }
+                found = found.or(Some(attribute.element.format));
+            }
+        }
+
+        Err(match found {
+            Some(found) => AccessError::FormatMismatch {
+                name: A::NAME,
+                expected: A::SELF,
+                found,
+            },
+            None => AccessError::MissingAttribute { name: A::NAME },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::format::AsFormat;
+    use vertex::{AsVertexFormat, Normal, Position, VertexFormat, VertexFormatError};
+
+    fn positions() -> MeshBuilder<'static> {
+        MeshBuilder::new()
+            .with_vertices(vec![Position([1.0, 2.0, 3.0]), Position([4.0, 5.0, 6.0])])
+            .unwrap()
+    }
+
+    #[test]
+    fn attribute() {
+        let mut builder = positions();
+        assert_eq!(
+            builder.attribute::<Position>().unwrap().collect::<Vec<_>>(),
+            vec![Position([1.0, 2.0, 3.0]), Position([4.0, 5.0, 6.0])]
+        );
+        builder
+            .attribute_mut::<Position>()
+            .unwrap()
+            .update(|Position(p)| Position([p[0], p[1], -p[2]]));
+        assert_eq!(
+            builder.attribute::<Position>().unwrap().nth(1),
+            Some(Position([4.0, 5.0, -6.0]))
+        );
+        assert_eq!(
+            builder.attribute::<Normal>().err(),
+            Some(AccessError::MissingAttribute { name: "normal" })
+        );
+    }
+
+    #[test]
+    fn zero_stride() {
+        let mut builder = MeshBuilder::new();
+        builder.vertices.push((
+            Cow::Owned(vec![0; 12]),
+            VertexFormat {
+                stride: 0,
+                ..Position::VERTEX_FORMAT
+            },
+        ));
+        let error = AccessError::InvalidVertices(MeshBuilderError::InvalidFormat {
+            index: 0,
+            error: VertexFormatError::ZeroStride,
+        });
+        assert_eq!(builder.attribute::<Position>().err(), Some(error.clone()));
+        assert_eq!(builder.attribute_mut::<Position>().err(), Some(error));
+    }
+
+    #[test]
+    fn replace_attribute() {
+        let mut builder = positions();
+        assert_eq!(
+            builder.replace_attribute(vec![Normal([0.0, 0.0, 1.0])]),
+            Err(AccessError::CountMismatch {
+                name: "normal",
+                expected: 2,
+                found: 1,
+            })
+        );
+        assert_eq!(
+            builder.replace_attribute(vec![Position([0.0; 3]); 3]),
+            Err(AccessError::CountMismatch {
+                name: "position",
+                expected: 2,
+                found: 3,
+            })
+        );
+        assert_eq!(builder.vertices.len(), 1);
+
+        builder
+            .replace_attribute(vec![Normal([0.0, 0.0, 1.0]); 2])
+            .unwrap();
+        builder
+            .replace_attribute(vec![Position([0.0; 3]); 2])
+            .unwrap();
+        assert_eq!(builder.vertices.len(), 2);
+        assert_eq!(
+            builder.vertices[1].1.attributes[0].element.format,
+            Normal::SELF
+        );
+        assert!(builder
+            .attribute::<Position>()
+            .unwrap()
+            .all(|p| p == Position([0.0; 3])));
+    }
+}
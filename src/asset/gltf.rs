@@ -0,0 +1,807 @@
+//!
+//! Import of glTF 2.0 meshes.
+//!
+
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use gltf::mesh::Mode;
+use gltf::{self, accessor, buffer, Accessor, Document, Semantic};
+use hal::format::Format;
+use hal::Primitive;
+
+use mesh::{MeshBuilder, MeshBuilderError};
+use morph::{MorphError, MorphTarget};
+use utils::{narrow_indices, read_pod};
+use vertex::{
+    indexed_name, Attribute, Color, DynamicAttribute, JointIndices, JointWeights, Normal, Position,
+    Tangent, TexCoord,
+};
+
+/// Error returned by `load_gltf`.
+#[derive(Debug, Fail)]
+pub enum GltfError {
+    /// File or its buffers can't be read or parsed.
+    #[fail(display = "Failed to load glTF: {}", _0)]
+    Gltf(#[cause] gltf::Error),
+
+    /// Buffer URI has unsupported scheme or can't be decoded.
+    /// External files are supported only when loading from file.
+    #[fail(display = "Buffer {} has unsupported URI", buffer)]
+    UnsupportedUri {
+        /// Index of the buffer.
+        buffer: usize,
+    },
+
+    /// Buffer data is shorter than buffer's `byteLength`.
+    #[fail(
+        display = "Buffer {} has {} bytes instead of {}",
+        buffer, found, expected
+    )]
+    BufferLength {
+        /// Index of the buffer.
+        buffer: usize,
+        /// Length declared in the document.
+        expected: usize,
+        /// Length of the data.
+        found: usize,
+    },
+
+    /// Accessor has type that can't be used for vertex attributes or indices.
+    #[fail(
+        display = "Accessor {} of type {:?} {:?} is not supported",
+        accessor, data_type, dimensions
+    )]
+    UnsupportedAccessor {
+        /// Index of the accessor.
+        accessor: usize,
+        /// Component type of the accessor.
+        data_type: accessor::DataType,
+        /// Dimensions of the accessor.
+        dimensions: accessor::Dimensions,
+    },
+
+    /// Accessor refers to data outside of its buffer.
+    #[fail(display = "Accessor {} is out of buffer bounds", accessor)]
+    OutOfBounds {
+        /// Index of the accessor.
+        accessor: usize,
+    },
+
+    /// Accessor without buffer view doesn't have vertex count of the primitive.
+    /// Vertex count is known only if `POSITION` accessor has buffer view.
+    #[fail(
+        display = "Accessor {} without buffer view has {} elements instead of {:?}",
+        accessor, count, expected
+    )]
+    CountMismatch {
+        /// Index of the accessor.
+        accessor: usize,
+        /// Number of elements in the accessor.
+        count: usize,
+        /// Number of vertices of the primitive.
+        expected: Option<usize>,
+    },
+
+    /// Primitive's vertex data is malformed.
+    #[fail(display = "Primitive's vertex data is invalid: {}", _0)]
+    InvalidVertices(#[cause] MeshBuilderError),
+
+    /// Primitive's morph targets are malformed.
+    #[fail(display = "Primitive's morph targets are invalid: {}", _0)]
+    InvalidMorphTarget(#[cause] MorphError),
+}
+
+/// Mesh primitive imported from glTF.
+#[derive(Clone, Debug)]
+pub struct GltfPrimitive {
+    /// Index of the mesh.
+    pub mesh: usize,
+
+    /// Name of the mesh.
+    pub mesh_name: Option<String>,
+
+    /// Index of the primitive in the mesh.
+    pub primitive: usize,
+
+    /// Index of the material of the primitive.
+    /// `None` for default material.
+    pub material: Option<usize>,
+
+    /// Names of nodes that instantiate the mesh.
+    pub node_names: Vec<String>,
+
+    /// Builder filled with primitive's data.
+    /// Each accessor becomes separate vertex buffer.
+    pub builder: MeshBuilder<'static>,
+}
+
+/// Load all mesh primitives from `.gltf` or `.glb` file.
+///
+/// Buffers may be embedded (data URIs or GLB binary chunk) or external files
+/// relative to the asset or with `file:` URIs. Other URI schemes are not supported.
+///
+/// Attributes are named after built-in attributes (`TEXCOORD_1` becomes `tex_coord_1` etc.)
+/// and custom attributes (`_BATCHID`) keep their names. Formats follow accessors' types,
+/// so normalized integer attributes stay packed.
+/// Sparse accessors are expanded. `LINE_LOOP` and `TRIANGLE_FAN` primitives
+/// are converted to line strips and triangle lists.
+/// glTF data is little-endian and is converted to byte order of the host.
+///
+/// Accessors are checked against their buffer views before data is allocated.
+/// Vertex attributes and morph targets without buffer view must have
+/// the count of `POSITION` accessor, which must have buffer view itself.
+pub fn load_gltf<P>(path: P) -> Result<Vec<GltfPrimitive>, GltfError>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let gltf = gltf::Gltf::open(path).map_err(GltfError::Gltf)?;
+    import(gltf, path.parent())
+}
+
+/// Import primitives of parsed glTF with external buffers relative to `base`.
+fn import(gltf: gltf::Gltf, base: Option<&Path>) -> Result<Vec<GltfPrimitive>, GltfError> {
+    let gltf::Gltf { document, blob } = gltf;
+    let buffers = document
+        .buffers()
+        .map(|buffer| load_buffer(&buffer, base, blob.as_ref()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let buffers = buffers.iter().map(|data| &data[..]).collect::<Vec<_>>();
+
+    let mut primitives = Vec::new();
+    for mesh in document.meshes() {
+        let node_names = node_names(&document, mesh.index());
+        let weights = mesh.weights().unwrap_or(&[]);
+        for primitive in mesh.primitives() {
+            primitives.push(GltfPrimitive {
+                mesh: mesh.index(),
+                mesh_name: mesh.name().map(str::to_string),
+                primitive: primitive.index(),
+                material: primitive.material().index(),
+                node_names: node_names.clone(),
+                builder: load_primitive(&primitive, &buffers, weights)?,
+            });
+        }
+    }
+    Ok(primitives)
+}
+
+/// Load data of the buffer from GLB binary chunk, data URI or file relative to `base`.
+fn load_buffer(
+    buffer: &buffer::Buffer,
+    base: Option<&Path>,
+    blob: Option<&Vec<u8>>,
+) -> Result<Vec<u8>, GltfError> {
+    let unsupported = || GltfError::UnsupportedUri {
+        buffer: buffer.index(),
+    };
+    let data = match buffer.source() {
+        buffer::Source::Bin => blob.cloned().unwrap_or_default(),
+        buffer::Source::Uri(uri) => {
+            if let Some(data) = uri.strip_prefix("data:") {
+                let (_, base64) = data.split_once(";base64,").ok_or_else(unsupported)?;
+                decode_base64(base64).ok_or_else(unsupported)?
+            } else {
+                let path = match uri.strip_prefix("file:") {
+                    Some(path) => PathBuf::from(path.trim_start_matches("//")),
+                    None if !uri.contains(':') => {
+                        let path = decode_percents(uri).ok_or_else(unsupported)?;
+                        base.ok_or_else(unsupported)?.join(path)
+                    }
+                    None => return Err(unsupported()),
+                };
+                fs::read(path).map_err(|err| GltfError::Gltf(gltf::Error::Io(err)))?
+            }
+        }
+    };
+
+    if data.len() < buffer.length() {
+        return Err(GltfError::BufferLength {
+            buffer: buffer.index(),
+            expected: buffer.length(),
+            found: data.len(),
+        });
+    }
+    Ok(data)
+}
+
+/// Decode standard base64 with optional padding.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim_end_matches('=').as_bytes();
+    let mut data = Vec::with_capacity(text.len() / 4 * 3 + 2);
+    let mut value = 0u32;
+    for (i, &c) in text.iter().enumerate() {
+        let digit = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        value = value << 6 | digit as u32;
+        if i % 4 == 3 {
+            data.extend_from_slice(&value.to_be_bytes()[1..]);
+            value = 0;
+        }
+    }
+    match text.len() % 4 {
+        0 => {}
+        2 => data.push((value >> 4) as u8),
+        3 => data.extend_from_slice(&((value >> 2) as u16).to_be_bytes()),
+        _ => return None,
+    }
+    Some(data)
+}
+
+/// Decode `%XX` escapes of URI reference.
+fn decode_percents(uri: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(uri.len());
+    let mut rest = uri.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn node_names(document: &Document, mesh: usize) -> Vec<String> {
+    document
+        .nodes()
+        .filter(|node| node.mesh().map_or(false, |m| m.index() == mesh))
+        .filter_map(|node| node.name().map(str::to_string))
+        .collect()
+}
+
+fn load_primitive(
+    primitive: &gltf::Primitive,
+    buffers: &[&[u8]],
+    weights: &[f32],
+) -> Result<MeshBuilder<'static>, GltfError> {
+    let mut builder = MeshBuilder::new();
+
+    // Count of accessors without buffer view is bounded only by this.
+    let expected = primitive
+        .get(&Semantic::Positions)
+        .filter(|accessor| accessor.view().is_some())
+        .map(|accessor| accessor.count());
+
+    let mut vertex_count = 0;
+    for (semantic, accessor) in primitive.attributes() {
+        let name = attribute_name(semantic);
+        let format = accessor_format(&accessor)?;
+        let data = read_accessor(&accessor, buffers, expected)?;
+        vertex_count = accessor.count();
+        builder
+            .add_attribute_data(DynamicAttribute::new(name, format), data)
+            .map_err(GltfError::InvalidVertices)?;
+    }
+
+    let indices = match primitive.indices() {
+        Some(accessor) => Some(read_indices(&accessor, buffers)?),
+        None => None,
+    };
+
+    let sequential = || (0..vertex_count as u32).collect::<Vec<_>>();
+    let (indices, prim) = match primitive.mode() {
+        Mode::Points => (indices, Primitive::PointList),
+        Mode::Lines => (indices, Primitive::LineList),
+        Mode::LineStrip => (indices, Primitive::LineStrip),
+        Mode::Triangles => (indices, Primitive::TriangleList),
+        Mode::TriangleStrip => (indices, Primitive::TriangleStrip),
+        Mode::LineLoop => {
+            let mut indices = indices.unwrap_or_else(sequential);
+            if let Some(&first) = indices.first() {
+                indices.push(first);
+            }
+            (Some(indices), Primitive::LineStrip)
+        }
+        Mode::TriangleFan => {
+            let fan = indices.unwrap_or_else(sequential);
+            let indices = (2..fan.len())
+                .flat_map(|i| vec![fan[0], fan[i - 1], fan[i]])
+                .collect();
+            (Some(indices), Primitive::TriangleList)
+        }
+    };
+
+    if let Some(indices) = indices {
+        builder.set_indices(narrow_indices(indices));
+    }
+    builder.set_prim_type(prim);
+    add_morph_targets(builder, primitive, buffers, weights)
+}
+
+fn add_morph_targets(
+    mut builder: MeshBuilder<'static>,
+    primitive: &gltf::Primitive,
+    buffers: &[&[u8]],
+    weights: &[f32],
+) -> Result<MeshBuilder<'static>, GltfError> {
+    let expected = builder
+        .vertex_count()
+        .map_err(GltfError::InvalidVertices)?
+        .map(|count| count as usize);
+    for (index, target) in primitive.morph_targets().enumerate() {
+        let deltas = |accessor: Option<Accessor>| match accessor {
+            Some(accessor) => read_deltas(&accessor, buffers, expected).map(Some),
+            None => Ok(None),
+        };
+
+        let mut morph = MorphTarget::new().with_weight(weights.get(index).cloned().unwrap_or(0.0));
+        morph.positions = deltas(target.positions())?.map(Into::into);
+        morph.normals = deltas(target.normals())?.map(Into::into);
+        morph.tangents = deltas(target.tangents())?.map(Into::into);
+        builder
+            .add_morph_target(morph)
+            .map_err(GltfError::InvalidMorphTarget)?;
+    }
+    Ok(builder)
+}
+
+fn attribute_name(semantic: Semantic) -> Cow<'static, str> {
+    match semantic {
+        Semantic::Positions => Position::NAME.into(),
+        Semantic::Normals => Normal::NAME.into(),
+        Semantic::Tangents => Tangent::NAME.into(),
+        Semantic::Colors(set) => indexed_name(Color::NAME, set),
+        Semantic::TexCoords(set) => indexed_name(TexCoord::NAME, set),
+        Semantic::Joints(set) => indexed_name(JointIndices::NAME, set),
+        Semantic::Weights(set) => indexed_name(JointWeights::NAME, set),
+        Semantic::Extras(name) => format!("_{}", name).into(),
+    }
+}
+
+fn accessor_format(accessor: &Accessor) -> Result<Format, GltfError> {
+    use gltf::accessor::DataType::*;
+    use gltf::accessor::Dimensions::*;
+
+    let format = match (
+        accessor.data_type(),
+        accessor.dimensions(),
+        accessor.normalized(),
+    ) {
+        (F32, Scalar, _) => Format::R32Float,
+        (F32, Vec2, _) => Format::Rg32Float,
+        (F32, Vec3, _) => Format::Rgb32Float,
+        (F32, Vec4, _) => Format::Rgba32Float,
+        (U32, Scalar, false) => Format::R32Uint,
+        (U32, Vec2, false) => Format::Rg32Uint,
+        (U32, Vec3, false) => Format::Rgb32Uint,
+        (U32, Vec4, false) => Format::Rgba32Uint,
+
+        (U16, Scalar, false) => Format::R16Uint,
+        (U16, Vec2, false) => Format::Rg16Uint,
+        (U16, Vec3, false) => Format::Rgb16Uint,
+        (U16, Vec4, false) => Format::Rgba16Uint,
+        (U16, Scalar, true) => Format::R16Unorm,
+        (U16, Vec2, true) => Format::Rg16Unorm,
+        (U16, Vec3, true) => Format::Rgb16Unorm,
+        (U16, Vec4, true) => Format::Rgba16Unorm,
+        (I16, Scalar, false) => Format::R16Int,
+        (I16, Vec2, false) => Format::Rg16Int,
+        (I16, Vec3, false) => Format::Rgb16Int,
+        (I16, Vec4, false) => Format::Rgba16Int,
+        (I16, Scalar, true) => Format::R16Inorm,
+        (I16, Vec2, true) => Format::Rg16Inorm,
+        (I16, Vec3, true) => Format::Rgb16Inorm,
+        (I16, Vec4, true) => Format::Rgba16Inorm,
+
+        (U8, Scalar, false) => Format::R8Uint,
+        (U8, Vec2, false) => Format::Rg8Uint,
+        (U8, Vec3, false) => Format::Rgb8Uint,
+        (U8, Vec4, false) => Format::Rgba8Uint,
+        (U8, Scalar, true) => Format::R8Unorm,
+        (U8, Vec2, true) => Format::Rg8Unorm,
+        (U8, Vec3, true) => Format::Rgb8Unorm,
+        (U8, Vec4, true) => Format::Rgba8Unorm,
+        (I8, Scalar, false) => Format::R8Int,
+        (I8, Vec2, false) => Format::Rg8Int,
+        (I8, Vec3, false) => Format::Rgb8Int,
+        (I8, Vec4, false) => Format::Rgba8Int,
+        (I8, Scalar, true) => Format::R8Inorm,
+        (I8, Vec2, true) => Format::Rg8Inorm,
+        (I8, Vec3, true) => Format::Rgb8Inorm,
+        (I8, Vec4, true) => Format::Rgba8Inorm,
+
+        _ => return Err(unsupported(accessor)),
+    };
+    Ok(format)
+}
+
+fn unsupported(accessor: &Accessor) -> GltfError {
+    GltfError::UnsupportedAccessor {
+        accessor: accessor.index(),
+        data_type: accessor.data_type(),
+        dimensions: accessor.dimensions(),
+    }
+}
+
+/// Read tightly packed elements of the accessor with sparse values applied.
+/// Components are converted from little-endian to byte order of the host.
+///
+/// Count of accessor with buffer view is checked against the view before allocation.
+/// Accessor without buffer view must have `expected` count.
+fn read_accessor(
+    accessor: &Accessor,
+    buffers: &[&[u8]],
+    expected: Option<usize>,
+) -> Result<Vec<u8>, GltfError> {
+    let out_of_bounds = || GltfError::OutOfBounds {
+        accessor: accessor.index(),
+    };
+    let size = accessor.size();
+    let count = accessor.count();
+
+    let view = match accessor.view() {
+        Some(view) => {
+            let stride = view.stride().unwrap_or(size);
+            let bytes = view_bytes(&view, buffers).ok_or_else(out_of_bounds)?;
+            let end = match count.checked_sub(1) {
+                Some(last) => last
+                    .checked_mul(stride)
+                    .and_then(|offset| offset.checked_add(accessor.offset()))
+                    .and_then(|offset| offset.checked_add(size)),
+                None => Some(0),
+            };
+            if end.map_or(true, |end| end > bytes.len()) {
+                return Err(out_of_bounds());
+            }
+            Some((bytes, stride))
+        }
+        None if expected == Some(count) => None,
+        None => {
+            return Err(GltfError::CountMismatch {
+                accessor: accessor.index(),
+                count,
+                expected,
+            })
+        }
+    };
+
+    let mut data = vec![0; size.checked_mul(count).ok_or_else(out_of_bounds)?];
+
+    // Accessor without buffer view is initialized with zeros.
+    if let Some((bytes, stride)) = view {
+        for index in 0..count {
+            let offset = accessor.offset() + index * stride;
+            let element = bytes.get(offset..offset + size).ok_or_else(out_of_bounds)?;
+            data[index * size..(index + 1) * size].copy_from_slice(element);
+        }
+    }
+
+    if let Some(sparse) = accessor.sparse() {
+        let indices = sparse.indices();
+        let index_bytes = view_bytes(&indices.view(), buffers).ok_or_else(out_of_bounds)?;
+        let values = sparse.values();
+        let value_bytes = view_bytes(&values.view(), buffers).ok_or_else(out_of_bounds)?;
+
+        let index_size = match indices.index_type() {
+            accessor::sparse::IndexType::U8 => 1,
+            accessor::sparse::IndexType::U16 => 2,
+            accessor::sparse::IndexType::U32 => 4,
+        };
+        for i in 0..sparse.count() {
+            let index = read_le(index_bytes, indices.offset() + i * index_size, index_size)
+                .ok_or_else(out_of_bounds)? as usize;
+            let offset = values.offset() + i * size;
+            let value = value_bytes
+                .get(offset..offset + size)
+                .ok_or_else(out_of_bounds)?;
+            data.get_mut(index * size..(index + 1) * size)
+                .ok_or_else(out_of_bounds)?
+                .copy_from_slice(value);
+        }
+    }
+
+    if cfg!(target_endian = "big") {
+        for component in data.chunks_mut(accessor.data_type().size()) {
+            component.reverse();
+        }
+    }
+    Ok(data)
+}
+
+/// Index accessor must have buffer view.
+fn read_indices(accessor: &Accessor, buffers: &[&[u8]]) -> Result<Vec<u32>, GltfError> {
+    let data = read_accessor(accessor, buffers, None)?;
+    let indices = match (accessor.data_type(), accessor.dimensions()) {
+        (accessor::DataType::U8, accessor::Dimensions::Scalar) => {
+            data.iter().map(|&index| index as u32).collect()
+        }
+        (accessor::DataType::U16, accessor::Dimensions::Scalar) => (0..accessor.count())
+            .map(|i| read_pod::<u16>(&data, i * 2) as u32)
+            .collect(),
+        (accessor::DataType::U32, accessor::Dimensions::Scalar) => (0..accessor.count())
+            .map(|i| read_pod::<u32>(&data, i * 4))
+            .collect(),
+        _ => return Err(unsupported(accessor)),
+    };
+    Ok(indices)
+}
+
+fn read_deltas(
+    accessor: &Accessor,
+    buffers: &[&[u8]],
+    expected: Option<usize>,
+) -> Result<Vec<[f32; 3]>, GltfError> {
+    match (accessor.data_type(), accessor.dimensions()) {
+        (accessor::DataType::F32, accessor::Dimensions::Vec3) => {}
+        _ => return Err(unsupported(accessor)),
+    }
+    let data = read_accessor(accessor, buffers, expected)?;
+    Ok((0..accessor.count())
+        .map(|i| read_pod(&data, i * 12))
+        .collect())
+}
+
+/// Read little-endian unsigned integer of `size` bytes.
+fn read_le(bytes: &[u8], offset: usize, size: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset + size)?;
+    Some(
+        bytes
+            .iter()
+            .rev()
+            .fold(0, |value, &byte| value << 8 | byte as u32),
+    )
+}
+
+fn view_bytes<'b>(view: &buffer::View, buffers: &[&'b [u8]]) -> Option<&'b [u8]> {
+    let buffer = buffers.get(view.buffer().index())?;
+    buffer.get(view.offset()..view.offset() + view.length())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use morph::MorphAttribute;
+
+    fn base64(bytes: &[u8]) -> String {
+        const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut result = String::new();
+        for chunk in bytes.chunks(3) {
+            let value = chunk
+                .iter()
+                .chain(&[0, 0])
+                .take(3)
+                .fold(0u32, |value, &byte| value << 8 | byte as u32);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    result.push(CHARS[(value >> (18 - 6 * i)) as usize & 63] as char);
+                } else {
+                    result.push('=');
+                }
+            }
+        }
+        result
+    }
+
+    const POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 1.0, 0.0],
+        [0.0, 1.0, 0.0],
+    ];
+
+    /// Quad as triangle fan with sparse morph target and the same vertices as line loop.
+    fn quad() -> gltf::Gltf {
+        quad_with_counts(4, 4)
+    }
+
+    /// Quad with counts of position accessor and of morph target accessor without buffer view.
+    fn quad_with_counts(positions: u64, target: u64) -> gltf::Gltf {
+        let mut data = Vec::new();
+        for component in POSITIONS.iter().flat_map(|p| p.iter()) {
+            data.extend_from_slice(&component.to_bits().to_le_bytes());
+        }
+        for index in 0..4u16 {
+            data.extend_from_slice(&index.to_le_bytes());
+        }
+        data.extend_from_slice(&[2, 0, 0, 0]);
+        for component in &[0.0f32, 0.0, 1.0] {
+            data.extend_from_slice(&component.to_bits().to_le_bytes());
+        }
+        assert_eq!(data.len(), 72);
+
+        let json = format!(
+            r#"{{
+                "asset": {{ "version": "2.0" }},
+                "buffers": [{{
+                    "byteLength": 72,
+                    "uri": "data:application/octet-stream;base64,{}"
+                }}],
+                "bufferViews": [
+                    {{ "buffer": 0, "byteOffset": 0, "byteLength": 48 }},
+                    {{ "buffer": 0, "byteOffset": 48, "byteLength": 8 }},
+                    {{ "buffer": 0, "byteOffset": 56, "byteLength": 1 }},
+                    {{ "buffer": 0, "byteOffset": 60, "byteLength": 12 }}
+                ],
+                "accessors": [
+                    {{
+                        "bufferView": 0, "componentType": 5126, "count": {}, "type": "VEC3",
+                        "min": [0, 0, 0], "max": [1, 1, 0]
+                    }},
+                    {{ "bufferView": 1, "componentType": 5123, "count": 4, "type": "SCALAR" }},
+                    {{
+                        "componentType": 5126, "count": {}, "type": "VEC3",
+                        "min": [0, 0, 0], "max": [0, 0, 1],
+                        "sparse": {{
+                            "count": 1,
+                            "indices": {{ "bufferView": 2, "componentType": 5121 }},
+                            "values": {{ "bufferView": 3 }}
+                        }}
+                    }}
+                ],
+                "meshes": [{{
+                    "name": "quad",
+                    "weights": [0.25],
+                    "primitives": [
+                        {{
+                            "attributes": {{ "POSITION": 0 }},
+                            "indices": 1,
+                            "mode": 6,
+                            "targets": [{{ "POSITION": 2 }}]
+                        }},
+                        {{ "attributes": {{ "POSITION": 0 }}, "mode": 2 }}
+                    ]
+                }}],
+                "nodes": [{{ "name": "node", "mesh": 0 }}]
+            }}"#,
+            base64(&data),
+            positions,
+            target
+        );
+        gltf::Gltf::from_slice(json.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn triangle_fan() {
+        let primitives = import(quad(), None).unwrap();
+        assert_eq!(primitives.len(), 2);
+        let fan = &primitives[0];
+        assert_eq!(fan.mesh_name, Some("quad".to_string()));
+        assert_eq!(fan.node_names, vec!["node".to_string()]);
+        assert_eq!(fan.material, None);
+        assert_eq!(fan.builder.prim, Primitive::TriangleList);
+        assert_eq!(
+            fan.builder.indices().unwrap().collect::<Vec<_>>(),
+            vec![0, 1, 2, 0, 2, 3]
+        );
+        assert_eq!(
+            fan.builder
+                .attribute::<Position>()
+                .unwrap()
+                .collect::<Vec<_>>(),
+            POSITIONS.iter().map(|&p| Position(p)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn line_loop() {
+        let primitives = import(quad(), None).unwrap();
+        let lines = &primitives[1];
+        assert_eq!(lines.primitive, 1);
+        assert_eq!(lines.builder.prim, Primitive::LineStrip);
+        assert_eq!(
+            lines.builder.indices().unwrap().collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 0]
+        );
+        assert!(lines.builder.morph_targets().is_empty());
+    }
+
+    #[test]
+    fn sparse_morph_target() {
+        let primitives = import(quad(), None).unwrap();
+        let builder = &primitives[0].builder;
+        let targets = builder.morph_targets();
+        assert_eq!(targets.len(), 1);
+        assert_eq!(targets[0].weight, 0.25);
+        assert_eq!(targets[0].attributes, vec![MorphAttribute::Position]);
+
+        let (ref deltas, ref format) = builder.vertices[1];
+        assert_eq!(format.attributes[0].name, "morph_position");
+        let deltas = (0..4)
+            .map(|i| read_pod::<[f32; 3]>(deltas, i * 12))
+            .collect::<Vec<_>>();
+        assert_eq!(deltas, vec![[0.0; 3], [0.0; 3], [0.0, 0.0, 1.0], [0.0; 3]]);
+    }
+
+    #[test]
+    fn untrusted_counts() {
+        // Count is checked against buffer view before allocation.
+        match import(quad_with_counts(1 << 40, 4), None) {
+            Err(GltfError::OutOfBounds { accessor: 0 }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+        match import(quad_with_counts(5, 4), None) {
+            Err(GltfError::OutOfBounds { accessor: 0 }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+        match import(quad_with_counts(u64::MAX, 4), None) {
+            Err(GltfError::OutOfBounds { accessor: 0 }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+
+        // Accessor without buffer view must match vertex count.
+        match import(quad_with_counts(4, 1_000_000_000_000), None) {
+            Err(GltfError::CountMismatch {
+                accessor: 2,
+                count: 1_000_000_000_000,
+                expected: Some(4),
+            }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn buffer_uris() {
+        let bytes = (0..=255u8).collect::<Vec<_>>();
+        for len in 0..8 {
+            assert_eq!(
+                decode_base64(&base64(&bytes[..len])),
+                Some(bytes[..len].to_vec())
+            );
+        }
+        assert_eq!(decode_base64(&base64(&bytes)), Some(bytes));
+        assert_eq!(decode_base64("AQID"), Some(vec![1, 2, 3]));
+        assert_eq!(decode_base64("AQ"), Some(vec![1]));
+        assert_eq!(decode_base64("A"), None);
+        assert_eq!(decode_base64("AQ*D"), None);
+
+        assert_eq!(
+            decode_percents("my%20mesh%2B.bin"),
+            Some("my mesh+.bin".to_string())
+        );
+        assert_eq!(decode_percents("mesh%2"), None);
+        assert_eq!(decode_percents("mesh%zz"), None);
+    }
+
+    #[test]
+    fn buffer_sources() {
+        let json = |uri: &str| {
+            format!(
+                r#"{{
+                    "asset": {{ "version": "2.0" }},
+                    "buffers": [{{ "byteLength": 4, "uri": "{}" }}]
+                }}"#,
+                uri
+            )
+        };
+        let parse = |json: String| gltf::Gltf::from_slice(json.as_bytes()).unwrap();
+
+        match import(parse(json("data:;base64,AQID")), None) {
+            Err(GltfError::BufferLength {
+                buffer: 0,
+                expected: 4,
+                found: 3,
+            }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+        match import(parse(json("mesh.bin")), None) {
+            Err(GltfError::UnsupportedUri { buffer: 0 }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+        match import(parse(json("https://example.com/mesh.bin")), None) {
+            Err(GltfError::UnsupportedUri { buffer: 0 }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+        assert!(import(parse(json("data:;base64,AQIDBA==")), None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn little_endian() {
+        assert_eq!(read_le(&[0x01, 0x02, 0x03, 0x04], 0, 4), Some(0x0403_0201));
+        assert_eq!(read_le(&[0x01, 0x02, 0x03], 1, 2), Some(0x0302));
+        assert_eq!(read_le(&[0x01, 0x02], 1, 2), None);
+    }
+}
@@ -0,0 +1,20 @@
+//!
+//! Importers that create `MeshBuilder`s from mesh assets.
+//!
+
+#[cfg(feature = "gltf")]
+#[allow(unknown_lints, non_local_definitions)]
+mod gltf;
+#[allow(unknown_lints, non_local_definitions)]
+mod obj;
+#[allow(unknown_lints, non_local_definitions)]
+mod ply;
+mod polygon;
+#[allow(unknown_lints, non_local_definitions)]
+mod stl;
+
+#[cfg(feature = "gltf")]
+pub use self::gltf::{load_gltf, GltfError, GltfPrimitive};
+pub use self::obj::{load_obj, parse_mtl, parse_obj, ObjError, ObjGroup, ObjMaterial, ObjMesh};
+pub use self::ply::{load_ply, parse_ply, PlyError};
+pub use self::stl::{load_stl, parse_stl, StlError};
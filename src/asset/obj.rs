@@ -0,0 +1,624 @@
+//!
+//! Import of Wavefront OBJ meshes and MTL materials.
+//!
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+use std::str::{FromStr, SplitWhitespace};
+
+use super::polygon::triangulate;
+use mesh::MeshBuilder;
+use utils::narrow_indices;
+use vertex::{PosNorm, PosNormTex, PosTex, Position};
+
+/// Error returned by OBJ and MTL parsing.
+#[derive(Debug, Fail)]
+pub enum ObjError {
+    /// File can't be read.
+    #[fail(display = "Failed to read file: {}", _0)]
+    Io(#[cause] io::Error),
+
+    /// Line can't be parsed.
+    #[fail(display = "Syntax error at line {}: {}", line, message)]
+    Syntax {
+        /// Number of the line starting from `1`.
+        line: usize,
+        /// Description of the error.
+        message: String,
+    },
+
+    /// Face refers to position, texture coordinate or normal that doesn't exist.
+    #[fail(display = "Index {} at line {} is out of range", index, line)]
+    IndexOutOfRange {
+        /// Number of the line starting from `1`.
+        line: usize,
+        /// Index as written in the file.
+        index: i64,
+    },
+}
+
+/// Range of triangles sharing object, group and material.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObjGroup {
+    /// Name of the object (`o`).
+    pub object: Option<String>,
+
+    /// Name of the group (`g`).
+    pub group: Option<String>,
+
+    /// Name of the material (`usemtl`).
+    pub material: Option<String>,
+
+    /// Range of indices in the index buffer.
+    pub indices: Range<u32>,
+}
+
+/// Mesh imported from OBJ.
+#[derive(Clone, Debug)]
+pub struct ObjMesh {
+    /// Builder with single vertex buffer and index buffer.
+    /// Vertex format is `PosNormTex`, `PosNorm`, `PosTex` or `Position`
+    /// depending on whether faces have normals and texture coordinates.
+    pub builder: MeshBuilder<'static>,
+
+    /// Ranges of triangles for objects, groups and materials.
+    pub groups: Vec<ObjGroup>,
+
+    /// Material libraries referenced by `mtllib`.
+    pub material_libraries: Vec<String>,
+}
+
+/// Material described in MTL file.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjMaterial {
+    /// Name of the material.
+    pub name: String,
+
+    /// Ambient color (`Ka`).
+    pub ambient: Option<[f32; 3]>,
+
+    /// Diffuse color (`Kd`).
+    pub diffuse: Option<[f32; 3]>,
+
+    /// Specular color (`Ks`).
+    pub specular: Option<[f32; 3]>,
+
+    /// Emissive color (`Ke`).
+    pub emissive: Option<[f32; 3]>,
+
+    /// Specular exponent (`Ns`).
+    pub shininess: Option<f32>,
+
+    /// Opacity (`d` or `1 - Tr`).
+    pub dissolve: Option<f32>,
+
+    /// Index of refraction (`Ni`).
+    pub optical_density: Option<f32>,
+
+    /// Illumination model (`illum`).
+    pub illumination: Option<u32>,
+
+    /// Ambient texture (`map_Ka`).
+    pub ambient_map: Option<String>,
+
+    /// Diffuse texture (`map_Kd`).
+    pub diffuse_map: Option<String>,
+
+    /// Specular texture (`map_Ks`).
+    pub specular_map: Option<String>,
+
+    /// Emissive texture (`map_Ke`).
+    pub emissive_map: Option<String>,
+
+    /// Normal or bump texture (`norm`, `map_Bump` or `bump`).
+    pub normal_map: Option<String>,
+
+    /// Opacity texture (`map_d`).
+    pub dissolve_map: Option<String>,
+}
+
+/// Load OBJ file and materials from its material libraries.
+/// Material libraries are looked up relative to the OBJ file.
+/// See `parse_obj`.
+pub fn load_obj<P>(path: P) -> Result<(ObjMesh, Vec<ObjMaterial>), ObjError>
+where
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let mesh = parse_obj(&read_file(path)?)?;
+    let mut materials = Vec::new();
+    for library in &mesh.material_libraries {
+        let library = match path.parent() {
+            Some(parent) => parent.join(library),
+            None => library.into(),
+        };
+        materials.extend(parse_mtl(&read_file(&library)?)?);
+    }
+    Ok((mesh, materials))
+}
+
+/// Parse OBJ source.
+///
+/// Position, texture coordinate and normal index triplets are welded
+/// into single vertex buffer. Indices are `u16` if all vertices can be addressed with them.
+/// Convex polygons are triangulated as fans and concave ones by ear clipping
+/// in the plane of the polygon.
+/// Points and lines are skipped.
+pub fn parse_obj(source: &str) -> Result<ObjMesh, ObjError> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut tex_coords: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut vertices: Vec<(usize, Option<usize>, Option<usize>)> = Vec::new();
+    let mut welded = HashMap::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    let mut groups = Vec::new();
+    let mut current = ObjGroup {
+        object: None,
+        group: None,
+        material: None,
+        indices: 0..0,
+    };
+    let mut material_libraries = Vec::new();
+
+    for (line, text) in lines(source) {
+        let mut words = text.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        match keyword {
+            "v" => positions.push(parse_floats(line, &mut words)?),
+            "vt" => {
+                let u = parse(line, words.next())?;
+                let v = match words.next() {
+                    Some(v) => parse(line, Some(v))?,
+                    None => 0.0,
+                };
+                // OBJ texture coordinates have origin at the bottom-left corner.
+                tex_coords.push([u, 1.0 - v]);
+            }
+            "vn" => normals.push(parse_floats(line, &mut words)?),
+            "f" => {
+                let mut polygon = Vec::new();
+                for word in words {
+                    let mut parts = word.split('/');
+                    let position = resolve(line, parts.next(), positions.len())?
+                        .ok_or_else(|| syntax(line, "Face vertex without position"))?;
+                    let tex_coord = resolve(line, parts.next(), tex_coords.len())?;
+                    let normal = resolve(line, parts.next(), normals.len())?;
+                    let key = (position, tex_coord, normal);
+                    let index = *welded.entry(key).or_insert_with(|| {
+                        vertices.push(key);
+                        vertices.len() as u32 - 1
+                    });
+                    polygon.push(index);
+                }
+                if polygon.len() < 3 {
+                    return Err(syntax(line, "Face has less than 3 vertices"));
+                }
+                let points = polygon
+                    .iter()
+                    .map(|&index| positions[vertices[index as usize].0])
+                    .collect::<Vec<_>>();
+                for triangle in triangulate(&points) {
+                    indices.extend(triangle.iter().map(|&corner| polygon[corner]));
+                }
+            }
+            "o" | "g" | "usemtl" => {
+                let name = words.collect::<Vec<_>>().join(" ");
+                let name = if name.is_empty() { None } else { Some(name) };
+                let end = indices.len() as u32;
+                let mut next = ObjGroup {
+                    indices: end..end,
+                    ..current.clone()
+                };
+                match keyword {
+                    "o" => {
+                        next.object = name;
+                        next.group = None;
+                    }
+                    "g" => next.group = name,
+                    _ => next.material = name,
+                }
+                current.indices.end = end;
+                if !current.indices.is_empty() {
+                    groups.push(current);
+                }
+                current = next;
+            }
+            "mtllib" => material_libraries.extend(words.map(str::to_string)),
+            _ => {}
+        }
+    }
+
+    current.indices.end = indices.len() as u32;
+    if !current.indices.is_empty() {
+        groups.push(current);
+    }
+
+    let has_tex_coords = vertices.iter().any(|v| v.1.is_some());
+    let has_normals = vertices.iter().any(|v| v.2.is_some());
+    let tex_coord =
+        |v: &(usize, Option<usize>, Option<usize>)| v.1.map_or([0.0, 0.0], |i| tex_coords[i]);
+    let normal =
+        |v: &(usize, Option<usize>, Option<usize>)| v.2.map_or([0.0, 0.0, 0.0], |i| normals[i]);
+
+    let mut builder = MeshBuilder::new();
+    match (has_normals, has_tex_coords) {
+        (true, true) => builder.push_vertices(
+            vertices
+                .iter()
+                .map(|v| PosNormTex {
+                    position: positions[v.0].into(),
+                    normal: normal(v).into(),
+                    tex_coord: tex_coord(v).into(),
+                })
+                .collect::<Vec<_>>(),
+        ),
+        (true, false) => builder.push_vertices(
+            vertices
+                .iter()
+                .map(|v| PosNorm {
+                    position: positions[v.0].into(),
+                    normal: normal(v).into(),
+                })
+                .collect::<Vec<_>>(),
+        ),
+        (false, true) => builder.push_vertices(
+            vertices
+                .iter()
+                .map(|v| PosTex {
+                    position: positions[v.0],
+                    tex_coord: tex_coord(v),
+                })
+                .collect::<Vec<_>>(),
+        ),
+        (false, false) => builder.push_vertices(
+            vertices
+                .iter()
+                .map(|v| Position(positions[v.0]))
+                .collect::<Vec<_>>(),
+        ),
+    };
+
+    builder.set_indices(narrow_indices(indices));
+
+    Ok(ObjMesh {
+        builder,
+        groups,
+        material_libraries,
+    })
+}
+
+/// Parse MTL source.
+pub fn parse_mtl(source: &str) -> Result<Vec<ObjMaterial>, ObjError> {
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+
+    for (line, text) in lines(source) {
+        let mut words = text.split_whitespace();
+        let keyword = match words.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        if keyword == "newmtl" {
+            materials.push(ObjMaterial {
+                name: words.collect::<Vec<_>>().join(" "),
+                ..ObjMaterial::default()
+            });
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(syntax(line, "Material property before `newmtl`")),
+        };
+        let map = || map_file(&text.trim_start()[keyword.len()..]);
+        match keyword {
+            "Ka" => material.ambient = Some(parse_floats(line, &mut words)?),
+            "Kd" => material.diffuse = Some(parse_floats(line, &mut words)?),
+            "Ks" => material.specular = Some(parse_floats(line, &mut words)?),
+            "Ke" => material.emissive = Some(parse_floats(line, &mut words)?),
+            "Ns" => material.shininess = Some(parse_floats::<[f32; 1]>(line, &mut words)?[0]),
+            "d" => material.dissolve = Some(parse_floats::<[f32; 1]>(line, &mut words)?[0]),
+            "Tr" => material.dissolve = Some(1.0 - parse_floats::<[f32; 1]>(line, &mut words)?[0]),
+            "Ni" => material.optical_density = Some(parse_floats::<[f32; 1]>(line, &mut words)?[0]),
+            "illum" => material.illumination = Some(parse(line, words.next())?),
+            "map_Ka" => material.ambient_map = map(),
+            "map_Kd" => material.diffuse_map = map(),
+            "map_Ks" => material.specular_map = map(),
+            "map_Ke" => material.emissive_map = map(),
+            "norm" | "map_Bump" | "map_bump" | "bump" => material.normal_map = map(),
+            "map_d" => material.dissolve_map = map(),
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Get file name of texture statement skipping texture options that precede it.
+/// File name is the rest of the line, so it may contain spaces.
+fn map_file(mut rest: &str) -> Option<String> {
+    loop {
+        let (option, tail) = next_word(rest);
+        // Number of option's arguments. Options with optional arguments take up to three numbers.
+        let (required, optional) = match option {
+            "-blendu" | "-blendv" | "-bm" | "-boost" | "-cc" | "-clamp" | "-imfchan"
+            | "-texres" | "-type" => (1, 0),
+            "-mm" => (2, 0),
+            "-o" | "-s" | "-t" => (1, 2),
+            _ => break,
+        };
+        rest = tail;
+        for _ in 0..required {
+            rest = next_word(rest).1;
+        }
+        for _ in 0..optional {
+            let (argument, tail) = next_word(rest);
+            if argument.parse::<f32>().is_err() {
+                break;
+            }
+            rest = tail;
+        }
+    }
+    let file = rest.trim();
+    if file.is_empty() {
+        None
+    } else {
+        Some(file.to_string())
+    }
+}
+
+/// Split off the first whitespace-separated word.
+fn next_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    (&text[..end], &text[end..])
+}
+
+fn read_file(path: &Path) -> Result<String, ObjError> {
+    let mut source = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut source))
+        .map_err(ObjError::Io)?;
+    Ok(source)
+}
+
+/// Split source into numbered lines with comments stripped and continuations joined.
+fn lines(source: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut joined: Option<(usize, String)> = None;
+    for (index, line) in source.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let continued = line.ends_with('\\');
+        let line = if continued {
+            &line[..line.len() - 1]
+        } else {
+            line
+        };
+        let mut current = joined.take().unwrap_or_else(|| (index + 1, String::new()));
+        current.1.push(' ');
+        current.1.push_str(line);
+        if continued {
+            joined = Some(current);
+        } else {
+            lines.push(current);
+        }
+    }
+    lines.extend(joined);
+    lines
+}
+
+fn syntax(line: usize, message: &str) -> ObjError {
+    ObjError::Syntax {
+        line,
+        message: message.to_string(),
+    }
+}
+
+fn parse<T>(line: usize, word: Option<&str>) -> Result<T, ObjError>
+where
+    T: FromStr,
+{
+    word.and_then(|word| word.parse().ok())
+        .ok_or_else(|| syntax(line, "Expected number"))
+}
+
+/// Parse required leading floats. Extra values are ignored.
+fn parse_floats<A>(line: usize, words: &mut SplitWhitespace) -> Result<A, ObjError>
+where
+    A: Default + AsMut<[f32]>,
+{
+    let mut values = A::default();
+    for value in values.as_mut() {
+        *value = parse(line, words.next())?;
+    }
+    Ok(values)
+}
+
+/// Resolve 1-based or negative relative index of face vertex element.
+/// Empty index means the element is absent.
+fn resolve(line: usize, index: Option<&str>, count: usize) -> Result<Option<usize>, ObjError> {
+    let index = match index {
+        None | Some("") => return Ok(None),
+        Some(index) => index,
+    };
+    let index: i64 = parse(line, Some(index))?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(ObjError::IndexOutOfRange { line, index });
+    }
+    Ok(Some(resolved as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::size_of;
+    use utils::read_pod;
+    use vertex::{AsVertexFormat, Normal, TexCoord};
+
+    const QUAD: &str = "
+# Quad split into two groups.
+mtllib quad.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1
+vn 0 0 1
+o quad
+usemtl red
+f 1/1/1 2/2/1 3/2/1 4/1/1
+g back
+usemtl blue
+f -1//1 -2//1 \\
+  -3//1
+";
+
+    #[test]
+    fn parse_quad() {
+        let mesh = parse_obj(QUAD).unwrap();
+        assert_eq!(mesh.material_libraries, vec!["quad.mtl".to_string()]);
+        assert_eq!(
+            mesh.groups,
+            vec![
+                ObjGroup {
+                    object: Some("quad".into()),
+                    group: None,
+                    material: Some("red".into()),
+                    indices: 0..6,
+                },
+                ObjGroup {
+                    object: Some("quad".into()),
+                    group: Some("back".into()),
+                    material: Some("blue".into()),
+                    indices: 6..9,
+                },
+            ]
+        );
+
+        let indices = mesh.builder.indices().unwrap().collect::<Vec<_>>();
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3, 4, 5, 6]);
+
+        let (ref vertices, ref format) = mesh.builder.vertices[0];
+        assert_eq!(*format, PosNormTex::VERTEX_FORMAT);
+        let vertex =
+            |index: usize| -> PosNormTex { read_pod(vertices, index * size_of::<PosNormTex>()) };
+        assert_eq!(vertices.len(), 7 * size_of::<PosNormTex>());
+        // `vt 1` has `v` of `0` that is flipped to `1`.
+        assert_eq!(vertex(1).tex_coord, TexCoord([1.0, 1.0]));
+        assert_eq!(vertex(0).tex_coord, TexCoord([0.0, 1.0]));
+        assert_eq!(vertex(2).position, Position([1.0, 1.0, 0.0]));
+        // Faces without texture coordinates get zero ones.
+        assert_eq!(vertex(4).position, Position([0.0, 1.0, 0.0]));
+        assert_eq!(vertex(4).tex_coord, TexCoord([0.0, 0.0]));
+        assert_eq!(vertex(4).normal, Normal([0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn concave_polygon() {
+        // L-shaped hexagon with reflex corner at `(1, 1)`.
+        // The fan from the first corner would leave the polygon.
+        let mesh =
+            parse_obj("v 2 0 0\nv 2 1 0\nv 1 1 0\nv 1 2 0\nv 0 2 0\nv 0 0 0\nf 1 2 3 4 5 6\n")
+                .unwrap();
+        let indices = mesh.builder.indices().unwrap().collect::<Vec<_>>();
+        assert_eq!(indices.len(), 12);
+
+        // Triangles face `+z` and cover the area of 3 without leaving the polygon.
+        let positions = mesh
+            .builder
+            .attribute::<Position>()
+            .unwrap()
+            .collect::<Vec<_>>();
+        let mut area = 0.0;
+        for triangle in indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize].0);
+            let z = (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0]);
+            assert!(z > 0.0);
+            area += z / 2.0;
+            let center = [(a[0] + b[0] + c[0]) / 3.0, (a[1] + b[1] + c[1]) / 3.0];
+            assert!(center[0] < 1.0 || center[1] < 1.0);
+        }
+        assert_eq!(area, 3.0);
+    }
+
+    #[test]
+    fn positions_only() {
+        let mesh = parse_obj("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert_eq!(mesh.builder.vertices[0].1, Position::VERTEX_FORMAT);
+        assert!(mesh.groups.iter().all(|g| g.object.is_none()));
+    }
+
+    #[test]
+    fn errors() {
+        match parse_obj("v 0 0 0\nv 1 0 0\nf 1 2 4\n") {
+            Err(ObjError::IndexOutOfRange { line: 3, index: 4 }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match parse_obj("v 0 0 0\nv 1 0 0\nf 1 2\n") {
+            Err(ObjError::Syntax { line: 3, .. }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match parse_obj("v 0 0\n") {
+            Err(ObjError::Syntax { line: 1, .. }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+        match parse_obj("vt\n") {
+            Err(ObjError::Syntax { line: 1, .. }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_materials() {
+        let materials = parse_mtl(
+            "
+newmtl red
+Kd 1 0 0
+Ns 10
+Tr 0.25
+illum 2
+map_Kd -o 0.5 0.5 -s 2 -bm 1 -clamp on textures/red brick.png
+map_Bump -bm 0.5 normal map.png
+newmtl blue
+Kd 0 0 1
+map_Ka -t 1 ambient.png
+map_d -o 1 2 3 -mm 0 1 mask.png
+",
+        )
+        .unwrap();
+        assert_eq!(materials.len(), 2);
+        assert_eq!(
+            materials[0],
+            ObjMaterial {
+                name: "red".into(),
+                diffuse: Some([1.0, 0.0, 0.0]),
+                shininess: Some(10.0),
+                dissolve: Some(0.75),
+                illumination: Some(2),
+                diffuse_map: Some("textures/red brick.png".into()),
+                normal_map: Some("normal map.png".into()),
+                ..ObjMaterial::default()
+            }
+        );
+        assert_eq!(materials[1].ambient_map, Some("ambient.png".into()));
+        assert_eq!(materials[1].dissolve_map, Some("mask.png".into()));
+
+        match parse_mtl("Kd 1 1 1\n") {
+            Err(ObjError::Syntax { line: 1, .. }) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,806 @@
+//!
+//! Import of PLY (Polygon File Format) meshes and point clouds.
+//!
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::{self, SplitWhitespace};
+
+use hal::format::Format;
+use hal::Primitive;
+
+use super::polygon::triangulate;
+use convert::{element_layout, encode_element, DEFAULT_VALUE};
+use mesh::MeshBuilder;
+use utils::narrow_indices;
+use vertex::{Color, DynamicAttribute, Normal, Position, TexCoord, VertexFormat};
+
+/// Error returned by PLY parsing.
+#[derive(Debug, Fail)]
+pub enum PlyError {
+    /// File can't be read.
+    #[fail(display = "Failed to read file: {}", _0)]
+    Io(#[cause] io::Error),
+
+    /// Header is malformed.
+    #[fail(display = "Invalid header at line {}: {}", line, message)]
+    InvalidHeader {
+        /// Number of the header line starting from `1`.
+        line: usize,
+        /// Description of the error.
+        message: String,
+    },
+
+    /// Data ends before all elements are read.
+    #[fail(display = "Unexpected end of data in element \"{}\"", element)]
+    UnexpectedEnd {
+        /// Name of the element.
+        element: String,
+    },
+
+    /// ASCII value can't be parsed.
+    #[fail(display = "Invalid value of property \"{}.{}\"", element, property)]
+    InvalidValue {
+        /// Name of the element.
+        element: String,
+        /// Name of the property.
+        property: String,
+    },
+
+    /// Face refers to vertex that doesn't exist.
+    #[fail(
+        display = "Face refers to vertex {} while there are {}",
+        index, vertex_count
+    )]
+    IndexOutOfRange {
+        /// Index of the vertex.
+        index: f64,
+        /// Number of vertices.
+        vertex_count: usize,
+    },
+}
+
+/// Load PLY file.
+pub fn load_ply<P>(path: P) -> Result<MeshBuilder<'static>, PlyError>
+where
+    P: AsRef<Path>,
+{
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(PlyError::Io)?;
+    parse_ply(&bytes)
+}
+
+/// Parse ASCII or binary PLY.
+///
+/// Vertex properties `x y z`, `nx ny nz`, `red green blue [alpha]`
+/// and `u v` (or `s t`, `texture_u texture_v`) are added as separate buffers
+/// of `Position`, `Normal`, `Color` and `TexCoord` attributes.
+/// Integer colors are normalized.
+/// Other scalar vertex properties are added as single buffer
+/// with attributes named after the properties.
+/// They keep integer formats while `double` is narrowed to `R32Float`.
+/// List vertex properties and elements other than `vertex` and `face` are skipped.
+/// Elements without properties are ignored whatever their count.
+///
+/// Faces are triangulated into `Primitive::TriangleList`,
+/// convex ones as fans and concave ones by ear clipping in the plane of the face.
+/// If there are no faces the mesh is a `Primitive::PointList`.
+///
+/// Element and list counts must be non-negative integers and face indices must be integral.
+/// ASCII values of integer properties must fit their types.
+pub fn parse_ply(bytes: &[u8]) -> Result<MeshBuilder<'static>, PlyError> {
+    let (encoding, elements, body) = parse_header(bytes)?;
+    let mut reader = match encoding {
+        Encoding::Ascii => Reader::Ascii(
+            str::from_utf8(body)
+                .map_err(|_| PlyError::InvalidHeader {
+                    line: 2,
+                    message: "ASCII body is not valid UTF-8".to_string(),
+                })?
+                .split_whitespace(),
+        ),
+        Encoding::BinaryLittleEndian => Reader::Binary {
+            bytes: body,
+            big_endian: false,
+        },
+        Encoding::BinaryBigEndian => Reader::Binary {
+            bytes: body,
+            big_endian: true,
+        },
+    };
+
+    let mut vertices: Option<(&Element, Vec<Vec<f64>>)> = None;
+    // Faces are triangulated once positions are known.
+    let mut faces = Vec::new();
+
+    for element in &elements {
+        // Element without properties has no data,
+        // so its count from the file can't be checked against the body.
+        if element.properties.is_empty() {
+            continue;
+        }
+
+        // Every value takes at least one byte, so counts from the file
+        // can't make capacity larger than the body.
+        let capacity = element.count.min(body.len());
+        let mut columns = vec![Vec::with_capacity(capacity); element.properties.len()];
+        for _ in 0..element.count {
+            for (property, column) in element.properties.iter().zip(&mut columns) {
+                let invalid = || PlyError::InvalidValue {
+                    element: element.name.clone(),
+                    property: property.name.clone(),
+                };
+                match property.kind {
+                    PropertyKind::Scalar(scalar) => {
+                        column.push(reader.read(scalar, element, property)?);
+                    }
+                    PropertyKind::List { count, item } => {
+                        let count = reader.read(count, element, property)?;
+                        if count < 0.0 || count.fract() != 0.0 {
+                            return Err(invalid());
+                        }
+                        let count = count as usize;
+                        let is_face = element.name == "face" && is_face_indices(&property.name);
+                        let mut polygon = Vec::with_capacity(count.min(body.len()));
+                        for _ in 0..count {
+                            let value = reader.read(item, element, property)?;
+                            if is_face && value.fract() != 0.0 {
+                                return Err(invalid());
+                            }
+                            polygon.push(value);
+                        }
+                        if is_face && polygon.len() >= 3 {
+                            faces.push(polygon);
+                        }
+                    }
+                }
+            }
+        }
+        if element.name == "vertex" {
+            vertices = Some((element, columns));
+        }
+    }
+
+    let mut builder = MeshBuilder::new();
+    let vertex_count = match vertices {
+        Some((element, columns)) => {
+            add_vertices(&mut builder, element, columns);
+            element.count
+        }
+        None => 0,
+    };
+
+    if faces.is_empty() {
+        builder.set_prim_type(Primitive::PointList);
+    } else {
+        let positions = builder
+            .attribute::<Position>()
+            .map(|positions| positions.map(|position| position.0).collect::<Vec<_>>())
+            .ok();
+        let mut indices = Vec::new();
+        for face in faces {
+            let polygon = face
+                .into_iter()
+                .map(|index| {
+                    if index >= 0.0 && index < vertex_count as f64 {
+                        Ok(index as u32)
+                    } else {
+                        Err(PlyError::IndexOutOfRange {
+                            index,
+                            vertex_count,
+                        })
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            // Faces of vertices without positions are triangulated as fans.
+            let points = polygon
+                .iter()
+                .map(|&index| positions.as_ref().map_or([0.0; 3], |p| p[index as usize]))
+                .collect::<Vec<_>>();
+            for triangle in triangulate(&points) {
+                indices.extend(triangle.iter().map(|&corner| polygon[corner]));
+            }
+        }
+        builder.set_indices(narrow_indices(indices));
+        builder.set_prim_type(Primitive::TriangleList);
+    }
+
+    Ok(builder)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(&self) -> usize {
+        match *self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    /// Format of the attribute that stores the property.
+    fn format(&self) -> Format {
+        match *self {
+            Scalar::I8 => Format::R8Int,
+            Scalar::U8 => Format::R8Uint,
+            Scalar::I16 => Format::R16Int,
+            Scalar::U16 => Format::R16Uint,
+            Scalar::I32 => Format::R32Int,
+            Scalar::U32 => Format::R32Uint,
+            Scalar::F32 | Scalar::F64 => Format::R32Float,
+        }
+    }
+
+    /// Range of integer values. `None` for floating point types.
+    fn range(&self) -> Option<(f64, f64)> {
+        match *self {
+            Scalar::I8 => Some((-128.0, 127.0)),
+            Scalar::U8 => Some((0.0, 255.0)),
+            Scalar::I16 => Some((-32768.0, 32767.0)),
+            Scalar::U16 => Some((0.0, 65535.0)),
+            Scalar::I32 => Some((-2147483648.0, 2147483647.0)),
+            Scalar::U32 => Some((0.0, 4294967295.0)),
+            Scalar::F32 | Scalar::F64 => None,
+        }
+    }
+
+    /// Factor that maps integer color channel to `[0, 1]`.
+    fn color_scale(&self) -> f64 {
+        match *self {
+            Scalar::U8 => 1.0 / 255.0,
+            Scalar::U16 => 1.0 / 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PropertyKind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Parse header and return encoding, elements and the rest of the bytes.
+fn parse_header(bytes: &[u8]) -> Result<(Encoding, Vec<Element>, &[u8]), PlyError> {
+    let mut encoding = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut rest = bytes;
+    let mut line = 0;
+
+    loop {
+        line += 1;
+        let error = |message: &str| PlyError::InvalidHeader {
+            line,
+            message: message.to_string(),
+        };
+        let end = rest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .ok_or_else(|| error("Missing `end_header`"))?;
+        let text = str::from_utf8(&rest[..end]).map_err(|_| error("Header is not ASCII"))?;
+        rest = &rest[end + 1..];
+
+        let mut words = text.split_whitespace();
+        let keyword = words.next();
+        if line == 1 {
+            if keyword != Some("ply") {
+                return Err(error("Missing `ply` magic"));
+            }
+            continue;
+        }
+
+        match keyword {
+            Some("format") => {
+                encoding = Some(match words.next() {
+                    Some("ascii") => Encoding::Ascii,
+                    Some("binary_little_endian") => Encoding::BinaryLittleEndian,
+                    Some("binary_big_endian") => Encoding::BinaryBigEndian,
+                    _ => return Err(error("Unknown format")),
+                });
+                if words.next() != Some("1.0") {
+                    return Err(error("Unsupported version"));
+                }
+            }
+            Some("element") => {
+                let name = words.next().ok_or_else(|| error("Missing element name"))?;
+                let count = words
+                    .next()
+                    .and_then(|count| count.parse().ok())
+                    .ok_or_else(|| error("Invalid element count"))?;
+                elements.push(Element {
+                    name: name.to_string(),
+                    count,
+                    properties: Vec::new(),
+                });
+            }
+            Some("property") => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("Property before element"))?;
+                let scalar = |word: Option<&str>| {
+                    word.and_then(Scalar::parse)
+                        .ok_or_else(|| error("Unknown property type"))
+                };
+                let kind = match words.next() {
+                    Some("list") => PropertyKind::List {
+                        count: scalar(words.next())?,
+                        item: scalar(words.next())?,
+                    },
+                    word => PropertyKind::Scalar(scalar(word)?),
+                };
+                let name = words.next().ok_or_else(|| error("Missing property name"))?;
+                element.properties.push(Property {
+                    name: name.to_string(),
+                    kind,
+                });
+            }
+            Some("end_header") => break,
+            Some("comment") | Some("obj_info") | None => {}
+            Some(_) => return Err(error("Unknown keyword")),
+        }
+    }
+
+    let encoding = encoding.ok_or_else(|| PlyError::InvalidHeader {
+        line,
+        message: "Missing `format`".to_string(),
+    })?;
+    Ok((encoding, elements, rest))
+}
+
+enum Reader<'a> {
+    Ascii(SplitWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl<'a> Reader<'a> {
+    fn read(
+        &mut self,
+        scalar: Scalar,
+        element: &Element,
+        property: &Property,
+    ) -> Result<f64, PlyError> {
+        let end = || PlyError::UnexpectedEnd {
+            element: element.name.clone(),
+        };
+        match *self {
+            Reader::Ascii(ref mut words) => {
+                let invalid = || PlyError::InvalidValue {
+                    element: element.name.clone(),
+                    property: property.name.clone(),
+                };
+                let value: f64 = words
+                    .next()
+                    .ok_or_else(end)?
+                    .parse()
+                    .map_err(|_| invalid())?;
+                match scalar.range() {
+                    Some((min, max)) if value.fract() != 0.0 || value < min || value > max => {
+                        Err(invalid())
+                    }
+                    _ => Ok(value),
+                }
+            }
+            Reader::Binary {
+                ref mut bytes,
+                big_endian,
+            } => {
+                let size = scalar.size();
+                if bytes.len() < size {
+                    return Err(end());
+                }
+                let (value, rest) = bytes.split_at(size);
+                *bytes = rest;
+
+                let fold = |bits: u64, &byte: &u8| bits << 8 | byte as u64;
+                let bits = if big_endian {
+                    value.iter().fold(0, fold)
+                } else {
+                    value.iter().rev().fold(0, fold)
+                };
+                Ok(match scalar {
+                    Scalar::I8 => bits as u8 as i8 as f64,
+                    Scalar::U8 => bits as u8 as f64,
+                    Scalar::I16 => bits as u16 as i16 as f64,
+                    Scalar::U16 => bits as u16 as f64,
+                    Scalar::I32 => bits as u32 as i32 as f64,
+                    Scalar::U32 => bits as u32 as f64,
+                    Scalar::F32 => f32::from_bits(bits as u32) as f64,
+                    Scalar::F64 => f64::from_bits(bits),
+                })
+            }
+        }
+    }
+}
+
+fn is_face_indices(name: &str) -> bool {
+    name == "vertex_indices" || name == "vertex_index"
+}
+
+/// Add buffers for vertex element properties.
+fn add_vertices(builder: &mut MeshBuilder<'static>, element: &Element, columns: Vec<Vec<f64>>) {
+    let mut used = vec![false; element.properties.len()];
+    {
+        let mut find = |names: &[&str]| -> Option<Vec<(usize, Scalar)>> {
+            let found = names
+                .iter()
+                .map(|&name| {
+                    element
+                        .properties
+                        .iter()
+                        .position(|property| property.name == name)
+                        .and_then(|index| match element.properties[index].kind {
+                            PropertyKind::Scalar(scalar) => Some((index, scalar)),
+                            PropertyKind::List { .. } => None,
+                        })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            for &(index, _) in &found {
+                used[index] = true;
+            }
+            Some(found)
+        };
+        let value = |&(index, _): &(usize, Scalar), vertex: usize| columns[index][vertex] as f32;
+
+        if let Some(xyz) = find(&["x", "y", "z"]) {
+            builder.push_vertices(
+                (0..element.count)
+                    .map(|v| Position([value(&xyz[0], v), value(&xyz[1], v), value(&xyz[2], v)]))
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        if let Some(n) = find(&["nx", "ny", "nz"]) {
+            builder.push_vertices(
+                (0..element.count)
+                    .map(|v| Normal([value(&n[0], v), value(&n[1], v), value(&n[2], v)]))
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        if let Some(rgb) = find(&["red", "green", "blue"]) {
+            let alpha = find(&["alpha"]).map(|alpha| alpha[0]);
+            let channel = |channel: Option<&(usize, Scalar)>, v: usize| {
+                channel.map_or(1.0, |channel| {
+                    value(channel, v) * channel.1.color_scale() as f32
+                })
+            };
+            builder.push_vertices(
+                (0..element.count)
+                    .map(|v| {
+                        Color([
+                            channel(Some(&rgb[0]), v),
+                            channel(Some(&rgb[1]), v),
+                            channel(Some(&rgb[2]), v),
+                            channel(alpha.as_ref(), v),
+                        ])
+                    })
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        let uv = find(&["u", "v"])
+            .or_else(|| find(&["s", "t"]))
+            .or_else(|| find(&["texture_u", "texture_v"]));
+        if let Some(uv) = uv {
+            // PLY texture coordinates have origin at the bottom-left corner.
+            builder.push_vertices(
+                (0..element.count)
+                    .map(|v| TexCoord([value(&uv[0], v), 1.0 - value(&uv[1], v)]))
+                    .collect::<Vec<_>>(),
+            );
+        }
+    }
+
+    let extra = element
+        .properties
+        .iter()
+        .zip(columns)
+        .zip(used)
+        .filter_map(|((property, column), used)| match property.kind {
+            PropertyKind::Scalar(scalar) if !used => Some((property, scalar, column)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if extra.is_empty() {
+        return;
+    }
+
+    let format = VertexFormat::from_attributes(extra.iter().map(|&(property, scalar, _)| {
+        DynamicAttribute::new(Cow::Owned(property.name.clone()), scalar.format())
+    }));
+    let stride = format.stride as usize;
+    let mut bytes = vec![0; stride * element.count];
+    for (attribute, (_, _, column)) in format.attributes.iter().zip(&extra) {
+        let layout = element_layout(attribute.element.format).expect("Scalar format");
+        for (vertex, &value) in column.iter().enumerate() {
+            let mut value4 = DEFAULT_VALUE;
+            value4[0] = value;
+            encode_element(
+                &mut bytes,
+                vertex * stride + attribute.element.offset as usize,
+                layout,
+                value4,
+            );
+        }
+    }
+    builder
+        .add_raw_vertices(bytes, format)
+        .expect("Generated format is valid");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::read_pod;
+
+    const ASCII: &[u8] = b"ply
+format ascii 1.0
+comment Two triangles of a quad.
+element vertex 4
+property float x
+property float y
+property float z
+property uchar red
+property uchar green
+property uchar blue
+property float confidence
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 255 0 0 0.5
+1 0 0 0 255 0 1
+1 1 0 0 0 255 0.25
+0 1 0 255 255 255 0
+4 0 1 2 3
+";
+
+    /// Header of binary fixtures with vertices of three floats and faces of `uchar` and `int`s.
+    fn binary(format: &str) -> Vec<u8> {
+        format!(
+            "ply\nformat {} 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+             property float z\nelement face 1\nproperty list uchar int vertex_indices\n\
+             end_header\n",
+            format
+        )
+        .into_bytes()
+    }
+
+    const POSITIONS: [[f32; 3]; 3] = [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]];
+
+    fn positions(builder: &MeshBuilder) -> Vec<Position> {
+        builder.attribute::<Position>().unwrap().collect()
+    }
+
+    #[test]
+    fn ascii() {
+        let builder = parse_ply(ASCII).unwrap();
+        assert_eq!(builder.prim, Primitive::TriangleList);
+        assert_eq!(
+            builder.indices().unwrap().collect::<Vec<_>>(),
+            vec![0, 1, 2, 0, 2, 3]
+        );
+        assert_eq!(positions(&builder)[2], Position([1.0, 1.0, 0.0]));
+        assert_eq!(
+            builder.attribute::<Color>().unwrap().nth(1),
+            Some(Color([0.0, 1.0, 0.0, 1.0]))
+        );
+
+        // Unknown property is kept in extra buffer.
+        let (ref bytes, ref format) = builder.vertices[2];
+        assert_eq!(format.attributes[0].name, "confidence");
+        assert_eq!(format.attributes[0].element.format, Format::R32Float);
+        assert_eq!(read_pod::<f32>(bytes, 8), 0.25);
+    }
+
+    #[test]
+    fn binary_little_endian() {
+        let mut bytes = binary("binary_little_endian");
+        for value in POSITIONS.iter().flat_map(|p| p.iter()) {
+            bytes.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+        bytes.push(3);
+        for index in 0..3i32 {
+            bytes.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let builder = parse_ply(&bytes).unwrap();
+        assert_eq!(
+            positions(&builder),
+            POSITIONS.iter().map(|&p| Position(p)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            builder.indices().unwrap().collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+
+        bytes.pop();
+        match parse_ply(&bytes) {
+            Err(PlyError::UnexpectedEnd { ref element }) if element == "face" => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn binary_big_endian() {
+        let mut bytes = binary("binary_big_endian");
+        for value in POSITIONS.iter().flat_map(|p| p.iter()) {
+            bytes.extend_from_slice(&value.to_bits().to_be_bytes());
+        }
+        bytes.push(3);
+        for index in &[2i32, 1, 0] {
+            bytes.extend_from_slice(&index.to_be_bytes());
+        }
+
+        let builder = parse_ply(&bytes).unwrap();
+        assert_eq!(
+            positions(&builder),
+            POSITIONS.iter().map(|&p| Position(p)).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            builder.indices().unwrap().collect::<Vec<_>>(),
+            vec![2, 1, 0]
+        );
+    }
+
+    #[test]
+    fn concave_face() {
+        // L-shaped hexagon with reflex corner at `(1, 1)`, listed before its vertices.
+        let builder = parse_ply(
+            b"ply\nformat ascii 1.0\nelement face 1\nproperty list uchar int vertex_indices\n\
+              element vertex 6\nproperty float x\nproperty float y\nproperty float z\n\
+              end_header\n6 0 1 2 3 4 5\n2 0 0\n2 1 0\n1 1 0\n1 2 0\n0 2 0\n0 0 0\n",
+        )
+        .unwrap();
+        let indices = builder.indices().unwrap().collect::<Vec<_>>();
+        assert_eq!(indices.len(), 12);
+        // The fan from the first corner would have triangles `0 2 3` and `0 3 4`.
+        for triangle in indices.chunks(3) {
+            assert!(!triangle.contains(&0) || !triangle.contains(&3));
+        }
+    }
+
+    #[test]
+    fn point_cloud() {
+        let builder = parse_ply(
+            b"ply\nformat ascii 1.0\nelement vertex 2\nproperty double x\nproperty double y\n\
+              property double z\nend_header\n0 0 0\n1 2 3\n",
+        )
+        .unwrap();
+        assert_eq!(builder.prim, Primitive::PointList);
+        assert!(builder.indices().is_none());
+        assert_eq!(positions(&builder)[1], Position([1.0, 2.0, 3.0]));
+    }
+
+    fn ascii_face(count: &str, face: &str) -> Result<MeshBuilder<'static>, PlyError> {
+        parse_ply(
+            format!(
+                "ply\nformat ascii 1.0\nelement vertex {}\nproperty float x\n\
+                 property float y\nproperty float z\nelement face 1\n\
+                 property list int float vertex_indices\nend_header\n\
+                 0 0 0\n1 0 0\n0 1 0\n{}\n",
+                count, face
+            )
+            .as_bytes(),
+        )
+    }
+
+    #[test]
+    fn invalid_counts() {
+        assert!(ascii_face("3", "3 0 1 2").is_ok());
+        for &count in &["-3", "3.5", "x"] {
+            match ascii_face(count, "3 0 1 2") {
+                Err(PlyError::InvalidHeader { line: 3, .. }) => {}
+                other => panic!("Unexpected result {:?}", other.map(|_| ())),
+            }
+        }
+        for &face in &["-1 0 1 2", "2.5 0 1 2"] {
+            match ascii_face("3", face) {
+                Err(PlyError::InvalidValue { ref property, .. })
+                    if property == "vertex_indices" => {}
+                other => panic!("Unexpected result {:?}", other.map(|_| ())),
+            }
+        }
+
+        // Huge counts fail at the end of data instead of allocating.
+        match ascii_face("3", "2147483647 0 1 2") {
+            Err(PlyError::UnexpectedEnd { ref element }) if element == "face" => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+        match ascii_face("18446744073709551615", "3 0 1 2") {
+            Err(PlyError::UnexpectedEnd { ref element }) if element == "vertex" => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+
+        // Elements without properties consume no data and are skipped.
+        let builder =
+            parse_ply(b"ply\nformat ascii 1.0\nelement junk 18446744073709551615\nend_header\n")
+                .unwrap();
+        assert_eq!(builder.prim, Primitive::PointList);
+        assert_eq!(builder.vertex_count(), Ok(None));
+    }
+
+    #[test]
+    fn invalid_indices() {
+        match ascii_face("3", "3 0 1.5 2") {
+            Err(PlyError::InvalidValue { ref property, .. }) if property == "vertex_indices" => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+        match ascii_face("3", "3 0 1 3") {
+            Err(PlyError::IndexOutOfRange {
+                vertex_count: 3, ..
+            }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn integer_range() {
+        let ply = |value: &str| {
+            parse_ply(
+                format!(
+                    "ply\nformat ascii 1.0\nelement vertex 1\nproperty uchar intensity\n\
+                     end_header\n{}\n",
+                    value
+                )
+                .as_bytes(),
+            )
+        };
+        assert!(ply("255").is_ok());
+        for &value in &["256", "-1", "0.5"] {
+            match ply(value) {
+                Err(PlyError::InvalidValue { ref property, .. }) if property == "intensity" => {}
+                other => panic!("Unexpected result {:?}", other.map(|_| ())),
+            }
+        }
+    }
+}
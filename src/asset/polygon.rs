@@ -0,0 +1,186 @@
+//!
+//! Triangulation of polygonal faces.
+//!
+
+use math::add;
+
+/// Triangulate simple polygon given by positions of its corners.
+/// Returns triangles as indices of the corners with the winding of the polygon.
+///
+/// Polygon is projected onto the plane of its Newell normal and ear clipping is used
+/// to triangulate concave polygons. Convex polygons, polygons with zero area
+/// and self-intersecting polygons are triangulated (or finished) as fans.
+pub fn triangulate(points: &[[f32; 3]]) -> Vec<[usize; 3]> {
+    let fan = |corners: &[usize]| {
+        (2..corners.len())
+            .map(|i| [corners[0], corners[i - 1], corners[i]])
+            .collect::<Vec<_>>()
+    };
+    let corners = (0..points.len()).collect::<Vec<_>>();
+    if points.len() <= 3 {
+        return fan(&corners);
+    }
+
+    let normal = newell_normal(points);
+    let [x, y, z] = [normal[0].abs(), normal[1].abs(), normal[2].abs()];
+    let axis = if x >= y && x >= z {
+        0
+    } else if y >= z {
+        1
+    } else {
+        2
+    };
+    // Zero area or non-finite positions.
+    if !normal[axis].is_normal() {
+        return fan(&corners);
+    }
+
+    // Drop the dominant axis and mirror if needed so that the polygon is counter-clockwise.
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let sign = normal[axis].signum();
+    let points = points
+        .iter()
+        .map(|p| [p[u] * sign, p[v]])
+        .collect::<Vec<_>>();
+
+    let turn = |a: usize, b: usize, c: usize| {
+        let (a, b, c) = (points[a], points[b], points[c]);
+        (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+    };
+    let n = corners.len();
+    if (0..n).all(|i| turn((i + n - 1) % n, i, (i + 1) % n) >= 0.0) {
+        return fan(&corners);
+    }
+
+    let mut triangles = Vec::with_capacity(n - 2);
+    let mut corners = corners;
+    let mut i = 0;
+    let mut misses = 0;
+    while corners.len() > 3 {
+        if misses == corners.len() {
+            // No ear left because polygon intersects itself.
+            triangles.extend(fan(&corners));
+            return triangles;
+        }
+        let len = corners.len();
+        let (a, b, c) = (
+            corners[(i + len - 1) % len],
+            corners[i % len],
+            corners[(i + 1) % len],
+        );
+        let is_ear = turn(a, b, c) > 0.0
+            && corners.iter().all(|&p| {
+                points[p] == points[a]
+                    || points[p] == points[b]
+                    || points[p] == points[c]
+                    || turn(a, b, p) < 0.0
+                    || turn(b, c, p) < 0.0
+                    || turn(c, a, p) < 0.0
+            });
+        if is_ear {
+            triangles.push([a, b, c]);
+            corners.remove(i % len);
+            misses = 0;
+        } else {
+            i += 1;
+            misses += 1;
+        }
+        i %= corners.len();
+    }
+    triangles.push([corners[0], corners[1], corners[2]]);
+    triangles
+}
+
+/// Normal of polygon plane scaled by twice the polygon area.
+fn newell_normal(points: &[[f32; 3]]) -> [f32; 3] {
+    let mut normal = [0.0; 3];
+    for (i, &p) in points.iter().enumerate() {
+        let q = points[(i + 1) % points.len()];
+        normal = add(
+            normal,
+            [
+                (p[1] - q[1]) * (p[2] + q[2]),
+                (p[2] - q[2]) * (p[0] + q[0]),
+                (p[0] - q[0]) * (p[1] + q[1]),
+            ],
+        );
+    }
+    normal
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::{cross, dot, sub};
+
+    /// Sum of triangle normals, which is the Newell normal if triangles cover the polygon once.
+    fn area_normal(points: &[[f32; 3]], triangles: &[[usize; 3]]) -> [f32; 3] {
+        let mut normal = [0.0; 3];
+        for t in triangles {
+            let (a, b, c) = (points[t[0]], points[t[1]], points[t[2]]);
+            normal = add(normal, cross(sub(b, a), sub(c, a)));
+        }
+        normal
+    }
+
+    #[test]
+    fn convex() {
+        let quad = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        assert_eq!(triangulate(&quad), vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(triangulate(&quad[..3]), vec![[0, 1, 2]]);
+        assert!(triangulate(&quad[..2]).is_empty());
+    }
+
+    #[test]
+    fn concave() {
+        // Arrow head with reflex corner at 1, in the `yz` plane facing `-x`.
+        let arrow = [
+            [0.0, 2.0, 1.0],
+            [0.0, 1.0, 0.0],
+            [0.0, 2.0, -1.0],
+            [0.0, 0.0, 0.0],
+        ];
+        let normal = newell_normal(&arrow);
+        assert!(normal[0] < 0.0);
+        let triangles = triangulate(&arrow);
+        assert_eq!(triangles.len(), 2);
+        assert_eq!(area_normal(&arrow, &triangles), normal);
+        // The fan from the first corner would cover the area outside the polygon.
+        assert!(!triangles.contains(&[0, 1, 2]));
+        assert_eq!(triangles, vec![[3, 0, 1], [1, 2, 3]]);
+        for t in &triangles {
+            let n = area_normal(&arrow, &[*t]);
+            assert!(dot(n, normal) > 0.0);
+        }
+    }
+
+    #[test]
+    fn degenerate() {
+        // Zero area.
+        let line = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0],
+            [3.0, 0.0, 0.0],
+        ];
+        assert_eq!(triangulate(&line), vec![[0, 1, 2], [0, 2, 3]]);
+
+        // Bow tie intersects itself so both halves cancel out.
+        let bow_tie = [
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        assert_eq!(triangulate(&bow_tie).len(), 2);
+
+        let mut nan = bow_tie;
+        nan[1][0] = f32::NAN;
+        assert_eq!(triangulate(&nan), vec![[0, 1, 2], [0, 2, 3]]);
+    }
+}
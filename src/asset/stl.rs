@@ -0,0 +1,395 @@
+//!
+//! Import and export of STL (stereolithography) meshes.
+//!
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::str;
+
+use hal::Primitive;
+
+use access::AccessError;
+use math::{bits, cross, normalize, sub};
+use mesh::MeshBuilder;
+use utils::narrow_indices;
+use vertex::{PosNorm, Position};
+
+/// Error returned by STL import and export.
+#[derive(Debug, Fail)]
+pub enum StlError {
+    /// File can't be read or written.
+    #[fail(display = "I/O error: {}", _0)]
+    Io(#[cause] io::Error),
+
+    /// ASCII line can't be parsed.
+    #[fail(display = "Syntax error at line {}: {}", line, message)]
+    Syntax {
+        /// Number of the line starting from `1`.
+        line: usize,
+        /// Description of the error.
+        message: String,
+    },
+
+    /// Binary data is shorter than the triangle count requires.
+    #[fail(display = "Binary STL has {} bytes, expected {}", found, expected)]
+    UnexpectedEnd {
+        /// Required size in bytes.
+        expected: usize,
+        /// Actual size in bytes.
+        found: usize,
+    },
+
+    /// Only triangle lists can be exported.
+    #[fail(display = "Primitive {:?} is not a triangle list", _0)]
+    UnsupportedPrimitive(Primitive),
+
+    /// Positions can't be read from `MeshBuilder`.
+    #[fail(display = "Positions are not accessible: {}", _0)]
+    MissingPositions(#[cause] AccessError),
+
+    /// Index refers to vertex that doesn't exist.
+    #[fail(
+        display = "Index {} is out of range for {} vertices",
+        index, vertex_count
+    )]
+    IndexOutOfRange {
+        /// Value of the index.
+        index: u32,
+        /// Number of vertices.
+        vertex_count: usize,
+    },
+}
+
+/// Load ASCII or binary STL file.
+/// See `parse_stl`.
+pub fn load_stl<P>(path: P, weld: bool) -> Result<MeshBuilder<'static>, StlError>
+where
+    P: AsRef<Path>,
+{
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_end(&mut bytes))
+        .map_err(StlError::Io)?;
+    parse_stl(&bytes, weld)
+}
+
+/// Parse ASCII or binary STL into triangle list of `PosNorm` vertices.
+/// Vertices get normal of their facet.
+/// Facets with zero normal get one computed from the winding.
+///
+/// If `weld` is `true` vertices with equal position and normal are merged
+/// and index buffer is created.
+/// Otherwise every facet gets its own three vertices and no index buffer.
+pub fn parse_stl(bytes: &[u8], weld: bool) -> Result<MeshBuilder<'static>, StlError> {
+    let vertices = if is_binary(bytes) {
+        parse_binary(bytes)?
+    } else {
+        parse_ascii(bytes)?
+    };
+
+    let mut builder = MeshBuilder::new();
+    if weld {
+        let mut welded = HashMap::new();
+        let mut unique = Vec::new();
+        let indices = vertices
+            .iter()
+            .map(|vertex| {
+                let key = (bits(vertex.position.0), bits(vertex.normal.0));
+                *welded.entry(key).or_insert_with(|| {
+                    unique.push(*vertex);
+                    unique.len() as u32 - 1
+                })
+            })
+            .collect();
+        builder.push_vertices(unique);
+        builder.set_indices(narrow_indices(indices));
+    } else {
+        builder.push_vertices(vertices);
+    }
+    Ok(builder)
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Write triangle list as binary STL.
+    /// Positions are read from `Position` attribute
+    /// and facet normals are computed from the winding.
+    pub fn write_stl<W>(&self, mut writer: W) -> Result<(), StlError>
+    where
+        W: Write,
+    {
+        if self.prim != Primitive::TriangleList {
+            return Err(StlError::UnsupportedPrimitive(self.prim));
+        }
+        let positions = self
+            .attribute::<Position>()
+            .map_err(StlError::MissingPositions)?
+            .collect::<Vec<_>>();
+        let indices = self
+            .read_indices()
+            .unwrap_or_else(|| (0..positions.len() as u32).collect());
+
+        let triangles = indices.len() / 3;
+        let mut bytes = Vec::with_capacity(84 + triangles * 50);
+        bytes.extend_from_slice(&[0; 80]);
+        bytes.extend_from_slice(&le_bytes(triangles as u32));
+        for triangle in indices.chunks(3).take(triangles) {
+            let mut corners = [[0.0; 3]; 3];
+            for (corner, &index) in corners.iter_mut().zip(triangle) {
+                *corner = positions
+                    .get(index as usize)
+                    .ok_or(StlError::IndexOutOfRange {
+                        index,
+                        vertex_count: positions.len(),
+                    })?
+                    .0;
+            }
+            let normal = facet_normal(corners);
+            for &value in normal.iter().chain(corners.iter().flat_map(|c| c.iter())) {
+                bytes.extend_from_slice(&le_bytes(value.to_bits()));
+            }
+            bytes.extend_from_slice(&[0, 0]);
+        }
+
+        writer.write_all(&bytes).map_err(StlError::Io)
+    }
+}
+
+/// Size of binary STL according to the facet count in its header.
+/// Returns `None` if the size doesn't fit into `usize`.
+fn binary_size(bytes: &[u8]) -> Option<usize> {
+    (read_u32(&bytes[80..84]) as usize)
+        .checked_mul(50)
+        .and_then(|size| size.checked_add(84))
+}
+
+/// Binary STL may start with `solid` too, so its size is checked first.
+fn is_binary(bytes: &[u8]) -> bool {
+    if bytes.len() >= 84 && binary_size(bytes) == Some(bytes.len()) {
+        return true;
+    }
+    !bytes.starts_with(b"solid")
+}
+
+fn parse_binary(bytes: &[u8]) -> Result<Vec<PosNorm>, StlError> {
+    let expected = if bytes.len() >= 84 {
+        // Size that overflows can't be satisfied by any input.
+        binary_size(bytes).unwrap_or(usize::MAX)
+    } else {
+        84
+    };
+    if bytes.len() < expected {
+        return Err(StlError::UnexpectedEnd {
+            expected,
+            found: bytes.len(),
+        });
+    }
+
+    let mut vertices = Vec::with_capacity((expected - 84) / 50 * 3);
+    for facet in bytes[84..expected].chunks(50) {
+        let mut values = [[0.0; 3]; 4];
+        for (i, value) in values.iter_mut().flat_map(|v| v.iter_mut()).enumerate() {
+            *value = f32::from_bits(read_u32(&facet[i * 4..]));
+        }
+        push_facet(&mut vertices, values[0], [values[1], values[2], values[3]]);
+    }
+    Ok(vertices)
+}
+
+fn parse_ascii(bytes: &[u8]) -> Result<Vec<PosNorm>, StlError> {
+    let mut vertices = Vec::new();
+    let mut normal = [0.0; 3];
+    let mut corners = Vec::with_capacity(3);
+
+    for (index, line) in bytes.split(|&byte| byte == b'\n').enumerate() {
+        let line_number = index + 1;
+        let error = |message: &str| StlError::Syntax {
+            line: line_number,
+            message: message.to_string(),
+        };
+        let line = str::from_utf8(line).map_err(|_| error("Line is not valid UTF-8"))?;
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("facet") => {
+                if words.next() != Some("normal") {
+                    return Err(error("Expected `facet normal`"));
+                }
+                normal = parse_vector(&mut words).ok_or_else(|| error("Invalid normal"))?;
+                corners.clear();
+            }
+            Some("vertex") => {
+                if corners.len() == 3 {
+                    return Err(error("Facet has more than 3 vertices"));
+                }
+                corners.push(parse_vector(&mut words).ok_or_else(|| error("Invalid vertex"))?);
+            }
+            Some("endfacet") => {
+                if corners.len() != 3 {
+                    return Err(error("Facet has less than 3 vertices"));
+                }
+                push_facet(&mut vertices, normal, [corners[0], corners[1], corners[2]]);
+            }
+            _ => {}
+        }
+    }
+    Ok(vertices)
+}
+
+fn parse_vector<'b, I>(words: &mut I) -> Option<[f32; 3]>
+where
+    I: Iterator<Item = &'b str>,
+{
+    let mut vector = [0.0; 3];
+    for value in &mut vector {
+        *value = words.next()?.parse().ok()?;
+    }
+    Some(vector)
+}
+
+fn push_facet(vertices: &mut Vec<PosNorm>, normal: [f32; 3], corners: [[f32; 3]; 3]) {
+    let normal = if normal == [0.0; 3] {
+        facet_normal(corners)
+    } else {
+        normal
+    };
+    for &corner in &corners {
+        vertices.push(PosNorm {
+            position: corner.into(),
+            normal: normal.into(),
+        });
+    }
+}
+
+/// Unit normal of counter-clockwise triangle. Zero for degenerate triangles.
+fn facet_normal(corners: [[f32; 3]; 3]) -> [f32; 3] {
+    normalize(cross(
+        sub(corners[1], corners[0]),
+        sub(corners[2], corners[0]),
+    ))
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    bytes[..4]
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | byte as u32)
+}
+
+fn le_bytes(value: u32) -> [u8; 4] {
+    [
+        value as u8,
+        (value >> 8) as u8,
+        (value >> 16) as u8,
+        (value >> 24) as u8,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vertex::Normal;
+
+    const QUAD: &[u8] = b"solid quad
+  facet normal 0 0 1
+    outer loop
+      vertex 0 0 0
+      vertex 1 0 0
+      vertex 1 1 0
+    endloop
+  endfacet
+  facet normal 0 0 0
+    outer loop
+      vertex 0 0 0
+      vertex 1 1 0
+      vertex 0 1 0
+    endloop
+  endfacet
+endsolid quad
+";
+
+    fn vertices(builder: &MeshBuilder) -> Vec<PosNorm> {
+        let positions = builder.attribute::<Position>().unwrap();
+        let normals = builder.attribute::<Normal>().unwrap();
+        positions
+            .zip(normals)
+            .map(|(position, normal)| PosNorm { position, normal })
+            .collect()
+    }
+
+    #[test]
+    fn ascii() {
+        let builder = parse_stl(QUAD, false).unwrap();
+        assert!(builder.indices().is_none());
+        let vertices = vertices(&builder);
+        assert_eq!(vertices.len(), 6);
+        assert_eq!(vertices[4].position, Position([1.0, 1.0, 0.0]));
+        // Zero normal is computed from the winding.
+        assert!(vertices.iter().all(|v| v.normal.0 == [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn weld() {
+        let builder = parse_stl(QUAD, true).unwrap();
+        assert_eq!(vertices(&builder).len(), 4);
+        assert_eq!(
+            builder.indices().unwrap().collect::<Vec<_>>(),
+            vec![0, 1, 2, 0, 2, 3]
+        );
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let builder = parse_stl(QUAD, true).unwrap();
+        let mut bytes = Vec::new();
+        builder.write_stl(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 84 + 2 * 50);
+        assert_eq!(read_u32(&bytes[80..]), 2);
+
+        let read = parse_stl(&bytes, false).unwrap();
+        assert_eq!(vertices(&read), vertices(&parse_stl(QUAD, false).unwrap()));
+
+        // Binary data may start with `solid` as well.
+        bytes[..5].copy_from_slice(b"solid");
+        assert_eq!(vertices(&parse_stl(&bytes, false).unwrap()).len(), 6);
+    }
+
+    #[test]
+    fn errors() {
+        let mut bytes = vec![0; 84];
+        bytes[80] = 2;
+        bytes.extend_from_slice(&[0; 60]);
+        match parse_stl(&bytes, false) {
+            Err(StlError::UnexpectedEnd {
+                expected: 184,
+                found: 144,
+            }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+
+        // Facet count is not trusted for allocation or size arithmetic.
+        let mut bytes = vec![0; 84];
+        bytes[80..84].copy_from_slice(&[0xff; 4]);
+        let expected = (u32::MAX as usize)
+            .checked_mul(50)
+            .and_then(|size| size.checked_add(84));
+        assert_eq!(binary_size(&bytes), expected);
+        match parse_stl(&bytes, false) {
+            Err(StlError::UnexpectedEnd { found: 84, .. }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+
+        let ascii = b"solid\nfacet normal 0 0 1\nvertex 0 0 0\nvertex 1 0 0\nendfacet\n";
+        match parse_stl(ascii, false) {
+            Err(StlError::Syntax { line: 5, .. }) => {}
+            other => panic!("Unexpected result {:?}", other.map(|_| ())),
+        }
+
+        let mut builder = parse_stl(QUAD, true).unwrap();
+        builder.set_prim_type(Primitive::LineList);
+        match builder.write_stl(Vec::new()) {
+            Err(StlError::UnsupportedPrimitive(Primitive::LineList)) => {}
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
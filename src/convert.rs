@@ -0,0 +1,626 @@
+//!
+//! Convert vertex data between vertex formats.
+//!
+
+use std::borrow::Cow;
+
+use hal::format::Format;
+use hal::pso::{ElemStride, InstanceRate};
+
+use mesh::{MeshBuilder, MeshBuilderError};
+use pack::{
+    f16_to_f32, f32_to_f16, from_snorm, from_unorm, pack_snorm_a2b10g10r10, to_snorm, to_unorm,
+    unpack_snorm_a2b10g10r10,
+};
+use utils::{read_pod, write_pod};
+use vertex::{VertexAttribute, VertexFormat, VertexFormatError};
+
+/// Value of attribute missing in source data.
+/// Missing components of converted attributes are taken from it as well.
+pub const DEFAULT_VALUE: [f64; 4] = [0.0, 0.0, 0.0, 1.0];
+
+/// Error returned when vertex data can't be converted.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum ConvertError {
+    /// Target vertex format is malformed.
+    #[fail(display = "Target vertex format is invalid: {}", _0)]
+    InvalidFormat(#[cause] VertexFormatError),
+
+    /// Source vertex data of `MeshBuilder` is malformed.
+    #[fail(display = "Source data is invalid: {}", _0)]
+    InvalidSource(#[cause] MeshBuilderError),
+
+    /// Source vertex format is malformed.
+    #[fail(display = "Source vertex format is invalid: {}", _0)]
+    InvalidSourceFormat(#[cause] VertexFormatError),
+
+    /// Size of source data is not multiple of source format's stride.
+    #[fail(
+        display = "Source data has size {} which is not multiple of stride {}",
+        size, stride
+    )]
+    SourceSize {
+        /// Size of the source data in bytes.
+        size: usize,
+        /// Stride of the source format.
+        stride: ElemStride,
+    },
+
+    /// Per-instance buffers with same rate have different number of elements.
+    #[fail(
+        display = "Vertex buffer {} has {} instances while previous ones with rate {} have {}",
+        index, found, rate, expected
+    )]
+    InstanceCountMismatch {
+        /// Index of the vertex buffer.
+        index: usize,
+        /// Instance rate of the buffers.
+        rate: InstanceRate,
+        /// Number of elements in previous buffers.
+        expected: usize,
+        /// Number of elements in the buffer.
+        found: usize,
+    },
+
+    /// Attribute has format that can't be converted.
+    #[fail(display = "Attribute \"{}\" has unsupported format {:?}", name, format)]
+    UnsupportedFormat {
+        /// Name of the attribute.
+        name: String,
+        /// Format of the attribute.
+        format: Format,
+    },
+
+    /// Attribute can't be converted between integer and non-integer formats.
+    #[fail(
+        display = "Attribute \"{}\" can't be converted from {:?} to {:?}",
+        name, from, to
+    )]
+    IncompatibleFormats {
+        /// Name of the attribute.
+        name: String,
+        /// Source format.
+        from: Format,
+        /// Target format.
+        to: Format,
+    },
+}
+
+/// Convert vertices from one format to another.
+///
+/// Attributes are matched by name.
+/// Attributes missing in target format are dropped.
+/// Attributes missing in source format are filled with `DEFAULT_VALUE`.
+/// Element formats are converted (`f32` to `f16` or to normalized integers,
+/// `Rgb32Float` to `Rgba32Float` etc.) except between integer and non-integer formats.
+pub fn convert_vertices(
+    vertices: &[u8],
+    from: &VertexFormat,
+    to: &VertexFormat,
+) -> Result<Vec<u8>, ConvertError> {
+    from.validate().map_err(ConvertError::InvalidSourceFormat)?;
+    if vertices.len() % from.stride as usize != 0 {
+        return Err(ConvertError::SourceSize {
+            size: vertices.len(),
+            stride: from.stride,
+        });
+    }
+    let count = vertices.len() / from.stride as usize;
+    convert_sources(&[(vertices, from)], to, count)
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Build new `MeshBuilder` with vertex data converted into specified formats.
+    ///
+    /// Each target format is filled from all source buffers with same input rate,
+    /// so attributes can be gathered from several buffers into one or split apart.
+    /// Per-instance source buffers with same rate must have same number of elements.
+    /// See `convert_vertices` for conversion rules.
+    /// Indices and primitive type are kept.
+    pub fn convert(&self, formats: &[VertexFormat<'static>]) -> Result<Self, ConvertError> {
+        self.validate().map_err(ConvertError::InvalidSource)?;
+
+        let mut builder = MeshBuilder {
+            vertices: Default::default(),
+            indices: self.indices.clone(),
+            narrow_indices: self.narrow_indices,
+            prim: self.prim,
+            morph_targets: self.morph_targets.clone(),
+        };
+
+        for format in formats {
+            let sources = self
+                .vertices
+                .iter()
+                .enumerate()
+                .filter(|&(_, (_, source))| source.rate == format.rate)
+                .map(|(index, (vertices, source))| (index, &**vertices, source))
+                .collect::<Vec<_>>();
+
+            let count = if format.is_per_instance() {
+                // Validation ensures only equal number of vertices in per-vertex buffers.
+                let mut count = None;
+                for &(index, vertices, source) in &sources {
+                    let found = vertices.len() / source.stride as usize;
+                    match count {
+                        None => count = Some(found),
+                        Some(expected) if expected != found => {
+                            return Err(ConvertError::InstanceCountMismatch {
+                                index,
+                                rate: format.rate,
+                                expected,
+                                found,
+                            });
+                        }
+                        _ => {}
+                    }
+                }
+                count.unwrap_or(0)
+            } else {
+                self.vertex_count()
+                    .map_err(ConvertError::InvalidSource)?
+                    .unwrap_or(0) as usize
+            };
+            let sources = sources
+                .into_iter()
+                .map(|(_, vertices, source)| (vertices, source))
+                .collect::<Vec<_>>();
+
+            let vertices = convert_sources(&sources, format, count)?;
+            builder
+                .vertices
+                .push((Cow::Owned(vertices), format.clone()));
+        }
+
+        Ok(builder)
+    }
+}
+
+/// Gather attributes of the target format from sources.
+fn convert_sources<'b, 'c>(
+    sources: &[(&'b [u8], &'b VertexFormat<'c>)],
+    to: &VertexFormat,
+    count: usize,
+) -> Result<Vec<u8>, ConvertError> {
+    to.validate().map_err(ConvertError::InvalidFormat)?;
+
+    let stride = to.stride as usize;
+    let mut result = vec![0u8; stride * count];
+
+    for attribute in to.attributes.iter() {
+        let target = element_layout(attribute.element.format).ok_or_else(|| {
+            ConvertError::UnsupportedFormat {
+                name: attribute.name.to_string(),
+                format: attribute.element.format,
+            }
+        })?;
+
+        match find_source(sources, attribute) {
+            Some((vertices, from, source)) => {
+                let layout = element_layout(source.element.format).ok_or_else(|| {
+                    ConvertError::UnsupportedFormat {
+                        name: source.name.to_string(),
+                        format: source.element.format,
+                    }
+                })?;
+                if layout.kind.is_integer() != target.kind.is_integer() {
+                    return Err(ConvertError::IncompatibleFormats {
+                        name: attribute.name.to_string(),
+                        from: source.element.format,
+                        to: attribute.element.format,
+                    });
+                }
+                for index in 0..count {
+                    let value = decode_element(
+                        vertices,
+                        index * from.stride as usize + source.element.offset as usize,
+                        layout,
+                    );
+                    encode_element(
+                        &mut result,
+                        index * stride + attribute.element.offset as usize,
+                        target,
+                        value,
+                    );
+                }
+            }
+            None => {
+                for index in 0..count {
+                    encode_element(
+                        &mut result,
+                        index * stride + attribute.element.offset as usize,
+                        target,
+                        DEFAULT_VALUE,
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Find source buffer that contains attribute with same name.
+fn find_source<'b, 'c>(
+    sources: &[(&'b [u8], &'b VertexFormat<'c>)],
+    attribute: &VertexAttribute,
+) -> Option<(&'b [u8], &'b VertexFormat<'c>, &'b VertexAttribute<'c>)> {
+    sources
+        .iter()
+        .filter_map(|&(vertices, format)| {
+            format
+                .attributes
+                .iter()
+                .find(|source| source.name == attribute.name)
+                .map(|source| (vertices, format, source))
+        })
+        .next()
+}
+
+/// Representation of element's components.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelKind {
+    /// Unsigned normalized integer.
+    Unorm,
+    /// Signed normalized integer.
+    Inorm,
+    /// Unsigned integer.
+    Uint,
+    /// Signed integer.
+    Int,
+    /// Floating point.
+    Float,
+}
+
+impl ChannelKind {
+    fn is_integer(&self) -> bool {
+        matches!(*self, ChannelKind::Uint | ChannelKind::Int)
+    }
+}
+
+/// Layout of element's components.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ElementLayout {
+    /// Representation of components.
+    pub kind: ChannelKind,
+    /// Number of bits per component.
+    pub bits: u32,
+    /// Number of components.
+    pub channels: usize,
+    /// Components are packed as `A2B10G10R10`.
+    pub packed: bool,
+}
+
+/// Get layout of element with specified format.
+/// Returns `None` for formats that can't be decoded.
+pub fn element_layout(format: Format) -> Option<ElementLayout> {
+    use self::ChannelKind::*;
+
+    let (kind, bits, channels) = match format {
+        Format::A2b10g10r10Unorm => {
+            return Some(ElementLayout {
+                kind: Unorm,
+                bits: 10,
+                channels: 4,
+                packed: true,
+            })
+        }
+        Format::A2b10g10r10Inorm => {
+            return Some(ElementLayout {
+                kind: Inorm,
+                bits: 10,
+                channels: 4,
+                packed: true,
+            })
+        }
+
+        Format::R8Unorm => (Unorm, 8, 1),
+        Format::Rg8Unorm => (Unorm, 8, 2),
+        Format::Rgb8Unorm => (Unorm, 8, 3),
+        Format::Rgba8Unorm => (Unorm, 8, 4),
+        Format::R8Inorm => (Inorm, 8, 1),
+        Format::Rg8Inorm => (Inorm, 8, 2),
+        Format::Rgb8Inorm => (Inorm, 8, 3),
+        Format::Rgba8Inorm => (Inorm, 8, 4),
+        Format::R8Uint => (Uint, 8, 1),
+        Format::Rg8Uint => (Uint, 8, 2),
+        Format::Rgb8Uint => (Uint, 8, 3),
+        Format::Rgba8Uint => (Uint, 8, 4),
+        Format::R8Int => (Int, 8, 1),
+        Format::Rg8Int => (Int, 8, 2),
+        Format::Rgb8Int => (Int, 8, 3),
+        Format::Rgba8Int => (Int, 8, 4),
+
+        Format::R16Unorm => (Unorm, 16, 1),
+        Format::Rg16Unorm => (Unorm, 16, 2),
+        Format::Rgb16Unorm => (Unorm, 16, 3),
+        Format::Rgba16Unorm => (Unorm, 16, 4),
+        Format::R16Inorm => (Inorm, 16, 1),
+        Format::Rg16Inorm => (Inorm, 16, 2),
+        Format::Rgb16Inorm => (Inorm, 16, 3),
+        Format::Rgba16Inorm => (Inorm, 16, 4),
+        Format::R16Uint => (Uint, 16, 1),
+        Format::Rg16Uint => (Uint, 16, 2),
+        Format::Rgb16Uint => (Uint, 16, 3),
+        Format::Rgba16Uint => (Uint, 16, 4),
+        Format::R16Int => (Int, 16, 1),
+        Format::Rg16Int => (Int, 16, 2),
+        Format::Rgb16Int => (Int, 16, 3),
+        Format::Rgba16Int => (Int, 16, 4),
+        Format::R16Float => (Float, 16, 1),
+        Format::Rg16Float => (Float, 16, 2),
+        Format::Rgb16Float => (Float, 16, 3),
+        Format::Rgba16Float => (Float, 16, 4),
+
+        Format::R32Uint => (Uint, 32, 1),
+        Format::Rg32Uint => (Uint, 32, 2),
+        Format::Rgb32Uint => (Uint, 32, 3),
+        Format::Rgba32Uint => (Uint, 32, 4),
+        Format::R32Int => (Int, 32, 1),
+        Format::Rg32Int => (Int, 32, 2),
+        Format::Rgb32Int => (Int, 32, 3),
+        Format::Rgba32Int => (Int, 32, 4),
+        Format::R32Float => (Float, 32, 1),
+        Format::Rg32Float => (Float, 32, 2),
+        Format::Rgb32Float => (Float, 32, 3),
+        Format::Rgba32Float => (Float, 32, 4),
+
+        _ => return None,
+    };
+
+    Some(ElementLayout {
+        kind,
+        bits,
+        channels,
+        packed: false,
+    })
+}
+
+/// Decode element at `offset`.
+/// Missing components are taken from `DEFAULT_VALUE`.
+pub fn decode_element(bytes: &[u8], offset: usize, layout: ElementLayout) -> [f64; 4] {
+    let mut value = DEFAULT_VALUE;
+
+    if layout.packed {
+        let packed: u32 = read_pod(bytes, offset);
+        match layout.kind {
+            ChannelKind::Inorm => {
+                let unpacked = unpack_snorm_a2b10g10r10(packed);
+                for (value, unpacked) in value.iter_mut().zip(&unpacked) {
+                    *value = *unpacked as f64;
+                }
+            }
+            _ => {
+                for (i, value) in value[..3].iter_mut().enumerate() {
+                    *value = from_unorm((packed >> (i * 10)) & 0x3ff, 10) as f64;
+                }
+                value[3] = from_unorm(packed >> 30, 2) as f64;
+            }
+        }
+        return value;
+    }
+
+    let size = layout.bits as usize / 8;
+    for (i, value) in value[..layout.channels].iter_mut().enumerate() {
+        let offset = offset + i * size;
+        *value = match (layout.kind, layout.bits) {
+            (ChannelKind::Unorm, 8) => from_unorm(read_pod::<u8>(bytes, offset) as u32, 8) as f64,
+            (ChannelKind::Unorm, 16) => {
+                from_unorm(read_pod::<u16>(bytes, offset) as u32, 16) as f64
+            }
+            (ChannelKind::Inorm, 8) => from_snorm(read_pod::<i8>(bytes, offset) as i32, 8) as f64,
+            (ChannelKind::Inorm, 16) => {
+                from_snorm(read_pod::<i16>(bytes, offset) as i32, 16) as f64
+            }
+            (ChannelKind::Uint, 8) => read_pod::<u8>(bytes, offset) as f64,
+            (ChannelKind::Uint, 16) => read_pod::<u16>(bytes, offset) as f64,
+            (ChannelKind::Uint, 32) => read_pod::<u32>(bytes, offset) as f64,
+            (ChannelKind::Int, 8) => read_pod::<i8>(bytes, offset) as f64,
+            (ChannelKind::Int, 16) => read_pod::<i16>(bytes, offset) as f64,
+            (ChannelKind::Int, 32) => read_pod::<i32>(bytes, offset) as f64,
+            (ChannelKind::Float, 16) => f16_to_f32(read_pod(bytes, offset)) as f64,
+            (ChannelKind::Float, 32) => read_pod::<f32>(bytes, offset) as f64,
+            _ => unreachable!("Unsupported layout {:?}", layout),
+        };
+    }
+    value
+}
+
+/// Encode element at `offset`.
+/// Values are clamped to the range of the target representation.
+pub fn encode_element(bytes: &mut [u8], offset: usize, layout: ElementLayout, value: [f64; 4]) {
+    if layout.packed {
+        let packed = match layout.kind {
+            ChannelKind::Inorm => pack_snorm_a2b10g10r10([
+                value[0] as f32,
+                value[1] as f32,
+                value[2] as f32,
+                value[3] as f32,
+            ]),
+            _ => {
+                to_unorm(value[0] as f32, 10)
+                    | to_unorm(value[1] as f32, 10) << 10
+                    | to_unorm(value[2] as f32, 10) << 20
+                    | to_unorm(value[3] as f32, 2) << 30
+            }
+        };
+        write_pod(bytes, offset, packed);
+        return;
+    }
+
+    let size = layout.bits as usize / 8;
+    for (i, &v) in value[..layout.channels].iter().enumerate() {
+        let offset = offset + i * size;
+        match (layout.kind, layout.bits) {
+            (ChannelKind::Unorm, 8) => write_pod(bytes, offset, to_unorm(v as f32, 8) as u8),
+            (ChannelKind::Unorm, 16) => write_pod(bytes, offset, to_unorm(v as f32, 16) as u16),
+            (ChannelKind::Inorm, 8) => write_pod(bytes, offset, to_snorm(v as f32, 8) as i8),
+            (ChannelKind::Inorm, 16) => write_pod(bytes, offset, to_snorm(v as f32, 16) as i16),
+            (ChannelKind::Uint, 8) => write_pod(bytes, offset, clamp_round(v, 0.0, 255.0) as u8),
+            (ChannelKind::Uint, 16) => {
+                write_pod(bytes, offset, clamp_round(v, 0.0, 65535.0) as u16)
+            }
+            (ChannelKind::Uint, 32) => {
+                write_pod(bytes, offset, clamp_round(v, 0.0, 4294967295.0) as u32)
+            }
+            (ChannelKind::Int, 8) => write_pod(bytes, offset, clamp_round(v, -128.0, 127.0) as i8),
+            (ChannelKind::Int, 16) => {
+                write_pod(bytes, offset, clamp_round(v, -32768.0, 32767.0) as i16)
+            }
+            (ChannelKind::Int, 32) => write_pod(
+                bytes,
+                offset,
+                clamp_round(v, -2147483648.0, 2147483647.0) as i32,
+            ),
+            (ChannelKind::Float, 16) => write_pod(bytes, offset, f32_to_f16(v as f32)),
+            (ChannelKind::Float, 32) => write_pod(bytes, offset, v as f32),
+            _ => unreachable!("Unsupported layout {:?}", layout),
+        }
+    }
+}
+
+fn clamp_round(value: f64, min: f64, max: f64) -> f64 {
+    value.round().max(min).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::size_of;
+    use utils::cast_slice;
+    use vertex::{
+        AsVertexFormat, Color, DynamicAttribute, HalfTexCoord, JointIndices, Normal, PackedNormal,
+        PosNorm, PosNormTexPacked, Position,
+    };
+
+    fn pos_norm() -> Vec<PosNorm> {
+        vec![
+            PosNorm {
+                position: [1.0, 2.0, 3.0].into(),
+                normal: [0.0, 0.0, 1.0].into(),
+            },
+            PosNorm {
+                position: [-1.0, 0.5, 0.0].into(),
+                normal: [0.0, -1.0, 0.0].into(),
+            },
+        ]
+    }
+
+    #[test]
+    fn convert_to_packed() {
+        let vertices = pos_norm();
+        let converted = convert_vertices(
+            cast_slice(&vertices),
+            &PosNorm::VERTEX_FORMAT,
+            &PosNormTexPacked::VERTEX_FORMAT,
+        )
+        .unwrap();
+        // Bytes of `Vec<u8>` are not necessarily aligned for `PosNormTexPacked`.
+        let size = size_of::<PosNormTexPacked>();
+        assert_eq!(converted.len(), 2 * size);
+        for (index, vertex) in vertices.iter().enumerate() {
+            let converted: PosNormTexPacked = read_pod(&converted, index * size);
+            assert_eq!(converted.position, vertex.position);
+            // Alpha channel of the normal is taken from default value.
+            assert_eq!(
+                converted.normal.0 & 0x3fff_ffff,
+                PackedNormal::from(vertex.normal).0
+            );
+            // Missing attribute is filled with default value.
+            assert_eq!(converted.tex_coord, HalfTexCoord([0, 0]));
+        }
+    }
+
+    #[test]
+    fn convert_invalid_source() {
+        let vertices = pos_norm();
+        let bytes = cast_slice(&vertices);
+        assert_eq!(
+            convert_vertices(
+                &bytes[..7],
+                &PosNorm::VERTEX_FORMAT,
+                &Position::VERTEX_FORMAT
+            ),
+            Err(ConvertError::SourceSize {
+                size: 7,
+                stride: 24
+            })
+        );
+        let zero_stride = VertexFormat {
+            stride: 0,
+            ..PosNorm::VERTEX_FORMAT
+        };
+        assert_eq!(
+            convert_vertices(bytes, &zero_stride, &Position::VERTEX_FORMAT),
+            Err(ConvertError::InvalidSourceFormat(
+                VertexFormatError::ZeroStride
+            ))
+        );
+    }
+
+    #[test]
+    fn convert_integer_to_float() {
+        let target = VertexFormat::from_attributes(vec![DynamicAttribute::new(
+            "joint_indices",
+            Format::Rgba32Float,
+        )]);
+        assert_eq!(
+            convert_vertices(
+                cast_slice(&[JointIndices([0, 1, 2, 3])]),
+                &JointIndices::VERTEX_FORMAT,
+                &target,
+            ),
+            Err(ConvertError::IncompatibleFormats {
+                name: "joint_indices".into(),
+                from: Format::Rgba16Uint,
+                to: Format::Rgba32Float,
+            })
+        );
+    }
+
+    #[test]
+    fn convert_gathers_buffers() {
+        let vertices = pos_norm();
+        let builder = MeshBuilder::new()
+            .with_vertices(vertices.iter().map(|v| v.position).collect::<Vec<_>>())
+            .unwrap()
+            .with_vertices(vertices.iter().map(|v| v.normal).collect::<Vec<_>>())
+            .unwrap();
+        let converted = builder.convert(&[PosNorm::VERTEX_FORMAT]).unwrap();
+        assert_eq!(converted.vertices.len(), 1);
+        assert_eq!(&*converted.vertices[0].0, cast_slice(&vertices));
+        assert_eq!(converted.vertices[0].1, PosNorm::VERTEX_FORMAT);
+    }
+
+    #[test]
+    fn convert_instance_count_mismatch() {
+        let builder = MeshBuilder::new()
+            .with_instances(vec![Position([0.0; 3]); 2], 1)
+            .unwrap()
+            .with_instances(vec![Color([1.0; 4]); 3], 1)
+            .unwrap();
+        let target = VertexFormat::from_attributes(vec![
+            DynamicAttribute::of::<Position>(),
+            DynamicAttribute::of::<Color>(),
+        ])
+        .per_instance(1);
+        assert_eq!(
+            builder.convert(&[target]).err(),
+            Some(ConvertError::InstanceCountMismatch {
+                index: 1,
+                rate: 1,
+                expected: 2,
+                found: 3,
+            })
+        );
+
+        let builder = MeshBuilder::new()
+            .with_instances(vec![Position([0.0; 3]); 3], 1)
+            .unwrap()
+            .with_instances(vec![Normal([0.0; 3]); 2], 2)
+            .unwrap();
+        assert!(builder
+            .convert(&[Position::VERTEX_FORMAT.per_instance(1)])
+            .is_ok());
+    }
+}
@@ -0,0 +1,311 @@
+//!
+//! Interleaving and deinterleaving of vertex buffers in `MeshBuilder`.
+//!
+
+use std::borrow::Cow;
+
+use hal::pso::InstanceRate;
+
+use mesh::{MeshBuilder, MeshBuilderError};
+use utils::format_size;
+use vertex::{DynamicAttribute, VertexFormat};
+
+/// Error returned when buffers of `MeshBuilder` can't be re-laid out.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum LayoutError {
+    /// Source vertex data of `MeshBuilder` is malformed.
+    #[fail(display = "Source data is invalid: {}", _0)]
+    InvalidSource(#[cause] MeshBuilderError),
+
+    /// Per-instance buffers with same rate have different number of elements.
+    #[fail(
+        display = "Vertex buffer {} has {} instances while previous ones with rate {} have {}",
+        index, found, rate, expected
+    )]
+    InstanceCountMismatch {
+        /// Index of the vertex buffer.
+        index: usize,
+        /// Instance rate of the buffers.
+        rate: InstanceRate,
+        /// Number of elements in previous buffers.
+        expected: usize,
+        /// Number of elements in the buffer.
+        found: usize,
+    },
+
+    /// Several buffers with same rate have attribute with same name.
+    #[fail(
+        display = "Vertex buffer {} has attribute \"{}\" already present in previous buffers",
+        index, name
+    )]
+    DuplicateAttribute {
+        /// Index of the vertex buffer.
+        index: usize,
+        /// Name of the attribute.
+        name: String,
+    },
+
+    /// Attribute of target format is not found in buffers with same rate and format.
+    #[fail(display = "Attribute \"{}\" is not found in source buffers", name)]
+    MissingAttribute {
+        /// Name of the attribute.
+        name: String,
+    },
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Merge all per-vertex buffers into single interleaved buffer.
+    /// Per-instance buffers with same rate are merged the same way.
+    ///
+    /// Attributes keep their names and formats and are placed in order of buffers.
+    /// Fails if the builder doesn't pass `MeshBuilder::validate`,
+    /// e.g. if per-vertex buffers have different number of vertices,
+    /// if per-instance buffers with same rate have different number of elements
+    /// or if several buffers with same rate have attribute with same name.
+    pub fn interleave(&self) -> Result<Self, LayoutError> {
+        self.check_layout()?;
+
+        let mut rates: Vec<InstanceRate> = Vec::new();
+        for (_, format) in &self.vertices {
+            if !rates.contains(&format.rate) {
+                rates.push(format.rate);
+            }
+        }
+
+        let formats = rates
+            .into_iter()
+            .map(|rate| {
+                let mut attributes: Vec<DynamicAttribute> = Vec::new();
+                for (_, format) in self.vertices.iter().filter(|(_, f)| f.rate == rate) {
+                    for attribute in format.attributes.iter() {
+                        attributes.push(DynamicAttribute::new(
+                            attribute.name.clone(),
+                            attribute.element.format,
+                        ));
+                    }
+                }
+                VertexFormat {
+                    rate,
+                    ..VertexFormat::from_attributes(attributes)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        self.relayout(&formats)
+    }
+
+    /// Split all buffers into separate buffers with single attribute each.
+    ///
+    /// Attributes keep their names, formats and input rates.
+    /// Fails on the same conditions as `MeshBuilder::interleave`.
+    pub fn deinterleave(&self) -> Result<Self, LayoutError> {
+        self.check_layout()?;
+
+        let mut formats: Vec<VertexFormat<'static>> = Vec::new();
+        for (_, format) in &self.vertices {
+            for attribute in format.attributes.iter() {
+                formats.push(VertexFormat {
+                    rate: format.rate,
+                    ..VertexFormat::from_attributes(Some(DynamicAttribute::new(
+                        attribute.name.clone(),
+                        attribute.element.format,
+                    )))
+                });
+            }
+        }
+
+        self.relayout(&formats)
+    }
+
+    /// Check that buffers can be re-laid out.
+    fn check_layout(&self) -> Result<(), LayoutError> {
+        self.validate().map_err(LayoutError::InvalidSource)?;
+
+        for (index, (vertices, format)) in self.vertices.iter().enumerate() {
+            let previous = &self.vertices[..index];
+            for (position, attribute) in format.attributes.iter().enumerate() {
+                let duplicate = format.attributes[..position]
+                    .iter()
+                    .chain(
+                        previous
+                            .iter()
+                            .filter(|(_, f)| f.rate == format.rate)
+                            .flat_map(|(_, f)| f.attributes.iter()),
+                    )
+                    .any(|a| a.name == attribute.name);
+                if duplicate {
+                    return Err(LayoutError::DuplicateAttribute {
+                        index,
+                        name: attribute.name.to_string(),
+                    });
+                }
+            }
+
+            if format.is_per_instance() {
+                let found = vertices.len() / format.stride as usize;
+                let expected = previous
+                    .iter()
+                    .find(|(_, f)| f.rate == format.rate)
+                    .map(|(v, f)| v.len() / f.stride as usize);
+                match expected {
+                    Some(expected) if expected != found => {
+                        return Err(LayoutError::InstanceCountMismatch {
+                            index,
+                            rate: format.rate,
+                            expected,
+                            found,
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Copy attributes into buffers with specified formats.
+    /// Every attribute of the formats must be present in buffers with same rate
+    /// and have same format there.
+    fn relayout(&self, formats: &[VertexFormat<'static>]) -> Result<Self, LayoutError> {
+        let mut builder = MeshBuilder {
+            vertices: Default::default(),
+            indices: self.indices.clone(),
+            narrow_indices: self.narrow_indices,
+            prim: self.prim,
+            morph_targets: self.morph_targets.clone(),
+        };
+
+        for format in formats {
+            let sources = self
+                .vertices
+                .iter()
+                .filter(|(_, source)| source.rate == format.rate)
+                .collect::<Vec<_>>();
+            let count = sources.first().map_or(0, |(vertices, source)| {
+                vertices.len() / source.stride as usize
+            });
+
+            let stride = format.stride as usize;
+            let mut vertices = vec![0u8; stride * count];
+            for attribute in format.attributes.iter() {
+                let (source, source_format, source_attribute) = sources
+                    .iter()
+                    .filter_map(|(vertices, source)| {
+                        source
+                            .attributes
+                            .iter()
+                            .find(|a| {
+                                a.name == attribute.name
+                                    && a.element.format == attribute.element.format
+                            })
+                            .map(|a| (vertices, source, a))
+                    })
+                    .next()
+                    .ok_or_else(|| LayoutError::MissingAttribute {
+                        name: attribute.name.to_string(),
+                    })?;
+
+                let size = format_size(attribute.element.format) as usize;
+                for index in 0..count {
+                    let from = index * source_format.stride as usize
+                        + source_attribute.element.offset as usize;
+                    let to = index * stride + attribute.element.offset as usize;
+                    vertices[to..to + size].copy_from_slice(&source[from..from + size]);
+                }
+            }
+
+            builder
+                .vertices
+                .push((Cow::Owned(vertices), format.clone()));
+        }
+
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::cast_slice;
+    use vertex::{AsVertexFormat, Color, Normal, PosNorm, Position};
+
+    fn pos_norm() -> MeshBuilder<'static> {
+        MeshBuilder::new()
+            .with_vertices(vec![Position([1.0, 2.0, 3.0]), Position([4.0, 5.0, 6.0])])
+            .unwrap()
+            .with_vertices(vec![Normal([0.0, 0.0, 1.0]), Normal([0.0, 1.0, 0.0])])
+            .unwrap()
+    }
+
+    #[test]
+    fn interleave() {
+        let interleaved = pos_norm().interleave().unwrap();
+        assert_eq!(interleaved.vertices.len(), 1);
+        let (ref vertices, ref format) = interleaved.vertices[0];
+        assert_eq!(*format, PosNorm::VERTEX_FORMAT);
+        let expected = [
+            PosNorm {
+                position: [1.0, 2.0, 3.0].into(),
+                normal: [0.0, 0.0, 1.0].into(),
+            },
+            PosNorm {
+                position: [4.0, 5.0, 6.0].into(),
+                normal: [0.0, 1.0, 0.0].into(),
+            },
+        ];
+        assert_eq!(&vertices[..], cast_slice(&expected));
+    }
+
+    #[test]
+    fn deinterleave() {
+        let builder = pos_norm();
+        let deinterleaved = builder.interleave().unwrap().deinterleave().unwrap();
+        assert_eq!(deinterleaved.vertices, builder.vertices);
+    }
+
+    #[test]
+    fn duplicate_attribute() {
+        let mut builder = pos_norm();
+        builder
+            .add_vertices(vec![Position([0.0; 3]), Position([0.0; 3])])
+            .unwrap();
+        let error = Some(LayoutError::DuplicateAttribute {
+            index: 2,
+            name: "position".into(),
+        });
+        assert_eq!(builder.interleave().err(), error);
+        assert_eq!(builder.deinterleave().err(), error);
+
+        // Same name with different rates is not a duplicate.
+        let mut builder = pos_norm();
+        builder.add_instances(vec![Position([0.0; 3])], 1).unwrap();
+        assert_eq!(builder.interleave().unwrap().vertices.len(), 2);
+    }
+
+    #[test]
+    fn instance_count_mismatch() {
+        let builder = pos_norm()
+            .with_instances(vec![Color([1.0; 4]); 2], 1)
+            .unwrap()
+            .with_instances(vec![Position([0.0; 3]); 3], 1)
+            .unwrap();
+        let error = Some(LayoutError::InstanceCountMismatch {
+            index: 3,
+            rate: 1,
+            expected: 2,
+            found: 3,
+        });
+        assert_eq!(builder.interleave().err(), error);
+        assert_eq!(builder.deinterleave().err(), error);
+    }
+
+    #[test]
+    fn missing_attribute() {
+        assert_eq!(
+            pos_norm().relayout(&[Color::VERTEX_FORMAT]).err(),
+            Some(LayoutError::MissingAttribute {
+                name: "color".into(),
+            })
+        );
+    }
+}
@@ -9,21 +9,96 @@
 #![deny(dead_code)]
 #![deny(unused_must_use)]
 
+#[macro_use]
 extern crate failure;
 extern crate gfx_hal as hal;
+#[cfg(feature = "derive")]
+extern crate gfx_mesh_derive;
 extern crate gfx_render as render;
+#[cfg(feature = "gltf")]
+extern crate gltf;
 
 #[cfg(feature = "serde")]
 #[macro_use]
 extern crate serde;
 extern crate smallvec;
 
+// `failure_derive` implements `Fail` and `Display` inside named constants,
+// so modules with error types allow non-local impls.
+#[allow(unknown_lints, non_local_definitions)]
+mod access;
+mod asset;
+#[allow(unknown_lints, non_local_definitions)]
+mod convert;
+#[allow(unknown_lints, non_local_definitions)]
+mod layout;
+mod math;
+#[allow(unknown_lints, non_local_definitions)]
 mod mesh;
+#[allow(unknown_lints, non_local_definitions)]
+mod morph;
+#[allow(unknown_lints, non_local_definitions)]
+mod normals;
+#[allow(unknown_lints, non_local_definitions)]
+mod optimize;
+mod pack;
+#[allow(unknown_lints, non_local_definitions)]
+mod pipeline;
+pub mod shapes;
+#[allow(unknown_lints, non_local_definitions)]
+mod skin;
+#[cfg(feature = "spirv")]
+#[allow(unknown_lints, non_local_definitions)]
+mod spirv;
+#[allow(unknown_lints, non_local_definitions)]
+mod tangents;
+#[allow(unknown_lints, non_local_definitions)]
 mod utils;
+#[allow(unknown_lints, non_local_definitions)]
 mod vertex;
+#[allow(unknown_lints, non_local_definitions)]
+mod weld;
 
-pub use mesh::{Bind, Incompatible, IndexBuffer, Indices, Mesh, MeshBuilder, VertexBuffer};
+pub use access::{AccessError, AttributeIter, AttributeMut, IndexIter};
+#[cfg(feature = "gltf")]
+pub use asset::{load_gltf, GltfError, GltfPrimitive};
+pub use asset::{load_obj, parse_mtl, parse_obj, ObjError, ObjGroup, ObjMaterial, ObjMesh};
+pub use asset::{load_ply, parse_ply, PlyError};
+pub use asset::{load_stl, parse_stl, StlError};
+pub use convert::{convert_vertices, ConvertError, DEFAULT_VALUE};
+pub use layout::LayoutError;
+pub use mesh::{
+    Bind, Incompatible, IndexBuffer, Indices, Mesh, MeshBuilder, MeshBuilderError, VertexBuffer,
+};
+pub use morph::{MorphAttribute, MorphDeltas, MorphError, MorphTarget, MorphTargetInfo};
+pub use normals::{NormalError, NormalWeighting};
+pub use optimize::{CacheStats, OptimizeError, OptimizeReport};
+pub use pipeline::{Locations, VertexInputDesc, VertexInputDescError};
+pub use skin::SkinningError;
+#[cfg(feature = "spirv")]
+pub use spirv::{SpirvError, VertexInput, VertexInputError, VertexInputs};
+pub use tangents::TangentError;
+pub use utils::{from_bytes, CastError};
 pub use vertex::{
-    AsVertexFormat, Attribute, Color, Normal, PosColor, PosNorm, PosNormTangTex, PosNormTex,
-    PosTex, Position, Query, Tangent, TexCoord, VertexFormat, WithAttribute,
+    indexed_name, AsVertexFormat, Attribute, Color, Color1, Color2, Color3, DynamicAttribute,
+    HalfTexCoord, HalfTexCoord1, HalfTexCoord2, HalfTexCoord3, JointIndices, JointIndices1,
+    JointIndices1U8, JointIndicesU8, JointWeights, JointWeights1, JointWeights1Unorm16,
+    JointWeights1Unorm8, JointWeightsUnorm16, JointWeightsUnorm8, Normal, OctNormal, OctTangent,
+    PackedColor, PackedColor1, PackedColor2, PackedColor3, PackedNormal, PackedTangent, PosColor,
+    PosColorPacked, PosNorm, PosNormTangTex, PosNormTangTexPacked, PosNormTangTexSkin, PosNormTex,
+    PosNormTexPacked, PosNormTexSkin, PosTex, Position, Query, Tangent, TexCoord, TexCoord1,
+    TexCoord2, TexCoord3, VertexAttribute, VertexFormat, VertexFormatError, WithAttribute,
 };
+pub use weld::WeldError;
+
+#[cfg(feature = "derive")]
+pub use gfx_mesh_derive::VertexFormat;
+
+/// Items used by code generated with `#[derive(VertexFormat)]`.
+#[cfg(feature = "derive")]
+#[doc(hidden)]
+pub mod __derive {
+    pub use hal::format::{AsFormat, Format};
+    pub use hal::memory::Pod;
+    pub use hal::pso::Element;
+}
@@ -3,22 +3,29 @@
 //!
 
 use std::borrow::Cow;
+use std::cmp::min;
+use std::collections::HashMap;
 use std::mem::size_of;
+use std::ops::Range;
 
 use failure::Error;
 
-use hal::buffer::{Access, IndexBufferView, Usage};
+use hal::buffer::{Access, IndexBufferView, Offset, Usage};
 use hal::command::RenderSubpassCommon;
 use hal::memory::Properties;
-use hal::pso::VertexBufferSet;
+use hal::pso::{ElemStride, InstanceRate, VertexBufferSet};
 use hal::queue::QueueFamilyId;
-use hal::{Backend, IndexCount, IndexType, Primitive, VertexCount};
+use hal::{Backend, IndexCount, IndexType, InstanceCount, Primitive, VertexCount};
 
 use smallvec::SmallVec;
 
+use access::IndexIter;
+use morph::MorphTargetInfo;
 use render::{Buffer, Factory};
-use utils::{cast_cow, is_slice_sorted, is_slice_sorted_by_key};
-use vertex::{AsVertexFormat, VertexFormat};
+use utils::{
+    cast_cow, cast_vec, is_slice_sorted, is_slice_sorted_by_key, narrow_indices, narrow_to_u16,
+};
+use vertex::{AsVertexFormat, DynamicAttribute, VertexAttribute, VertexFormat, VertexFormatError};
 
 /// Vertex buffer with it's format
 #[derive(Debug)]
@@ -36,12 +43,18 @@ pub struct IndexBuffer<B: Backend> {
     len: IndexCount,
 }
 
-/// Abstracts over two types of indices and their absence.
+/// Abstracts over three types of indices and their absence.
 #[derive(Debug)]
 pub enum Indices<'a> {
     /// No indices.
     None,
 
+    /// `u8` per index.
+    /// Keeps index data of small meshes compact in the builder and in serialized form.
+    /// `gfx-hal` has no 8-bit index type,
+    /// so these are always widened to `u16` when the mesh is built.
+    U8(Cow<'a, [u8]>),
+
     /// `u16` per index.
     U16(Cow<'a, [u16]>),
 
@@ -49,6 +62,24 @@ pub enum Indices<'a> {
     U32(Cow<'a, [u32]>),
 }
 
+impl From<Vec<u8>> for Indices<'static> {
+    fn from(vec: Vec<u8>) -> Self {
+        Indices::U8(vec.into())
+    }
+}
+
+impl<'a> From<&'a [u8]> for Indices<'a> {
+    fn from(slice: &'a [u8]) -> Self {
+        Indices::U8(slice.into())
+    }
+}
+
+impl<'a> From<Cow<'a, [u8]>> for Indices<'a> {
+    fn from(cow: Cow<'a, [u8]>) -> Self {
+        Indices::U8(cow)
+    }
+}
+
 impl From<Vec<u16>> for Indices<'static> {
     fn from(vec: Vec<u16>) -> Self {
         Indices::U16(vec.into())
@@ -85,15 +116,43 @@ impl<'a> From<Cow<'a, [u32]>> for Indices<'a> {
     }
 }
 
+/// Size of indices stored in `MeshBuilder`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub(crate) enum IndexWidth {
+    U8,
+    U16,
+    U32,
+}
+
+impl IndexWidth {
+    /// Size of single index in bytes.
+    pub(crate) fn size(self) -> usize {
+        match self {
+            IndexWidth::U8 => size_of::<u8>(),
+            IndexWidth::U16 => size_of::<u16>(),
+            IndexWidth::U32 => size_of::<u32>(),
+        }
+    }
+}
+
 /// Generics-free mesh builder.
 /// Useful for creating mesh from non-predefined set of data.
 /// Like from glTF.
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MeshBuilder<'a> {
-    vertices: SmallVec<[(Cow<'a, [u8]>, VertexFormat<'static>); 16]>,
-    indices: Option<(Cow<'a, [u8]>, IndexType)>,
-    prim: Primitive,
+    pub(crate) vertices: SmallVec<[(Cow<'a, [u8]>, VertexFormat<'static>); 16]>,
+    pub(crate) indices: Option<(Cow<'a, [u8]>, IndexWidth)>,
+    pub(crate) narrow_indices: bool,
+    pub(crate) prim: Primitive,
+    pub(crate) morph_targets: Vec<MorphTargetInfo>,
+}
+
+impl<'a> Default for MeshBuilder<'a> {
+    fn default() -> Self {
+        MeshBuilder::new()
+    }
 }
 
 impl<'a> MeshBuilder<'a> {
@@ -102,7 +161,9 @@ impl<'a> MeshBuilder<'a> {
         MeshBuilder {
             vertices: SmallVec::new(),
             indices: None,
+            narrow_indices: false,
             prim: Primitive::TriangleList,
+            morph_targets: Vec::new(),
         }
     }
 
@@ -122,33 +183,181 @@ impl<'a> MeshBuilder<'a> {
     {
         self.indices = match indices.into() {
             Indices::None => None,
-            Indices::U16(i) => Some((cast_cow(i), IndexType::U16)),
-            Indices::U32(i) => Some((cast_cow(i), IndexType::U32)),
+            Indices::U8(i) => Some((i, IndexWidth::U8)),
+            Indices::U16(i) => Some((cast_cow(i), IndexWidth::U16)),
+            Indices::U32(i) => Some((cast_cow(i), IndexWidth::U32)),
         };
         self
     }
 
+    /// Narrow indices when the mesh is built.
+    /// See `set_narrow_indices`.
+    pub fn with_narrow_indices(mut self, narrow: bool) -> Self {
+        self.set_narrow_indices(narrow);
+        self
+    }
+
+    /// Upload `u32` indices as `u16` if they fit when the mesh is built.
+    /// Indices fit if all of them are below `0xFFFF`, the `u16` primitive restart index.
+    /// Indices stored in the builder are not changed.
+    ///
+    /// Indices are never uploaded as `u8` since `gfx-hal` has no 8-bit index type.
+    /// `u8` indices are widened to `u16` whether this is enabled or not.
+    ///
+    /// Disabled by default.
+    pub fn set_narrow_indices(&mut self, narrow: bool) -> &mut Self {
+        self.narrow_indices = narrow;
+        self
+    }
+
     /// Add another vertices to the `MeshBuilder`
-    pub fn with_vertices<V, D>(mut self, vertices: D) -> Self
+    pub fn with_vertices<V, D>(mut self, vertices: D) -> Result<Self, MeshBuilderError>
     where
         V: AsVertexFormat + 'a,
         D: Into<Cow<'a, [V]>>,
     {
-        self.add_vertices(vertices);
-        self
+        self.add_vertices(vertices)?;
+        Ok(self)
     }
 
-    /// Add another vertices to the `MeshBuilder`
-    pub fn add_vertices<V, D>(&mut self, vertices: D) -> &mut Self
+    /// Add another vertices to the `MeshBuilder`.
+    /// Fails if `V::VERTEX_FORMAT` is invalid or its stride differs from size of `V`.
+    pub fn add_vertices<V, D>(&mut self, vertices: D) -> Result<&mut Self, MeshBuilderError>
+    where
+        V: AsVertexFormat + 'a,
+        D: Into<Cow<'a, [V]>>,
+    {
+        check_vertex_size::<V>(self.vertices.len(), &V::VERTEX_FORMAT)?;
+        self.add_raw_vertices(cast_cow(vertices.into()), V::VERTEX_FORMAT)
+    }
+
+    /// Add vertices of a format known to be valid, like built-in ones.
+    pub(crate) fn push_vertices<V, D>(&mut self, vertices: D) -> &mut Self
     where
         V: AsVertexFormat + 'a,
         D: Into<Cow<'a, [V]>>,
     {
+        debug_assert!(check_vertex_size::<V>(self.vertices.len(), &V::VERTEX_FORMAT).is_ok());
+        debug_assert!(V::VERTEX_FORMAT.validate().is_ok());
         self.vertices
             .push((cast_cow(vertices.into()), V::VERTEX_FORMAT));
         self
     }
 
+    /// Add per-instance data to the `MeshBuilder`.
+    /// Data advances every `rate` instances.
+    pub fn with_instances<V, D>(
+        mut self,
+        instances: D,
+        rate: InstanceRate,
+    ) -> Result<Self, MeshBuilderError>
+    where
+        V: AsVertexFormat + 'a,
+        D: Into<Cow<'a, [V]>>,
+    {
+        self.add_instances(instances, rate)?;
+        Ok(self)
+    }
+
+    /// Add per-instance data to the `MeshBuilder`.
+    /// Data advances every `rate` instances.
+    /// Fails if `V::VERTEX_FORMAT` is invalid or its stride differs from size of `V`.
+    ///
+    /// # Panics
+    ///
+    /// If `rate` is zero.
+    pub fn add_instances<V, D>(
+        &mut self,
+        instances: D,
+        rate: InstanceRate,
+    ) -> Result<&mut Self, MeshBuilderError>
+    where
+        V: AsVertexFormat + 'a,
+        D: Into<Cow<'a, [V]>>,
+    {
+        let format = V::VERTEX_FORMAT.per_instance(rate);
+        check_vertex_size::<V>(self.vertices.len(), &format)?;
+        self.add_raw_vertices(cast_cow(instances.into()), format)
+    }
+
+    /// Add raw vertices with format known only at runtime to the `MeshBuilder`.
+    /// Format's `rate` defines if data is per-vertex or per-instance.
+    pub fn with_raw_vertices<D>(
+        mut self,
+        vertices: D,
+        format: VertexFormat<'static>,
+    ) -> Result<Self, MeshBuilderError>
+    where
+        D: Into<Cow<'a, [u8]>>,
+    {
+        self.add_raw_vertices(vertices, format)?;
+        Ok(self)
+    }
+
+    /// Add raw vertices with format known only at runtime to the `MeshBuilder`.
+    /// Format is validated and size of the data must be multiple of format's stride.
+    pub fn add_raw_vertices<D>(
+        &mut self,
+        vertices: D,
+        format: VertexFormat<'static>,
+    ) -> Result<&mut Self, MeshBuilderError>
+    where
+        D: Into<Cow<'a, [u8]>>,
+    {
+        let index = self.vertices.len();
+        let vertices = vertices.into();
+        format
+            .validate()
+            .map_err(|error| MeshBuilderError::InvalidFormat { index, error })?;
+        check_buffer_size(index, &vertices, &format)?;
+        self.vertices.push((vertices, format));
+        Ok(self)
+    }
+
+    /// Add per-vertex data of single attribute described at runtime to the `MeshBuilder`.
+    pub fn with_attribute_data<D>(
+        mut self,
+        attribute: DynamicAttribute,
+        data: D,
+    ) -> Result<Self, MeshBuilderError>
+    where
+        D: Into<Cow<'a, [u8]>>,
+    {
+        self.add_attribute_data(attribute, data)?;
+        Ok(self)
+    }
+
+    /// Add per-vertex data of single attribute described at runtime to the `MeshBuilder`.
+    /// Data must be tightly packed values of the attribute's format.
+    pub fn add_attribute_data<D>(
+        &mut self,
+        attribute: DynamicAttribute,
+        data: D,
+    ) -> Result<&mut Self, MeshBuilderError>
+    where
+        D: Into<Cow<'a, [u8]>>,
+    {
+        self.add_raw_vertices(data, VertexFormat::from_attributes(Some(attribute)))
+    }
+
+    /// Find per-vertex buffer that contains attribute with specified name.
+    pub(crate) fn find_attribute(
+        &self,
+        name: &str,
+    ) -> Option<(&[u8], &VertexFormat<'static>, &VertexAttribute<'static>)> {
+        self.vertices
+            .iter()
+            .filter(|(_, format)| !format.is_per_instance())
+            .filter_map(|(vertices, format)| {
+                format
+                    .attributes
+                    .iter()
+                    .find(|attribute| attribute.name == name)
+                    .map(|attribute| (&**vertices, format, attribute))
+            })
+            .next()
+    }
+
     /// Sets the primitive type of the mesh.
     ///
     /// By default, meshes are constructed as triangle lists.
@@ -165,7 +374,125 @@ impl<'a> MeshBuilder<'a> {
         self
     }
 
+    /// Read indices widened to `u32`.
+    /// Returns `None` if there is no index buffer.
+    pub(crate) fn read_indices(&self) -> Option<Vec<u32>> {
+        self.indices().map(Iterator::collect)
+    }
+
+    /// Indices in the form they are uploaded by `build`.
+    /// `u8` indices are always widened to `u16`
+    /// and `u32` indices are narrowed to `u16` if enabled and they fit.
+    fn upload_indices<'b>(&'b self) -> Option<(Cow<'b, [u8]>, IndexType)> {
+        let (indices, width) = match self.indices {
+            Some((ref indices, width)) => (&**indices, width),
+            None => return None,
+        };
+        let narrowed = match width {
+            IndexWidth::U8 => narrow_to_u16(IndexIter::new(indices, width)),
+            IndexWidth::U16 => return Some((Cow::Borrowed(indices), IndexType::U16)),
+            IndexWidth::U32 if self.narrow_indices => narrow_to_u16(IndexIter::new(indices, width)),
+            IndexWidth::U32 => None,
+        };
+        Some(match narrowed {
+            Some(narrowed) => (Cow::Owned(cast_vec(narrowed)), IndexType::U16),
+            None => (Cow::Borrowed(indices), IndexType::U32),
+        })
+    }
+
+    /// Rebuild per-vertex buffers so that vertex `i` is a copy of former vertex `sources[i]`.
+    pub(crate) fn remap_vertices(&mut self, sources: &[u32]) {
+        for &mut (ref mut vertices, ref format) in self.vertices.iter_mut() {
+            if format.is_per_instance() || format.stride == 0 {
+                continue;
+            }
+            let stride = format.stride as usize;
+            let mut remapped = Vec::with_capacity(sources.len() * stride);
+            for &source in sources {
+                let start = source as usize * stride;
+                remapped.extend_from_slice(&vertices[start..start + stride]);
+            }
+            *vertices = Cow::Owned(remapped);
+        }
+    }
+
+    /// Give every group of corners its own copies of vertices shared with other groups.
+    /// Corner `i` refers to vertex `indices[i]` and belongs to group `groups[i]`.
+    /// Indices are replaced if any vertex is duplicated.
+    /// Returns group of every vertex or `None` for vertices not referenced by corners.
+    pub(crate) fn split_vertices(
+        &mut self,
+        vertex_count: usize,
+        mut indices: Vec<u32>,
+        groups: &[usize],
+    ) -> Vec<Option<usize>> {
+        let mut sources = (0..vertex_count as u32).collect::<Vec<_>>();
+        let mut vertex_groups = vec![None; vertex_count];
+        let mut splits = HashMap::new();
+        for (index, &group) in indices.iter_mut().zip(groups) {
+            let assigned = vertex_groups[*index as usize];
+            match assigned {
+                None => vertex_groups[*index as usize] = Some(group),
+                Some(first) if first == group => {}
+                Some(_) => {
+                    let vertex = *index;
+                    *index = *splits.entry((vertex, group)).or_insert_with(|| {
+                        sources.push(vertex);
+                        vertex_groups.push(Some(group));
+                        sources.len() as u32 - 1
+                    });
+                }
+            }
+        }
+
+        if sources.len() > vertex_count {
+            self.remap_vertices(&sources);
+            self.set_indices(narrow_indices(indices));
+        }
+        vertex_groups
+    }
+
+    /// Number of vertices in per-vertex buffers.
+    /// Returns `None` if there are no per-vertex buffers.
+    /// Fails if per-vertex buffers have different number of vertices.
+    pub fn vertex_count(&self) -> Result<Option<VertexCount>, MeshBuilderError> {
+        let mut count = None;
+        for (index, (vertices, format)) in self.vertices.iter().enumerate() {
+            if format.is_per_instance() || format.stride == 0 {
+                continue;
+            }
+            let len = vertices.len() as VertexCount / format.stride;
+            match count {
+                None => count = Some(len),
+                Some(expected) if expected != len => {
+                    return Err(MeshBuilderError::VertexCountMismatch {
+                        index,
+                        expected,
+                        found: len,
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(count)
+    }
+
+    /// Check that all vertex formats are valid,
+    /// sizes of vertex buffers are multiple of formats' strides
+    /// and all per-vertex buffers have same number of vertices.
+    pub fn validate(&self) -> Result<(), MeshBuilderError> {
+        for (index, (vertices, format)) in self.vertices.iter().enumerate() {
+            format
+                .validate()
+                .map_err(|error| MeshBuilderError::InvalidFormat { index, error })?;
+            check_buffer_size(index, vertices, format)?;
+        }
+        self.vertex_count()?;
+        Ok(())
+    }
+
     /// Builds and returns the new mesh.
+    /// Fails if `MeshBuilder::validate` fails.
     pub fn build<B>(
         &self,
         family: QueueFamilyId,
@@ -174,36 +501,54 @@ impl<'a> MeshBuilder<'a> {
     where
         B: Backend,
     {
-        Ok(Mesh {
-            vbufs: self
-                .vertices
-                .iter()
-                .map(|&(ref vertices, ref format)| {
-                    let len = vertices.len() as VertexCount / format.stride;
-                    Ok(VertexBuffer {
-                        buffer: {
-                            let mut buffer = factory.create_buffer(
-                                vertices.len() as _,
-                                Usage::VERTEX | Usage::TRANSFER_DST,
-                                Properties::DEVICE_LOCAL,
-                            )?;
-                            factory.upload_buffer(
-                                &mut buffer,
-                                family,
-                                Access::VERTEX_BUFFER_READ,
-                                0,
-                                &vertices,
-                            )?;
-                            buffer
-                        },
-                        format: format.clone(),
-                        len,
-                    })
+        self.validate()?;
+
+        let instances = self
+            .vertices
+            .iter()
+            .filter(|(_, format)| format.is_per_instance())
+            .map(|(instances, format)| {
+                (instances.len() as InstanceCount / format.stride) * format.rate as InstanceCount
+            })
+            .fold(None, |acc, count| {
+                Some(acc.map_or(count, |acc| min(acc, count)))
+            })
+            .unwrap_or(1);
+
+        let mut vbufs = self
+            .vertices
+            .iter()
+            .map(|(vertices, format)| {
+                let len = vertices.len() as VertexCount / format.stride;
+                Ok(VertexBuffer {
+                    buffer: {
+                        let mut buffer = factory.create_buffer(
+                            vertices.len() as _,
+                            Usage::VERTEX | Usage::TRANSFER_DST,
+                            Properties::DEVICE_LOCAL,
+                        )?;
+                        factory.upload_buffer(
+                            &mut buffer,
+                            family,
+                            Access::VERTEX_BUFFER_READ,
+                            0,
+                            vertices,
+                        )?;
+                        buffer
+                    },
+                    format: format.clone(),
+                    len,
                 })
-                .collect::<Result<_, Error>>()?,
-            ibuf: match self.indices {
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        // `Mesh::bind` expects buffers sorted by format.
+        vbufs.sort_by(|a, b| a.format.cmp(&b.format));
+
+        Ok(Mesh {
+            vbufs,
+            ibuf: match self.upload_indices() {
                 None => None,
-                Some((ref indices, index_type)) => {
+                Some((indices, index_type)) => {
                     let stride = match index_type {
                         IndexType::U16 => size_of::<u16>(),
                         IndexType::U32 => size_of::<u32>(),
@@ -231,7 +576,100 @@ impl<'a> MeshBuilder<'a> {
                 }
             },
             prim: self.prim,
+            instances,
+            morph_targets: self.morph_targets.clone(),
+        })
+    }
+}
+
+/// Error returned by `MeshBuilder` for malformed vertex data.
+#[derive(Clone, Copy, Debug, Fail, PartialEq, Eq)]
+pub enum MeshBuilderError {
+    /// Vertex format is malformed.
+    #[fail(display = "Vertex buffer {} has invalid format: {}", index, error)]
+    InvalidFormat {
+        /// Index of the vertex buffer.
+        index: usize,
+        /// Validation error.
+        #[cause]
+        error: VertexFormatError,
+    },
+
+    /// Size of vertex buffer is not multiple of format's stride.
+    #[fail(
+        display = "Vertex buffer {} has size {} which is not multiple of stride {}",
+        index, size, stride
+    )]
+    BufferSize {
+        /// Index of the vertex buffer.
+        index: usize,
+        /// Size of the vertex buffer in bytes.
+        size: usize,
+        /// Stride of the vertex format.
+        stride: ElemStride,
+    },
+
+    /// Size of vertex type differs from format's stride.
+    #[fail(
+        display = "Vertex buffer {} has vertices of size {} while stride is {}",
+        index, size, stride
+    )]
+    StrideMismatch {
+        /// Index of the vertex buffer.
+        index: usize,
+        /// Size of the vertex type in bytes.
+        size: usize,
+        /// Stride of the vertex format.
+        stride: ElemStride,
+    },
+
+    /// Per-vertex buffers have different number of vertices.
+    #[fail(
+        display = "Vertex buffer {} has {} vertices while previous ones have {}",
+        index, found, expected
+    )]
+    VertexCountMismatch {
+        /// Index of the vertex buffer.
+        index: usize,
+        /// Number of vertices in previous buffers.
+        expected: VertexCount,
+        /// Number of vertices in the buffer.
+        found: VertexCount,
+    },
+}
+
+/// Check that size of vertex buffer is multiple of format's stride.
+pub(crate) fn check_buffer_size(
+    index: usize,
+    vertices: &[u8],
+    format: &VertexFormat,
+) -> Result<(), MeshBuilderError> {
+    if format.stride == 0 {
+        Err(MeshBuilderError::InvalidFormat {
+            index,
+            error: VertexFormatError::ZeroStride,
+        })
+    } else if vertices.len() % format.stride as usize != 0 {
+        Err(MeshBuilderError::BufferSize {
+            index,
+            size: vertices.len(),
+            stride: format.stride,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Check that size of vertex type is equal to format's stride.
+fn check_vertex_size<V>(index: usize, format: &VertexFormat) -> Result<(), MeshBuilderError> {
+    if size_of::<V>() != format.stride as usize {
+        Err(MeshBuilderError::StrideMismatch {
+            index,
+            size: size_of::<V>(),
+            stride: format.stride,
         })
+    } else {
+        Ok(())
     }
 }
 
@@ -242,6 +680,8 @@ pub struct Mesh<B: Backend> {
     vbufs: Vec<VertexBuffer<B>>,
     ibuf: Option<IndexBuffer<B>>,
     prim: Primitive,
+    instances: InstanceCount,
+    morph_targets: Vec<MorphTargetInfo>,
 }
 
 impl<B> Mesh<B>
@@ -249,6 +689,7 @@ where
     B: Backend,
 {
     /// Build new mesh with `HMeshBuilder`
+    #[allow(clippy::new_ret_no_self)]
     pub fn new<'a>() -> MeshBuilder<'a> {
         MeshBuilder::new()
     }
@@ -258,7 +699,62 @@ where
         self.prim
     }
 
+    /// Names and default weights of morph targets of the `Mesh`.
+    /// Deltas are bound as attributes named by `MorphAttribute::name`.
+    pub fn morph_targets(&self) -> &[MorphTargetInfo] {
+        &self.morph_targets
+    }
+
+    /// Number of instances covered by per-instance buffers of the `Mesh`.
+    /// `1` if `Mesh` has no per-instance buffers.
+    pub fn instance_count(&self) -> InstanceCount {
+        self.instances
+    }
+
+    /// Vertex formats of the `Mesh`'s vertex buffers.
+    pub fn vertex_formats(&self) -> impl Iterator<Item = &VertexFormat<'static>> + Clone {
+        self.vbufs.iter().map(|vbuf| &vbuf.format)
+    }
+
+    /// Find formats of vertex buffers that provide specified attributes.
+    /// Attributes are matched by name and format.
+    /// Returned formats are sorted and can be used with `Mesh::bind` and `VertexInputDesc::new`.
+    pub fn formats_for(
+        &self,
+        attributes: &[DynamicAttribute],
+    ) -> Result<Vec<VertexFormat<'static>>, Incompatible> {
+        let mut formats = Vec::new();
+        for attribute in attributes {
+            let format = self
+                .vbufs
+                .iter()
+                .map(|vbuf| &vbuf.format)
+                .find(|format| {
+                    format
+                        .attribute(&attribute.name)
+                        .map_or(false, |a| a.element.format == attribute.format)
+                })
+                .ok_or(Incompatible)?;
+            if !formats.contains(format) {
+                formats.push(format.clone());
+            }
+        }
+        formats.sort();
+        Ok(formats)
+    }
+
+    /// Bind buffers that provide specified attributes.
+    /// Same as `Mesh::bind` with formats found by `Mesh::formats_for`.
+    pub fn bind_attributes<'a>(
+        &'a self,
+        attributes: &[DynamicAttribute],
+        vertex: &mut VertexBufferSet<'a, B>,
+    ) -> Result<Bind<'a, B>, Incompatible> {
+        self.bind(&self.formats_for(attributes)?, vertex)
+    }
+
     /// Bind buffers to specified attribute locations.
+    /// Both per-vertex and per-instance formats may be requested.
     pub fn bind<'a>(
         &'a self,
         formats: &[VertexFormat],
@@ -268,18 +764,13 @@ where
         debug_assert!(is_slice_sorted_by_key(&self.vbufs, |vbuf| &vbuf.format));
         debug_assert!(vertex.0.is_empty());
 
-        let mut next = 0;
+        let indices = find_compatible_buffers(&self.vbufs, formats, |vbuf| &vbuf.format)?;
         let mut vertex_count = None;
-        for format in formats {
-            if let Some(index) = find_compatible_buffer(&self.vbufs[next..], format) {
-                // Ensure buffer is valid
-                vertex.0.push((self.vbufs[index].buffer.raw(), 0));
-                next = index + 1;
+        for (format, index) in formats.iter().zip(indices) {
+            vertex.0.push((self.vbufs[index].buffer.raw(), 0));
+            if !format.is_per_instance() {
                 assert!(vertex_count.is_none() || vertex_count == Some(self.vbufs[index].len));
                 vertex_count = Some(self.vbufs[index].len);
-            } else {
-                // Can't bind
-                return Err(Incompatible);
             }
         }
         Ok(self
@@ -290,9 +781,11 @@ where
                 offset: 0,
                 index_type: ibuf.index_type,
                 count: ibuf.len,
+                instances: self.instances,
             })
             .unwrap_or(Bind::Unindexed {
                 count: vertex_count.unwrap_or(0),
+                instances: self.instances,
             }))
     }
 
@@ -313,8 +806,8 @@ where
 pub struct Incompatible;
 
 /// Result of buffers bindings.
-/// It only contains `IndexBufferView` (if index buffers exists)
-/// and vertex count.
+/// It only contains `IndexBufferView` (if index buffers exists),
+/// vertex count and instance count.
 /// Vertex buffers are in separate `VertexBufferSet`
 #[derive(Copy, Clone, Debug)]
 pub enum Bind<'a, B: Backend> {
@@ -328,11 +821,15 @@ pub enum Bind<'a, B: Backend> {
         index_type: IndexType,
         /// Indices count to use in `draw_indexed` method.
         count: IndexCount,
+        /// Instance count to use in `draw_indexed` method.
+        instances: InstanceCount,
     },
     /// Not indexed binding.
     Unindexed {
         /// Vertex count to use in `draw` method.
         count: VertexCount,
+        /// Instance count to use in `draw` method.
+        instances: InstanceCount,
     },
 }
 
@@ -340,8 +837,27 @@ impl<'a, B> Bind<'a, B>
 where
     B: Backend,
 {
+    /// Instance count of the bound mesh.
+    pub fn instance_count(&self) -> InstanceCount {
+        match *self {
+            Bind::Indexed { instances, .. } | Bind::Unindexed { instances, .. } => instances,
+        }
+    }
+
     /// Record drawing command for this biding.
+    /// Draws all instances of the bound mesh.
     pub fn draw(&self, vertex: VertexBufferSet<B>, encoder: &mut RenderSubpassCommon<B>) {
+        self.draw_instanced(vertex, 0..self.instance_count(), encoder);
+    }
+
+    /// Record drawing command for this biding.
+    /// Draws specified range of instances.
+    pub fn draw_instanced(
+        &self,
+        vertex: VertexBufferSet<B>,
+        instances: Range<InstanceCount>,
+        encoder: &mut RenderSubpassCommon<B>,
+    ) {
         encoder.bind_vertex_buffers(0, vertex);
         match *self {
             Bind::Indexed {
@@ -349,40 +865,70 @@ where
                 offset,
                 index_type,
                 count,
+                ..
             } => {
                 encoder.bind_index_buffer(IndexBufferView {
                     buffer,
                     offset,
                     index_type,
                 });
-                encoder.draw_indexed(0..count, 0, 0..1);
+                encoder.draw_indexed(0..count, 0, instances);
             }
-            Bind::Unindexed { count } => {
-                encoder.draw(0..count, 0..1);
+            Bind::Unindexed { count, .. } => {
+                encoder.draw(0..count, instances);
             }
         }
     }
+
+    /// Record drawing command for this biding.
+    /// Draws specified range of instances.
+    /// `extra` buffers (per-instance data not owned by the mesh for example)
+    /// are bound after mesh's buffers.
+    pub fn draw_instanced_with<'b>(
+        &self,
+        mut vertex: VertexBufferSet<'b, B>,
+        extra: &[(&'b B::Buffer, Offset)],
+        instances: Range<InstanceCount>,
+        encoder: &mut RenderSubpassCommon<B>,
+    ) {
+        vertex.0.extend_from_slice(extra);
+        self.draw_instanced(vertex, instances, encoder);
+    }
 }
 
-/// Helper function to find buffer with compatible format.
-fn find_compatible_buffer<B>(vbufs: &[VertexBuffer<B>], format: &VertexFormat) -> Option<usize>
+/// Find index of compatible buffer for each of requested `formats`.
+/// Both buffers and formats are sorted, so search for the next format
+/// starts after the buffer found for the previous one.
+fn find_compatible_buffers<T, F>(
+    buffers: &[T],
+    formats: &[VertexFormat],
+    format_of: F,
+) -> Result<SmallVec<[usize; 16]>, Incompatible>
 where
-    B: Backend,
+    F: Fn(&T) -> &VertexFormat<'static>,
 {
-    debug_assert!(is_slice_sorted_by_key(&*format.attributes, |a| a.offset));
-    for (i, vbuf) in vbufs.iter().enumerate() {
-        debug_assert!(is_slice_sorted_by_key(&*vbuf.format.attributes, |a| a.offset));
-        if is_compatible(&vbuf.format, format) {
-            return Some(i);
-        }
+    let mut next = 0;
+    let mut indices = SmallVec::new();
+    for format in formats {
+        debug_assert!(format.validate().is_ok());
+        let index = buffers[next..]
+            .iter()
+            .position(|buffer| {
+                debug_assert!(format_of(buffer).validate().is_ok());
+                is_compatible(format_of(buffer), format)
+            })
+            .ok_or(Incompatible)?;
+        indices.push(next + index);
+        next += index + 1;
     }
-    None
+    Ok(indices)
 }
 
 /// Check is vertex format `left` is compatible with `right`.
-/// `left` must have same `stride` and contain all attributes from `right`.
+/// `left` must have same `stride` and `rate` and contain all attributes from `right`.
+/// Attributes are matched by semantic name, format and offset.
 fn is_compatible(left: &VertexFormat, right: &VertexFormat) -> bool {
-    if left.stride != right.stride {
+    if left.stride != right.stride || left.rate != right.rate {
         return false;
     }
 
@@ -398,3 +944,183 @@ fn is_compatible(left: &VertexFormat, right: &VertexFormat) -> bool {
             })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::format::Format;
+    use hal::memory::Pod;
+    use vertex::{Color, Normal, PosNorm, Position, TexCoord};
+
+    /// Vertex type whose format claims smaller stride than its size.
+    #[repr(C)]
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Short(Position, Normal);
+
+    unsafe impl Pod for Short {}
+
+    impl AsVertexFormat for Short {
+        const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
+            attributes: Cow::Borrowed(&[]),
+            stride: 12,
+            rate: 0,
+        };
+    }
+
+    #[test]
+    fn add_vertices_checks_stride() {
+        let mut builder = MeshBuilder::new();
+        let vertex = Short([0.0; 3].into(), [0.0; 3].into());
+        assert_eq!(
+            builder.add_vertices(vec![vertex]).err(),
+            Some(MeshBuilderError::StrideMismatch {
+                index: 0,
+                size: 24,
+                stride: 12,
+            })
+        );
+        assert_eq!(
+            builder.add_instances(vec![vertex], 1).err(),
+            Some(MeshBuilderError::StrideMismatch {
+                index: 0,
+                size: 24,
+                stride: 12,
+            })
+        );
+        assert!(builder.vertices.is_empty());
+        assert!(builder
+            .add_vertices(vec![PosNorm {
+                position: [0.0; 3].into(),
+                normal: [0.0; 3].into(),
+            }])
+            .is_ok());
+    }
+
+    #[test]
+    fn add_raw_vertices_checks_size() {
+        let format =
+            VertexFormat::from_attributes(vec![DynamicAttribute::new("value", Format::R32Float)]);
+        let mut builder = MeshBuilder::new();
+        assert_eq!(
+            builder.add_raw_vertices(vec![0u8; 6], format.clone()).err(),
+            Some(MeshBuilderError::BufferSize {
+                index: 0,
+                size: 6,
+                stride: 4,
+            })
+        );
+        assert!(builder.add_raw_vertices(vec![0u8; 8], format).is_ok());
+        assert_eq!(builder.vertex_count(), Ok(Some(2)));
+    }
+
+    #[test]
+    fn check_buffer_size_rejects_zero_stride() {
+        let format = VertexFormat {
+            attributes: Cow::Borrowed(&[]),
+            stride: 0,
+            rate: 0,
+        };
+        assert_eq!(
+            check_buffer_size(3, &[0; 4], &format),
+            Err(MeshBuilderError::InvalidFormat {
+                index: 3,
+                error: VertexFormatError::ZeroStride,
+            })
+        );
+    }
+
+    fn find(buffers: &[VertexFormat<'static>], formats: &[VertexFormat]) -> Option<Vec<usize>> {
+        find_compatible_buffers(buffers, formats, |format| format)
+            .map(|indices| indices.to_vec())
+            .ok()
+    }
+
+    #[test]
+    fn find_compatible_buffers_in_order() {
+        let mut buffers = vec![
+            Position::VERTEX_FORMAT,
+            Normal::VERTEX_FORMAT,
+            TexCoord::VERTEX_FORMAT,
+            Color::VERTEX_FORMAT.per_instance(1),
+        ];
+        buffers.sort();
+        let mut formats = vec![
+            TexCoord::VERTEX_FORMAT,
+            Position::VERTEX_FORMAT,
+            Color::VERTEX_FORMAT.per_instance(1),
+        ];
+        formats.sort();
+        let expected = formats
+            .iter()
+            .map(|format| buffers.iter().position(|buffer| buffer == format).unwrap())
+            .collect::<Vec<_>>();
+
+        // Indices are absolute even though search continues after previous match.
+        assert_eq!(find(&buffers, &formats), Some(expected));
+        for (index, format) in buffers.iter().enumerate() {
+            assert_eq!(
+                find(&buffers, ::std::slice::from_ref(format)),
+                Some(vec![index])
+            );
+        }
+    }
+
+    #[test]
+    fn find_compatible_buffers_once() {
+        let buffers = [PosNorm::VERTEX_FORMAT, PosNorm::VERTEX_FORMAT];
+        assert_eq!(
+            find(&buffers, &[PosNorm::VERTEX_FORMAT, PosNorm::VERTEX_FORMAT]),
+            Some(vec![0, 1])
+        );
+        assert_eq!(
+            find(
+                &buffers,
+                &[
+                    PosNorm::VERTEX_FORMAT,
+                    PosNorm::VERTEX_FORMAT,
+                    PosNorm::VERTEX_FORMAT,
+                ]
+            ),
+            None
+        );
+        assert_eq!(find(&buffers, &[Position::VERTEX_FORMAT]), None);
+    }
+
+    fn uploaded(builder: &MeshBuilder) -> (Vec<u32>, IndexType) {
+        let (bytes, index_type) = builder.upload_indices().unwrap();
+        let width = match index_type {
+            IndexType::U16 => IndexWidth::U16,
+            IndexType::U32 => IndexWidth::U32,
+        };
+        (IndexIter::new(&bytes, width).collect(), index_type)
+    }
+
+    #[test]
+    fn upload_indices() {
+        assert!(MeshBuilder::new().upload_indices().is_none());
+
+        let builder = MeshBuilder::new().with_indices(vec![0u8, 255, 7]);
+        assert_eq!(
+            builder.indices().unwrap().collect::<Vec<_>>(),
+            vec![0, 255, 7]
+        );
+        assert_eq!(uploaded(&builder), (vec![0, 255, 7], IndexType::U16));
+
+        let builder = MeshBuilder::new().with_indices(vec![1u16, 2, 3]);
+        assert_eq!(uploaded(&builder), (vec![1, 2, 3], IndexType::U16));
+
+        let builder = MeshBuilder::new().with_indices(vec![1u32, 65534]);
+        assert_eq!(uploaded(&builder), (vec![1, 65534], IndexType::U32));
+        let builder = builder.with_narrow_indices(true);
+        assert_eq!(uploaded(&builder), (vec![1, 65534], IndexType::U16));
+        assert_eq!(builder.indices.as_ref().unwrap().1, IndexWidth::U32);
+
+        // `0xFFFF` would become the primitive restart index.
+        for &index in &[65535, 65536] {
+            let builder = MeshBuilder::new()
+                .with_indices(vec![1u32, index])
+                .with_narrow_indices(true);
+            assert_eq!(uploaded(&builder), (vec![1, index], IndexType::U32));
+        }
+    }
+}
@@ -0,0 +1,360 @@
+//!
+//! Morph targets (blend shapes) in `MeshBuilder`.
+//!
+//! Deltas of each morph target are stored as separate per-vertex buffers
+//! with attributes named after the target index (`morph_position`, `morph_position_1` etc.).
+//! So they are bound through `Mesh::bind` as any other vertex buffer.
+//!
+
+use std::borrow::Cow;
+
+use hal::format::AsFormat;
+use hal::VertexCount;
+
+use mesh::{MeshBuilder, MeshBuilderError};
+use utils::cast_cow;
+use vertex::{indexed_name, DynamicAttribute, VertexFormat};
+
+/// Deltas of single morphed attribute.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MorphDeltas<'a> {
+    /// Delta for every vertex.
+    Dense(Cow<'a, [[f32; 3]]>),
+
+    /// Deltas for listed vertices. Other vertices are not displaced.
+    Sparse {
+        /// Indices of displaced vertices.
+        indices: Cow<'a, [u32]>,
+        /// Deltas for vertices in `indices`.
+        deltas: Cow<'a, [[f32; 3]]>,
+    },
+}
+
+impl<'a, T> From<T> for MorphDeltas<'a>
+where
+    T: Into<Cow<'a, [[f32; 3]]>>,
+{
+    fn from(deltas: T) -> Self {
+        MorphDeltas::Dense(deltas.into())
+    }
+}
+
+/// Attribute displaced by morph targets.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum MorphAttribute {
+    /// Position deltas.
+    Position,
+    /// Normal deltas.
+    Normal,
+    /// Tangent deltas (without handedness).
+    Tangent,
+}
+
+impl MorphAttribute {
+    /// Name of the attribute that stores deltas of the morph target.
+    pub fn name(&self, target: u32) -> Cow<'static, str> {
+        let base = match *self {
+            MorphAttribute::Position => "morph_position",
+            MorphAttribute::Normal => "morph_normal",
+            MorphAttribute::Tangent => "morph_tangent",
+        };
+        indexed_name(base, target)
+    }
+
+    /// Attribute that stores deltas of the morph target.
+    pub fn attribute(&self, target: u32) -> DynamicAttribute {
+        DynamicAttribute::new(self.name(target), <[f32; 3] as AsFormat>::SELF)
+    }
+}
+
+/// Morph target to add to `MeshBuilder`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MorphTarget<'a> {
+    /// Name of the target.
+    pub name: Option<String>,
+
+    /// Default weight of the target.
+    pub weight: f32,
+
+    /// Position deltas.
+    pub positions: Option<MorphDeltas<'a>>,
+
+    /// Normal deltas.
+    pub normals: Option<MorphDeltas<'a>>,
+
+    /// Tangent deltas.
+    pub tangents: Option<MorphDeltas<'a>>,
+}
+
+impl<'a> MorphTarget<'a> {
+    /// Create morph target without deltas and with zero weight.
+    pub fn new() -> Self {
+        MorphTarget::default()
+    }
+
+    /// Set name of the target.
+    pub fn with_name<N>(mut self, name: N) -> Self
+    where
+        N: Into<String>,
+    {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set default weight of the target.
+    pub fn with_weight(mut self, weight: f32) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    /// Set position deltas.
+    pub fn with_positions<D>(mut self, deltas: D) -> Self
+    where
+        D: Into<MorphDeltas<'a>>,
+    {
+        self.positions = Some(deltas.into());
+        self
+    }
+
+    /// Set normal deltas.
+    pub fn with_normals<D>(mut self, deltas: D) -> Self
+    where
+        D: Into<MorphDeltas<'a>>,
+    {
+        self.normals = Some(deltas.into());
+        self
+    }
+
+    /// Set tangent deltas.
+    pub fn with_tangents<D>(mut self, deltas: D) -> Self
+    where
+        D: Into<MorphDeltas<'a>>,
+    {
+        self.tangents = Some(deltas.into());
+        self
+    }
+}
+
+/// Name and default weight of morph target in `MeshBuilder` and `Mesh`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MorphTargetInfo {
+    /// Name of the target.
+    pub name: Option<String>,
+
+    /// Default weight of the target.
+    pub weight: f32,
+
+    /// Attributes the target displaces.
+    pub attributes: Vec<MorphAttribute>,
+}
+
+/// Error returned when morph target can't be added.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum MorphError {
+    /// Vertex data of the builder is malformed.
+    #[fail(display = "Vertex data is invalid: {}", _0)]
+    InvalidVertices(#[cause] MeshBuilderError),
+
+    /// Builder has no per-vertex buffers to morph.
+    #[fail(display = "No vertices to morph")]
+    NoVertices,
+
+    /// Dense deltas have different number of vertices.
+    #[fail(
+        display = "{:?} deltas are provided for {} vertices while mesh has {}",
+        attribute, found, expected
+    )]
+    VertexCountMismatch {
+        /// Morphed attribute.
+        attribute: MorphAttribute,
+        /// Number of vertices in mesh.
+        expected: usize,
+        /// Number of deltas.
+        found: usize,
+    },
+
+    /// Sparse deltas have different number of indices and deltas.
+    #[fail(
+        display = "Sparse {:?} deltas have {} indices and {} deltas",
+        attribute, indices, deltas
+    )]
+    SparseLengthMismatch {
+        /// Morphed attribute.
+        attribute: MorphAttribute,
+        /// Number of indices.
+        indices: usize,
+        /// Number of deltas.
+        deltas: usize,
+    },
+
+    /// Sparse deltas refer to vertex out of range.
+    #[fail(
+        display = "Sparse {:?} delta refers to vertex {} while mesh has {}",
+        attribute, index, vertex_count
+    )]
+    IndexOutOfRange {
+        /// Morphed attribute.
+        attribute: MorphAttribute,
+        /// Index of the vertex.
+        index: u32,
+        /// Number of vertices in mesh.
+        vertex_count: VertexCount,
+    },
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Add morph target to the `MeshBuilder`.
+    pub fn with_morph_target(mut self, target: MorphTarget<'a>) -> Result<Self, MorphError> {
+        self.add_morph_target(target)?;
+        Ok(self)
+    }
+
+    /// Add morph target to the `MeshBuilder`.
+    /// Each kind of deltas is added as separate per-vertex buffer
+    /// with attribute `MorphAttribute::attribute(index)`
+    /// where `index` is the number of previously added targets.
+    /// Sparse deltas are expanded so per-vertex buffers must be added first.
+    pub fn add_morph_target(&mut self, target: MorphTarget<'a>) -> Result<&mut Self, MorphError> {
+        let vertex_count = self
+            .vertex_count()
+            .map_err(MorphError::InvalidVertices)?
+            .ok_or(MorphError::NoVertices)?;
+        let index = self.morph_targets.len() as u32;
+
+        let mut buffers = Vec::new();
+        let mut attributes = Vec::new();
+        for (attribute, deltas) in [
+            (MorphAttribute::Position, target.positions),
+            (MorphAttribute::Normal, target.normals),
+            (MorphAttribute::Tangent, target.tangents),
+        ] {
+            if let Some(deltas) = deltas {
+                let deltas = dense_deltas(attribute, deltas, vertex_count)?;
+                buffers.push((
+                    cast_cow(deltas),
+                    VertexFormat::from_attributes(Some(attribute.attribute(index))),
+                ));
+                attributes.push(attribute);
+            }
+        }
+
+        self.vertices.extend(buffers);
+        self.morph_targets.push(MorphTargetInfo {
+            name: target.name,
+            weight: target.weight,
+            attributes,
+        });
+        Ok(self)
+    }
+
+    /// Get names and default weights of morph targets.
+    pub fn morph_targets(&self) -> &[MorphTargetInfo] {
+        &self.morph_targets
+    }
+}
+
+/// Expand deltas to every vertex.
+fn dense_deltas<'a>(
+    attribute: MorphAttribute,
+    deltas: MorphDeltas<'a>,
+    vertex_count: VertexCount,
+) -> Result<Cow<'a, [[f32; 3]]>, MorphError> {
+    match deltas {
+        MorphDeltas::Dense(deltas) => {
+            if deltas.len() != vertex_count as usize {
+                return Err(MorphError::VertexCountMismatch {
+                    attribute,
+                    expected: vertex_count as usize,
+                    found: deltas.len(),
+                });
+            }
+            Ok(deltas)
+        }
+        MorphDeltas::Sparse { indices, deltas } => {
+            if indices.len() != deltas.len() {
+                return Err(MorphError::SparseLengthMismatch {
+                    attribute,
+                    indices: indices.len(),
+                    deltas: deltas.len(),
+                });
+            }
+            let mut dense = vec![[0.0; 3]; vertex_count as usize];
+            for (&index, delta) in indices.iter().zip(deltas.iter()) {
+                if index >= vertex_count {
+                    return Err(MorphError::IndexOutOfRange {
+                        attribute,
+                        index,
+                        vertex_count,
+                    });
+                }
+                dense[index as usize] = *delta;
+            }
+            Ok(Cow::Owned(dense))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::cast_slice;
+    use vertex::Position;
+
+    fn positions() -> MeshBuilder<'static> {
+        MeshBuilder::new()
+            .with_vertices(vec![Position([0.0; 3]); 3])
+            .unwrap()
+    }
+
+    #[test]
+    fn vertex_count_mismatch() {
+        let target = MorphTarget::new().with_positions(vec![[1.0; 3]; 2]);
+        assert_eq!(
+            positions().with_morph_target(target).err(),
+            Some(MorphError::VertexCountMismatch {
+                attribute: MorphAttribute::Position,
+                expected: 3,
+                found: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn sparse_deltas() {
+        let target = MorphTarget::new()
+            .with_weight(0.5)
+            .with_normals(MorphDeltas::Sparse {
+                indices: vec![2].into(),
+                deltas: vec![[0.0, 1.0, 0.0]].into(),
+            });
+        let builder = positions().with_morph_target(target).unwrap();
+        assert_eq!(
+            builder.morph_targets(),
+            &[MorphTargetInfo {
+                name: None,
+                weight: 0.5,
+                attributes: vec![MorphAttribute::Normal],
+            }]
+        );
+        let (ref deltas, ref format) = builder.vertices[1];
+        assert_eq!(format.attributes[0].name, "morph_normal");
+        let mut expected = vec![[0.0f32; 3]; 3];
+        expected[2] = [0.0, 1.0, 0.0];
+        assert_eq!(&deltas[..], cast_slice(&expected));
+
+        let target = MorphTarget::new().with_normals(MorphDeltas::Sparse {
+            indices: vec![3].into(),
+            deltas: vec![[0.0; 3]].into(),
+        });
+        assert_eq!(
+            positions().with_morph_target(target).err(),
+            Some(MorphError::IndexOutOfRange {
+                attribute: MorphAttribute::Normal,
+                index: 3,
+                vertex_count: 3,
+            })
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//!
+//! Generation of vertex normals for `MeshBuilder`.
+//!
+
+use std::collections::HashMap;
+
+use hal::Primitive;
+
+use access::AccessError;
+use math::{add, bits, cross, dot, normalize, scale, sub};
+use mesh::{MeshBuilder, MeshBuilderError};
+use vertex::{Normal, Position};
+
+/// Weighting of face normals when they are averaged into vertex normals.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NormalWeighting {
+    /// Faces contribute proportionally to their area.
+    Area,
+
+    /// Faces contribute proportionally to their angle at the vertex.
+    /// Result doesn't depend on how polygons are triangulated.
+    Angle,
+}
+
+/// Error returned when normals can't be generated.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum NormalError {
+    /// Vertex data of the builder is malformed.
+    #[fail(display = "Vertex data is invalid: {}", _0)]
+    InvalidVertices(#[cause] MeshBuilderError),
+
+    /// Normals can be generated only for triangle lists.
+    #[fail(display = "Primitive {:?} is not a triangle list", _0)]
+    UnsupportedPrimitive(Primitive),
+
+    /// Positions can't be read.
+    #[fail(display = "Positions are not accessible: {}", _0)]
+    InvalidPositions(#[cause] AccessError),
+
+    /// Builder already has normals in format other than `Normal`.
+    #[fail(display = "Normals can't be replaced: {}", _0)]
+    InvalidNormals(#[cause] AccessError),
+
+    /// Index refers to vertex that doesn't exist.
+    #[fail(
+        display = "Index {} is out of range for {} vertices",
+        index, vertex_count
+    )]
+    IndexOutOfRange {
+        /// Value of the index.
+        index: u32,
+        /// Number of vertices.
+        vertex_count: usize,
+    },
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Generate smooth normals.
+    /// See `set_smooth_normals`.
+    pub fn with_smooth_normals(
+        mut self,
+        weighting: NormalWeighting,
+        crease_angle: f32,
+    ) -> Result<Self, NormalError> {
+        self.set_smooth_normals(weighting, crease_angle)?;
+        Ok(self)
+    }
+
+    /// Generate smooth normals by averaging weighted normals of faces
+    /// that share vertex position, so vertices split along UV seams are still smooth.
+    ///
+    /// Faces meeting at angle greater than `crease_angle` (in radians) are not averaged.
+    /// Vertices at such hard edges are split and indices are updated.
+    /// Pass `PI` to smooth across all edges.
+    ///
+    /// Existing `Normal` attribute is overwritten in place,
+    /// otherwise new buffer with `Normal` attribute is added.
+    /// Use `interleave` to merge it with positions into single stream.
+    pub fn set_smooth_normals(
+        &mut self,
+        weighting: NormalWeighting,
+        crease_angle: f32,
+    ) -> Result<&mut Self, NormalError> {
+        self.set_normals(weighting, crease_angle, true)
+    }
+
+    /// Generate flat normals.
+    /// See `set_flat_normals`.
+    pub fn with_flat_normals(mut self) -> Result<Self, NormalError> {
+        self.set_flat_normals()?;
+        Ok(self)
+    }
+
+    /// Generate flat normals so every face is shaded with its own normal.
+    /// Vertices shared by faces that are not coplanar are split and indices are updated.
+    ///
+    /// Existing `Normal` attribute is overwritten in place,
+    /// otherwise new buffer with `Normal` attribute is added.
+    pub fn set_flat_normals(&mut self) -> Result<&mut Self, NormalError> {
+        self.set_normals(NormalWeighting::Area, FLAT_ANGLE, false)
+    }
+
+    /// Generate normals averaging faces that share vertex position if `by_position` is `true`
+    /// or only faces that share vertex index otherwise.
+    fn set_normals(
+        &mut self,
+        weighting: NormalWeighting,
+        crease_angle: f32,
+        by_position: bool,
+    ) -> Result<&mut Self, NormalError> {
+        self.vertex_count().map_err(NormalError::InvalidVertices)?;
+        if self.prim != Primitive::TriangleList {
+            return Err(NormalError::UnsupportedPrimitive(self.prim));
+        }
+        match self.attribute::<Normal>() {
+            Ok(_) | Err(AccessError::MissingAttribute { .. }) => {}
+            Err(error) => return Err(NormalError::InvalidNormals(error)),
+        }
+        let positions = self
+            .attribute::<Position>()
+            .map_err(NormalError::InvalidPositions)?
+            .map(|position| position.0)
+            .collect::<Vec<_>>();
+        let vertex_count = positions.len();
+        let indices = self
+            .read_indices()
+            .unwrap_or_else(|| (0..vertex_count as u32).collect());
+        if let Some(&index) = indices
+            .iter()
+            .find(|&&index| index as usize >= vertex_count)
+        {
+            return Err(NormalError::IndexOutOfRange {
+                index,
+                vertex_count,
+            });
+        }
+        let corner_count = indices.len() / 3 * 3;
+
+        // Unit normal of each face and weighted normal of each corner.
+        let mut face_normals = Vec::with_capacity(corner_count / 3);
+        let mut weighted = Vec::with_capacity(corner_count);
+        for triangle in indices[..corner_count].chunks(3) {
+            let corners = [
+                positions[triangle[0] as usize],
+                positions[triangle[1] as usize],
+                positions[triangle[2] as usize],
+            ];
+            let area_normal = cross(sub(corners[1], corners[0]), sub(corners[2], corners[0]));
+            let unit = normalize(area_normal);
+            face_normals.push(unit);
+            for i in 0..3 {
+                weighted.push(match weighting {
+                    NormalWeighting::Area => area_normal,
+                    NormalWeighting::Angle => {
+                        let a = normalize(sub(corners[(i + 1) % 3], corners[i]));
+                        let b = normalize(sub(corners[(i + 2) % 3], corners[i]));
+                        scale(unit, dot(a, b).clamp(-1.0, 1.0).acos())
+                    }
+                });
+            }
+        }
+
+        // Corners that may share normal.
+        let mut clusters = HashMap::new();
+        for (corner, &index) in indices[..corner_count].iter().enumerate() {
+            let key = if by_position {
+                bits(positions[index as usize])
+            } else {
+                [index, 0, 0]
+            };
+            clusters.entry(key).or_insert_with(Vec::new).push(corner);
+        }
+
+        // Split clusters into smoothing groups of faces
+        // whose normals are transitively within crease angle.
+        let min_cos = crease_angle.cos();
+        let mut group_normals = Vec::new();
+        let mut corner_groups = vec![0; corner_count];
+        for corners in clusters.values() {
+            let normal = |i: usize| face_normals[corners[i] / 3];
+            let mut parents = (0..corners.len()).collect::<Vec<_>>();
+            for i in 0..corners.len() {
+                for j in 0..i {
+                    if normal(i) != [0.0; 3]
+                        && normal(j) != [0.0; 3]
+                        && dot(normal(i), normal(j)) >= min_cos
+                    {
+                        let (root_i, root_j) = (find(&mut parents, i), find(&mut parents, j));
+                        parents[root_i] = root_j;
+                    }
+                }
+            }
+            // Degenerate faces join the group of the first regular face.
+            let regular = (0..corners.len()).find(|&i| normal(i) != [0.0; 3]);
+
+            let mut groups = HashMap::new();
+            for (i, &corner) in corners.iter().enumerate() {
+                let root = match regular {
+                    Some(regular) if normal(i) == [0.0; 3] => find(&mut parents, regular),
+                    _ => find(&mut parents, i),
+                };
+                let group = *groups.entry(root).or_insert_with(|| {
+                    group_normals.push([0.0; 3]);
+                    group_normals.len() - 1
+                });
+                group_normals[group] = add(group_normals[group], weighted[corner]);
+                corner_groups[corner] = group;
+            }
+        }
+
+        let vertex_groups = self.split_vertices(vertex_count, indices, &corner_groups);
+        let normals = vertex_groups
+            .into_iter()
+            .map(|group| Normal(group.map_or([0.0; 3], |group| normalize(group_normals[group]))))
+            .collect();
+        self.replace_attribute(normals)
+            .map_err(NormalError::InvalidNormals)?;
+        Ok(self)
+    }
+}
+
+/// Coplanar faces still share vertices when flat normals are generated.
+const FLAT_ANGLE: f32 = 1e-3;
+
+/// Find root of union-find set with path halving.
+fn find(parents: &mut [usize], mut index: usize) -> usize {
+    while parents[index] != index {
+        parents[index] = parents[parents[index]];
+        index = parents[index];
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::PI;
+    use utils::narrow_indices;
+
+    /// Cube with 8 shared corners and outward counter-clockwise faces.
+    fn cube() -> MeshBuilder<'static> {
+        let positions = (0..8)
+            .map(|i| {
+                let coord = |bit: u32| if i >> bit & 1 == 1 { 1.0 } else { -1.0 };
+                Position([coord(0), coord(1), coord(2)])
+            })
+            .collect::<Vec<_>>();
+        let quads = [
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+        ];
+        let indices = quads
+            .iter()
+            .flat_map(|q| vec![q[0], q[1], q[2], q[0], q[2], q[3]])
+            .collect();
+        let mut builder = MeshBuilder::new();
+        builder
+            .push_vertices(positions)
+            .set_indices(narrow_indices(indices));
+        builder
+    }
+
+    fn approx(a: [f32; 3], b: [f32; 3]) -> bool {
+        (0..3).all(|i| (a[i] - b[i]).abs() < 1e-5)
+    }
+
+    /// Check that every vertex of every triangle has normal of the triangle.
+    fn assert_flat(builder: &MeshBuilder) {
+        let positions = builder.attribute::<Position>().unwrap().collect::<Vec<_>>();
+        let normals = builder.attribute::<Normal>().unwrap().collect::<Vec<_>>();
+        for triangle in builder.read_indices().unwrap().chunks(3) {
+            let corner = |i: usize| positions[triangle[i] as usize].0;
+            let face = normalize(cross(sub(corner(1), corner(0)), sub(corner(2), corner(0))));
+            for &index in triangle {
+                assert!(approx(normals[index as usize].0, face));
+            }
+        }
+    }
+
+    #[test]
+    fn flat_cube() {
+        let builder = cube().with_flat_normals().unwrap();
+        // Each face gets its own 4 vertices.
+        assert_eq!(builder.vertex_count(), Ok(Some(24)));
+        assert_eq!(builder.read_indices().unwrap().len(), 36);
+        assert_flat(&builder);
+    }
+
+    #[test]
+    fn smooth_cube() {
+        let builder = cube()
+            .with_smooth_normals(NormalWeighting::Angle, PI)
+            .unwrap();
+        assert_eq!(builder.vertex_count(), Ok(Some(8)));
+        let positions = builder.attribute::<Position>().unwrap();
+        let normals = builder.attribute::<Normal>().unwrap();
+        for (position, normal) in positions.zip(normals) {
+            // Corner normal points away from the center.
+            assert!(approx(normal.0, normalize(position.0)));
+        }
+    }
+
+    #[test]
+    fn creased_cube() {
+        // Cube edges are sharper than the crease angle.
+        let builder = cube()
+            .with_smooth_normals(NormalWeighting::Area, PI / 3.0)
+            .unwrap();
+        assert_eq!(builder.vertex_count(), Ok(Some(24)));
+        assert_flat(&builder);
+    }
+
+    #[test]
+    fn replace_normals() {
+        let mut builder = cube().with_flat_normals().unwrap();
+        builder
+            .set_smooth_normals(NormalWeighting::Area, PI / 3.0)
+            .unwrap();
+        // Existing normals are overwritten instead of adding new buffer.
+        assert_eq!(builder.vertices.len(), 2);
+        assert_flat(&builder);
+
+        builder.set_prim_type(Primitive::TriangleStrip);
+        assert_eq!(
+            builder.set_flat_normals().err(),
+            Some(NormalError::UnsupportedPrimitive(Primitive::TriangleStrip))
+        );
+    }
+}
@@ -0,0 +1,562 @@
+//!
+//! Reordering of triangles and vertices of `MeshBuilder` for rendering performance.
+//!
+
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use hal::Primitive;
+
+use access::AccessError;
+use math::{add, cross, dot, normalize, scale, sub};
+use mesh::{MeshBuilder, MeshBuilderError};
+use utils::narrow_indices;
+use vertex::Position;
+
+/// Efficiency of post-transform vertex cache for triangle list
+/// measured by simulating FIFO cache.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheStats {
+    /// Average cache miss ratio, i.e. vertex shader invocations per triangle.
+    /// `3.0` is the worst, large regular grids approach `0.5`.
+    pub acmr: f32,
+
+    /// Average transformed vertex ratio, i.e. vertex shader invocations per referenced vertex.
+    /// `1.0` is the best.
+    pub atvr: f32,
+}
+
+/// Cache statistics reported by `MeshBuilder::optimize`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OptimizeReport {
+    /// Statistics of the original order.
+    pub before: CacheStats,
+
+    /// Statistics of the optimized order.
+    pub after: CacheStats,
+}
+
+/// Error returned when mesh can't be optimized.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum OptimizeError {
+    /// Vertex data of the builder is malformed.
+    #[fail(display = "Vertex data is invalid: {}", _0)]
+    InvalidVertices(#[cause] MeshBuilderError),
+
+    /// Only triangle lists can be optimized.
+    #[fail(display = "Primitive {:?} is not a triangle list", _0)]
+    UnsupportedPrimitive(Primitive),
+
+    /// Positions required for overdraw optimization can't be read.
+    #[fail(display = "Positions are not accessible: {}", _0)]
+    InvalidPositions(#[cause] AccessError),
+
+    /// Index refers to vertex that doesn't exist.
+    #[fail(
+        display = "Index {} is out of range for {} vertices",
+        index, vertex_count
+    )]
+    IndexOutOfRange {
+        /// Value of the index.
+        index: u32,
+        /// Number of vertices.
+        vertex_count: usize,
+    },
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Simulate FIFO post-transform vertex cache with `cache_size` entries
+    /// for triangles in current order.
+    pub fn cache_stats(&self, cache_size: usize) -> Result<CacheStats, OptimizeError> {
+        let (vertex_count, indices) = read_triangles(self)?;
+        Ok(cache_stats(&indices, vertex_count, cache_size))
+    }
+
+    /// Reorder triangles for post-transform vertex cache of `cache_size` entries
+    /// using Tipsify algorithm, then reorder vertices in order of first use
+    /// so vertex fetch is sequential, and remap indices accordingly.
+    ///
+    /// If `overdraw_threshold` is set, triangles are also split into clusters
+    /// that are sorted so outward facing ones are drawn first, reducing overdraw.
+    /// Threshold is allowed ACMR degradation, e.g. `1.05` allows 5% more
+    /// vertex shader invocations in exchange for smaller clusters.
+    /// That requires `Position` attribute.
+    ///
+    /// Mesh without index buffer gets one.
+    /// Indices are stored as `u16` if they fit.
+    /// Vertices not referenced by any triangle are moved to the end.
+    pub fn optimize(
+        &mut self,
+        cache_size: usize,
+        overdraw_threshold: Option<f32>,
+    ) -> Result<OptimizeReport, OptimizeError> {
+        let (vertex_count, mut indices) = read_triangles(self)?;
+        let before = cache_stats(&indices, vertex_count, cache_size);
+
+        let corners = indices.len() / 3 * 3;
+        let mut optimized = tipsify(&indices[..corners], vertex_count, cache_size);
+        if let Some(threshold) = overdraw_threshold {
+            let positions = self
+                .attribute::<Position>()
+                .map_err(OptimizeError::InvalidPositions)?
+                .map(|position| position.0)
+                .collect::<Vec<_>>();
+            optimized = sort_clusters(&optimized, &positions, cache_size, threshold);
+        }
+        indices[..corners].copy_from_slice(&optimized);
+
+        // Vertices are numbered in order of first use.
+        let mut remap = vec![None; vertex_count];
+        let mut sources = Vec::with_capacity(vertex_count);
+        for index in &mut indices {
+            let vertex = *index as usize;
+            *index = *remap[vertex].get_or_insert_with(|| {
+                sources.push(vertex as u32);
+                sources.len() as u32 - 1
+            });
+        }
+        sources.extend((0..vertex_count as u32).filter(|&vertex| remap[vertex as usize].is_none()));
+
+        let after = cache_stats(&indices, vertex_count, cache_size);
+        self.remap_vertices(&sources);
+        self.set_indices(narrow_indices(indices));
+        Ok(OptimizeReport { before, after })
+    }
+}
+
+/// Read number of vertices and indices of triangle list.
+/// Sequential indices are generated for mesh without index buffer.
+fn read_triangles(builder: &MeshBuilder) -> Result<(usize, Vec<u32>), OptimizeError> {
+    let vertex_count = builder
+        .vertex_count()
+        .map_err(OptimizeError::InvalidVertices)?
+        .unwrap_or(0) as usize;
+    if builder.prim != Primitive::TriangleList {
+        return Err(OptimizeError::UnsupportedPrimitive(builder.prim));
+    }
+    let indices = builder
+        .read_indices()
+        .unwrap_or_else(|| (0..vertex_count as u32).collect());
+    if let Some(&index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertex_count)
+    {
+        return Err(OptimizeError::IndexOutOfRange {
+            index,
+            vertex_count,
+        });
+    }
+    Ok((vertex_count, indices))
+}
+
+/// FIFO cache simulated with timestamps.
+/// Vertex is in cache if it was added less than `size` insertions ago.
+struct Cache {
+    size: usize,
+    timestamps: Vec<usize>,
+    time: usize,
+}
+
+impl Cache {
+    fn new(size: usize, vertex_count: usize) -> Self {
+        Cache {
+            size,
+            timestamps: vec![0; vertex_count],
+            time: size + 1,
+        }
+    }
+
+    fn age(&self, vertex: usize) -> usize {
+        self.time - self.timestamps[vertex]
+    }
+
+    /// Access vertex. Returns `true` on cache miss.
+    fn access(&mut self, vertex: usize) -> bool {
+        if self.age(vertex) > self.size {
+            self.timestamps[vertex] = self.time;
+            self.time += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Access vertices of triangle. Returns number of cache misses.
+    fn access_triangle(&mut self, triangle: &[u32]) -> usize {
+        triangle
+            .iter()
+            .filter(|&&index| self.access(index as usize))
+            .count()
+    }
+
+    fn flush(&mut self) {
+        self.time += self.size + 1;
+    }
+}
+
+fn cache_stats(indices: &[u32], vertex_count: usize, cache_size: usize) -> CacheStats {
+    let triangles = indices.len() / 3;
+    let mut cache = Cache::new(cache_size, vertex_count);
+    let misses = indices
+        .chunks(3)
+        .take(triangles)
+        .map(|triangle| cache.access_triangle(triangle))
+        .sum::<usize>();
+
+    let mut referenced = vec![false; vertex_count];
+    for &index in &indices[..triangles * 3] {
+        referenced[index as usize] = true;
+    }
+    let referenced = referenced.into_iter().filter(|&used| used).count();
+
+    CacheStats {
+        acmr: ratio(misses, triangles),
+        atvr: ratio(misses, referenced),
+    }
+}
+
+fn ratio(count: usize, total: usize) -> f32 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f32 / total as f32
+    }
+}
+
+/// Reorder triangles for vertex cache locality.
+/// See "Fast Triangle Reordering for Vertex Locality and Reduced Overdraw"
+/// by Sander, Nehab and Barczak.
+fn tipsify(indices: &[u32], vertex_count: usize, cache_size: usize) -> Vec<u32> {
+    // Triangles using each vertex.
+    let mut offsets = vec![0; vertex_count + 1];
+    for &index in indices {
+        offsets[index as usize + 1] += 1;
+    }
+    for vertex in 0..vertex_count {
+        offsets[vertex + 1] += offsets[vertex];
+    }
+    let mut adjacency = vec![0; indices.len()];
+    let mut fill = offsets.clone();
+    for (corner, &index) in indices.iter().enumerate() {
+        adjacency[fill[index as usize]] = corner / 3;
+        fill[index as usize] += 1;
+    }
+
+    // Number of triangles not emitted yet for each vertex.
+    let mut live = (0..vertex_count)
+        .map(|vertex| offsets[vertex + 1] - offsets[vertex])
+        .collect::<Vec<_>>();
+    let mut emitted = vec![false; indices.len() / 3];
+    let mut cache = Cache::new(cache_size, vertex_count);
+    let mut dead_end = Vec::new();
+    let mut cursor = 0;
+    let mut output = Vec::with_capacity(indices.len());
+    let mut candidates = Vec::new();
+
+    let mut fanning = skip_dead_end(&live, &mut dead_end, &mut cursor);
+    while let Some(vertex) = fanning {
+        candidates.clear();
+        for &triangle in &adjacency[offsets[vertex]..offsets[vertex + 1]] {
+            if emitted[triangle] {
+                continue;
+            }
+            emitted[triangle] = true;
+            for &index in &indices[triangle * 3..triangle * 3 + 3] {
+                let index = index as usize;
+                output.push(index as u32);
+                dead_end.push(index);
+                candidates.push(index);
+                live[index] -= 1;
+                cache.access(index);
+            }
+        }
+
+        // Prefer the oldest candidate that will still be in cache
+        // after its remaining triangles are emitted.
+        let mut next: Option<(usize, usize)> = None;
+        for &candidate in &candidates {
+            if live[candidate] == 0 {
+                continue;
+            }
+            let age = cache.age(candidate);
+            let priority = if age + 2 * live[candidate] <= cache_size {
+                age
+            } else {
+                0
+            };
+            if next.map_or(true, |(_, best)| priority > best) {
+                next = Some((candidate, priority));
+            }
+        }
+        fanning = match next {
+            Some((candidate, _)) => Some(candidate),
+            None => skip_dead_end(&live, &mut dead_end, &mut cursor),
+        };
+    }
+    output
+}
+
+/// Pick recently used vertex with triangles left, or next such vertex in input order.
+fn skip_dead_end(live: &[usize], dead_end: &mut Vec<usize>, cursor: &mut usize) -> Option<usize> {
+    while let Some(vertex) = dead_end.pop() {
+        if live[vertex] > 0 {
+            return Some(vertex);
+        }
+    }
+    while *cursor < live.len() {
+        let vertex = *cursor;
+        *cursor += 1;
+        if live[vertex] > 0 {
+            return Some(vertex);
+        }
+    }
+    None
+}
+
+/// Split cache-optimized triangles into clusters and sort them
+/// so that clusters facing away from the mesh center are drawn first.
+fn sort_clusters(
+    indices: &[u32],
+    positions: &[[f32; 3]],
+    cache_size: usize,
+    threshold: f32,
+) -> Vec<u32> {
+    let triangles = indices.len() / 3;
+    let mut cache = Cache::new(cache_size, positions.len());
+
+    // Triangle missing all vertices in cache likely starts disjoint patch.
+    let hard = (0..triangles)
+        .filter(|&triangle| {
+            cache.access_triangle(&indices[triangle * 3..triangle * 3 + 3]) == 3 || triangle == 0
+        })
+        .collect::<Vec<_>>();
+
+    // Patches are split further where their ACMR is reached.
+    let mut starts = Vec::new();
+    for (i, &start) in hard.iter().enumerate() {
+        let end = hard.get(i + 1).cloned().unwrap_or(triangles);
+        let triangle = |t: usize| &indices[t * 3..t * 3 + 3];
+
+        cache.flush();
+        let misses = (start..end)
+            .map(|t| cache.access_triangle(triangle(t)))
+            .sum::<usize>();
+        let target = threshold * ratio(misses, end - start);
+
+        cache.flush();
+        starts.push(start);
+        let (mut misses, mut count) = (0, 0);
+        for t in start..end {
+            misses += cache.access_triangle(triangle(t));
+            count += 1;
+            if t + 1 < end && ratio(misses, count) <= target {
+                starts.push(t + 1);
+                cache.flush();
+                misses = 0;
+                count = 0;
+            }
+        }
+    }
+
+    let corners = |t: usize| {
+        [
+            positions[indices[t * 3] as usize],
+            positions[indices[t * 3 + 1] as usize],
+            positions[indices[t * 3 + 2] as usize],
+        ]
+    };
+    let area_centroid = |range: Range<usize>| {
+        let (mut area, mut centroid, mut normal) = (0.0, [0.0; 3], [0.0; 3]);
+        for t in range {
+            let c = corners(t);
+            let n = cross(sub(c[1], c[0]), sub(c[2], c[0]));
+            let a = dot(n, n).sqrt();
+            area += a;
+            centroid = add(centroid, scale(add(add(c[0], c[1]), c[2]), a / 3.0));
+            normal = add(normal, n);
+        }
+        let centroid = if area > 0.0 {
+            scale(centroid, 1.0 / area)
+        } else {
+            centroid
+        };
+        (centroid, normalize(normal))
+    };
+
+    let (center, _) = area_centroid(0..triangles);
+    let mut clusters = starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).cloned().unwrap_or(triangles);
+            let (centroid, normal) = area_centroid(start..end);
+            (dot(sub(centroid, center), normal), start..end)
+        })
+        .collect::<Vec<_>>();
+    clusters.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+
+    clusters
+        .into_iter()
+        .flat_map(|(_, range)| indices[range.start * 3..range.end * 3].iter().cloned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::bits;
+    use std::collections::VecDeque;
+
+    fn builder(vertex_count: usize, indices: Vec<u32>) -> MeshBuilder<'static> {
+        let positions = (0..vertex_count)
+            .map(|i| Position([(i % 8) as f32, (i / 8) as f32, 0.0]))
+            .collect::<Vec<_>>();
+        let mut builder = MeshBuilder::new();
+        builder
+            .push_vertices(positions)
+            .set_indices(narrow_indices(indices));
+        builder
+    }
+
+    /// Grid of `size` × `size` quads with triangles in scrambled order.
+    fn scrambled_grid(size: u32) -> (usize, Vec<u32>) {
+        let vertex = |x: u32, y: u32| y * (size + 1) + x;
+        let mut triangles = Vec::new();
+        for y in 0..size {
+            for x in 0..size {
+                triangles.push([vertex(x, y), vertex(x + 1, y), vertex(x + 1, y + 1)]);
+                triangles.push([vertex(x, y), vertex(x + 1, y + 1), vertex(x, y + 1)]);
+            }
+        }
+        let count = triangles.len();
+        let indices = (0..count)
+            .flat_map(|i| triangles[i * 13 % count].to_vec())
+            .collect();
+        (((size + 1) * (size + 1)) as usize, indices)
+    }
+
+    /// Straightforward FIFO cache simulation.
+    fn fifo_misses(indices: &[u32], cache_size: usize) -> usize {
+        let mut cache = VecDeque::new();
+        let mut misses = 0;
+        for index in indices {
+            if !cache.contains(index) {
+                misses += 1;
+                cache.push_back(*index);
+                if cache.len() > cache_size {
+                    cache.pop_front();
+                }
+            }
+        }
+        misses
+    }
+
+    /// Triangles as positions rotated to start with the smallest index of original vertex.
+    fn triangles(builder: &MeshBuilder) -> Vec<[[u32; 3]; 3]> {
+        let positions = builder.attribute::<Position>().unwrap().collect::<Vec<_>>();
+        let mut triangles = builder
+            .read_indices()
+            .unwrap()
+            .chunks(3)
+            .map(|t| {
+                let corner = |i: usize| bits(positions[t[i % 3] as usize].0);
+                let first = (0..3).min_by_key(|&i| corner(i)).unwrap();
+                [corner(first), corner(first + 1), corner(first + 2)]
+            })
+            .collect::<Vec<_>>();
+        triangles.sort();
+        triangles
+    }
+
+    #[test]
+    fn known_sequence() {
+        // Third triangle reuses vertices of the first one after they are evicted.
+        let mut builder = builder(6, vec![0, 1, 2, 3, 4, 5, 0, 1, 2]);
+        assert_eq!(
+            builder.cache_stats(3),
+            Ok(CacheStats {
+                acmr: 3.0,
+                atvr: 1.5,
+            })
+        );
+
+        let report = builder.optimize(3, None).unwrap();
+        assert_eq!(
+            report,
+            OptimizeReport {
+                before: CacheStats {
+                    acmr: 3.0,
+                    atvr: 1.5,
+                },
+                after: CacheStats {
+                    acmr: 2.0,
+                    atvr: 1.0,
+                },
+            }
+        );
+        assert_eq!(builder.cache_stats(3), Ok(report.after));
+        // Vertices are renumbered in order of first use.
+        assert_eq!(
+            builder.read_indices().unwrap(),
+            vec![0, 1, 2, 0, 1, 2, 3, 4, 5]
+        );
+    }
+
+    #[test]
+    fn grid() {
+        let cache_size = 8;
+        let (vertex_count, indices) = scrambled_grid(8);
+        let triangle_count = indices.len() / 3;
+        let mut builder = builder(vertex_count, indices.clone());
+        let original = triangles(&builder);
+
+        let before = builder.cache_stats(cache_size).unwrap();
+        let misses = fifo_misses(&indices, cache_size);
+        assert_eq!(before.acmr, misses as f32 / triangle_count as f32);
+        assert_eq!(before.atvr, misses as f32 / vertex_count as f32);
+
+        let report = builder.optimize(cache_size, None).unwrap();
+        assert_eq!(report.before, before);
+        let optimized = builder.read_indices().unwrap();
+        assert_eq!(
+            report.after.acmr,
+            fifo_misses(&optimized, cache_size) as f32 / triangle_count as f32
+        );
+        assert!(report.after.acmr < 0.8 * report.before.acmr);
+        assert_eq!(triangles(&builder), original);
+
+        let mut next = 0;
+        for &index in &optimized {
+            assert!(index <= next);
+            next = next.max(index + 1);
+        }
+    }
+
+    #[test]
+    fn overdraw() {
+        let (vertex_count, indices) = scrambled_grid(4);
+        let mut builder = builder(vertex_count, indices);
+        let original = triangles(&builder);
+        let report = builder.optimize(16, Some(1.05)).unwrap();
+        assert!(report.after.acmr <= report.before.acmr);
+        assert_eq!(triangles(&builder), original);
+    }
+
+    #[test]
+    fn errors() {
+        let mut builder = builder(3, vec![0, 1, 3]);
+        assert_eq!(
+            builder.cache_stats(16),
+            Err(OptimizeError::IndexOutOfRange {
+                index: 3,
+                vertex_count: 3,
+            })
+        );
+        builder.set_indices(narrow_indices(vec![0, 1, 2]));
+        builder.set_prim_type(Primitive::LineList);
+        assert_eq!(
+            builder.optimize(16, None).err(),
+            Some(OptimizeError::UnsupportedPrimitive(Primitive::LineList))
+        );
+    }
+}
@@ -0,0 +1,329 @@
+//!
+//! Conversions between floats and packed representations of attributes.
+//!
+
+/// Convert `f32` to IEEE 754 half-precision float bits.
+/// Rounds to nearest even.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mant = bits & 0x7f_ffff;
+
+    if exp == 0xff {
+        // Infinity or NaN.
+        return sign | 0x7c00 | if mant != 0 { 0x200 } else { 0 };
+    }
+
+    let exp = exp - 127 + 15;
+    if exp >= 0x1f {
+        // Too large. Becomes infinity.
+        return sign | 0x7c00;
+    }
+
+    if exp <= 0 {
+        if exp < -10 {
+            // Too small. Becomes zero.
+            return sign;
+        }
+        // Subnormal half.
+        let mant = mant | 0x80_0000;
+        let shift = (14 - exp) as u32;
+        let half = mant >> shift;
+        let rem = mant & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round = (rem > halfway || (rem == halfway && half & 1 != 0)) as u32;
+        return sign | (half + round) as u16;
+    }
+
+    let half = ((exp as u32) << 10) | (mant >> 13);
+    let rem = mant & 0x1fff;
+    let round = (rem > 0x1000 || (rem == 0x1000 && half & 1 != 0)) as u32;
+    // Carry from mantissa into exponent is intended.
+    sign | (half + round) as u16
+}
+
+/// Convert IEEE 754 half-precision float bits to `f32`.
+pub fn f16_to_f32(half: u16) -> f32 {
+    let sign = ((half & 0x8000) as u32) << 16;
+    let exp = ((half >> 10) & 0x1f) as u32;
+    let mant = (half & 0x3ff) as u32;
+
+    let bits = if exp == 0 {
+        if mant == 0 {
+            sign
+        } else {
+            // Subnormal half is normal `f32`.
+            let mut exp = 127 - 15 + 1;
+            let mut mant = mant;
+            while mant & 0x400 == 0 {
+                mant <<= 1;
+                exp -= 1;
+            }
+            sign | (exp << 23) | ((mant & 0x3ff) << 13)
+        }
+    } else if exp == 0x1f {
+        sign | 0x7f80_0000 | (mant << 13)
+    } else {
+        sign | ((exp + 127 - 15) << 23) | (mant << 13)
+    };
+
+    f32::from_bits(bits)
+}
+
+/// Convert float in range `[0, 1]` to unsigned normalized value with `bits` bits.
+pub fn to_unorm(value: f32, bits: u32) -> u32 {
+    let max = ((1u64 << bits) - 1) as f32;
+    (clamp(value, 0.0, 1.0) * max).round() as u32
+}
+
+/// Convert unsigned normalized value with `bits` bits to float in range `[0, 1]`.
+pub fn from_unorm(value: u32, bits: u32) -> f32 {
+    let max = ((1u64 << bits) - 1) as f32;
+    value as f32 / max
+}
+
+/// Convert weights to unsigned normalized values with `bits` bits
+/// keeping their sum equal to the maximum value.
+/// Rounding error is compensated on the largest weight.
+pub fn quantize_weights(weights: [f32; 4], bits: u32) -> [u32; 4] {
+    let max = ((1u64 << bits) - 1) as i64;
+    let mut quantized = [0u32; 4];
+    let mut sum = 0;
+    let mut largest = 0;
+    for i in 0..4 {
+        quantized[i] = to_unorm(weights[i], bits);
+        sum += quantized[i] as i64;
+        if quantized[i] > quantized[largest] {
+            largest = i;
+        }
+    }
+    if sum != 0 {
+        let fixed = quantized[largest] as i64 + max - sum;
+        quantized[largest] = fixed.max(0).min(max) as u32;
+    }
+    quantized
+}
+
+/// Convert float in range `[-1, 1]` to signed normalized value with `bits` bits.
+pub fn to_snorm(value: f32, bits: u32) -> i32 {
+    let max = ((1u64 << (bits - 1)) - 1) as f32;
+    (clamp(value, -1.0, 1.0) * max).round() as i32
+}
+
+/// Convert signed normalized value with `bits` bits to float in range `[-1, 1]`.
+pub fn from_snorm(value: i32, bits: u32) -> f32 {
+    let max = ((1u64 << (bits - 1)) - 1) as f32;
+    (value as f32 / max).max(-1.0)
+}
+
+/// Pack four floats in range `[-1, 1]` into `A2B10G10R10` signed normalized value.
+pub fn pack_snorm_a2b10g10r10(value: [f32; 4]) -> u32 {
+    let x = to_snorm(value[0], 10) as u32 & 0x3ff;
+    let y = to_snorm(value[1], 10) as u32 & 0x3ff;
+    let z = to_snorm(value[2], 10) as u32 & 0x3ff;
+    let w = to_snorm(value[3], 2) as u32 & 0x3;
+    x | (y << 10) | (z << 20) | (w << 30)
+}
+
+/// Unpack `A2B10G10R10` signed normalized value into four floats in range `[-1, 1]`.
+pub fn unpack_snorm_a2b10g10r10(value: u32) -> [f32; 4] {
+    // Shift field to the top and back to extend sign.
+    let field = |shift: u32, bits: u32| ((value << (32 - shift - bits)) as i32) >> (32 - bits);
+    [
+        from_snorm(field(0, 10), 10),
+        from_snorm(field(10, 10), 10),
+        from_snorm(field(20, 10), 10),
+        from_snorm(field(30, 2), 2),
+    ]
+}
+
+/// Encode unit vector into octahedral representation.
+pub fn oct_encode(vector: [f32; 3]) -> [f32; 2] {
+    let l1 = vector[0].abs() + vector[1].abs() + vector[2].abs();
+    if l1 == 0.0 {
+        return [0.0, 0.0];
+    }
+    let x = vector[0] / l1;
+    let y = vector[1] / l1;
+    if vector[2] < 0.0 {
+        [(1.0 - y.abs()) * sign(x), (1.0 - x.abs()) * sign(y)]
+    } else {
+        [x, y]
+    }
+}
+
+/// Decode unit vector from octahedral representation.
+pub fn oct_decode(oct: [f32; 2]) -> [f32; 3] {
+    let z = 1.0 - oct[0].abs() - oct[1].abs();
+    let (x, y) = if z < 0.0 {
+        (
+            (1.0 - oct[1].abs()) * sign(oct[0]),
+            (1.0 - oct[0].abs()) * sign(oct[1]),
+        )
+    } else {
+        (oct[0], oct[1])
+    };
+    let len = (x * x + y * y + z * z).sqrt();
+    [x / len, y / len, z / len]
+}
+
+fn sign(value: f32) -> f32 {
+    if value < 0.0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
+
+fn clamp(value: f32, min: f32, max: f32) -> f32 {
+    value.max(min).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &[f32], b: &[f32], epsilon: f32) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert!((a - b).abs() <= epsilon, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn f16_zero() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f16_to_f32(0x0000).to_bits(), 0.0f32.to_bits());
+        assert_eq!(f16_to_f32(0x8000).to_bits(), (-0.0f32).to_bits());
+    }
+
+    #[test]
+    fn f16_normal() {
+        for &(value, half) in &[
+            (1.0, 0x3c00),
+            (-2.0, 0xc000),
+            (0.5, 0x3800),
+            (65504.0, 0x7bff),
+            (1.0 / 16384.0, 0x0400),
+        ] {
+            assert_eq!(f32_to_f16(value), half);
+            assert_eq!(f16_to_f32(half), value);
+        }
+        // Round to nearest even.
+        assert_eq!(f32_to_f16(1.0 + 1.0 / 2048.0), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 / 2048.0), 0x3c02);
+    }
+
+    #[test]
+    fn f16_subnormal() {
+        let min = 1.0 / 16_777_216.0;
+        assert_eq!(f32_to_f16(min), 0x0001);
+        assert_eq!(f32_to_f16(-min), 0x8001);
+        assert_eq!(f16_to_f32(0x0001), min);
+        assert_eq!(f16_to_f32(0x03ff), 1023.0 * min);
+        assert_eq!(f32_to_f16(1023.0 * min), 0x03ff);
+        // Half of the smallest subnormal rounds to even zero, more than half rounds up.
+        assert_eq!(f32_to_f16(min / 2.0), 0x0000);
+        assert_eq!(f32_to_f16(min * 0.75), 0x0001);
+        assert_eq!(f32_to_f16(1.0e-10), 0x0000);
+        assert_eq!(f32_to_f16(-1.0e-10), 0x8000);
+        for half in 1..0x400 {
+            assert_eq!(f32_to_f16(f16_to_f32(half)), half);
+        }
+    }
+
+    #[test]
+    fn f16_special() {
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+        // Overflow becomes infinity, including rounding up past the largest half.
+        assert_eq!(f32_to_f16(1.0e6), 0x7c00);
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+
+        let nan = f32_to_f16(f32::NAN);
+        assert_eq!(nan & 0x7c00, 0x7c00);
+        assert_ne!(nan & 0x3ff, 0);
+        assert!(f16_to_f32(nan).is_nan());
+        assert!(f16_to_f32(0x7c01).is_nan());
+    }
+
+    #[test]
+    fn unorm() {
+        assert_eq!(to_unorm(0.0, 8), 0);
+        assert_eq!(to_unorm(1.0, 8), 255);
+        assert_eq!(to_unorm(2.0, 8), 255);
+        assert_eq!(to_unorm(-1.0, 16), 0);
+        assert_eq!(to_unorm(0.5, 8), 128);
+        for value in 0..256 {
+            assert_eq!(to_unorm(from_unorm(value, 8), 8), value);
+        }
+        assert_eq!(from_unorm(65535, 16), 1.0);
+    }
+
+    #[test]
+    fn snorm() {
+        assert_eq!(to_snorm(1.0, 8), 127);
+        assert_eq!(to_snorm(-1.0, 8), -127);
+        assert_eq!(to_snorm(-2.0, 16), -32767);
+        assert_eq!(to_snorm(0.0, 16), 0);
+        assert_eq!(to_snorm(-0.0, 16), 0);
+        // Minimum value maps to -1 too.
+        assert_eq!(from_snorm(-128, 8), -1.0);
+        for value in -127..128 {
+            assert_eq!(to_snorm(from_snorm(value, 8), 8), value);
+        }
+    }
+
+    #[test]
+    fn weights() {
+        let quantized = quantize_weights([1.0 / 3.0, 1.0 / 3.0, 1.0 / 3.0, 0.0], 8);
+        assert_eq!(quantized.iter().sum::<u32>(), 255);
+        assert_eq!(quantize_weights([1.0, 0.0, 0.0, 0.0], 16), [65535, 0, 0, 0]);
+        assert_eq!(quantize_weights([0.0; 4], 8), [0; 4]);
+    }
+
+    #[test]
+    fn a2b10g10r10() {
+        assert_eq!(pack_snorm_a2b10g10r10([0.0; 4]), 0);
+        assert_eq!(pack_snorm_a2b10g10r10([1.0, 0.0, 0.0, 1.0]), 0x4000_01ff);
+        assert_eq!(
+            unpack_snorm_a2b10g10r10(pack_snorm_a2b10g10r10([1.0, -1.0, 0.5, -1.0])),
+            [1.0, -1.0, 256.0 / 511.0, -1.0]
+        );
+        for &value in &[[0.25, -0.75, 0.0, 1.0], [-1.0, 1.0, -0.5, -1.0]] {
+            let unpacked = unpack_snorm_a2b10g10r10(pack_snorm_a2b10g10r10(value));
+            assert_close(&unpacked, &value, 1.0 / 511.0);
+            assert_eq!(unpacked[3], value[3]);
+        }
+        // Both encodings of -1 decode to -1.
+        assert_eq!(unpack_snorm_a2b10g10r10(0x200)[0], -1.0);
+        assert_eq!(unpack_snorm_a2b10g10r10(0x8000_0000)[3], -1.0);
+    }
+
+    #[test]
+    fn octahedral() {
+        let vectors = [
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, -1.0],
+            [1.0, 0.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [0.6, 0.0, -0.8],
+            [-0.48, 0.6, -0.64],
+            [0.48, -0.6, -0.64],
+            [-0.0, -0.0, -1.0],
+        ];
+        for vector in &vectors {
+            let oct = oct_encode(*vector);
+            assert!(oct[0].abs() <= 1.0 && oct[1].abs() <= 1.0);
+            assert_close(&oct_decode(oct), vector, 1.0e-6);
+        }
+        // Lower hemisphere folds into corners.
+        assert_eq!(oct_encode([0.0, 0.0, -1.0]), [1.0, 1.0]);
+        assert_eq!(oct_encode([-0.6, 0.0, -0.8])[0], -1.0 + 0.0);
+        assert_eq!(oct_encode([0.0; 3]), [0.0, 0.0]);
+    }
+}
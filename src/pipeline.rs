@@ -0,0 +1,346 @@
+//!
+//! Vertex input descriptions for graphics pipelines.
+//!
+
+use hal::pso::{AttributeDesc, GraphicsPipelineDesc, Location, VertexBufferDesc};
+use hal::Backend;
+
+use vertex::{Query, VertexFormat};
+
+/// Location assignment for attributes of vertex formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Locations<'a> {
+    /// Assign locations in order of formats and their attributes starting from `0`.
+    Sequential,
+
+    /// Location of an attribute is the index of its name in the slice.
+    /// Attributes with names that are not listed are not described.
+    Names(&'a [&'a str]),
+
+    /// Caller-supplied location for each attribute name.
+    /// Attributes with names that are not listed are not described.
+    Explicit(&'a [(&'a str, Location)]),
+}
+
+/// Error returned when vertex input description can't be created.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum VertexInputDescError {
+    /// Location is assigned to attribute that none of formats has.
+    #[fail(display = "Attribute \"{}\" is not found in vertex formats", name)]
+    MissingAttribute {
+        /// Name of the attribute.
+        name: String,
+    },
+
+    /// Same location is assigned to two attributes.
+    #[fail(
+        display = "Attributes \"{}\" and \"{}\" are both assigned to location {}",
+        first, second, location
+    )]
+    LocationClash {
+        /// Assigned location.
+        location: Location,
+        /// Name of the first attribute.
+        first: String,
+        /// Name of the second attribute.
+        second: String,
+    },
+}
+
+/// Vertex buffers and attributes of a graphics pipeline.
+///
+/// Buffer bindings are numbered in order of the formats
+/// which is the order `Mesh::bind` pushes buffers into `VertexBufferSet`.
+/// So the same sorted formats list must be used for both.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VertexInputDesc {
+    /// Descriptions of vertex buffers. Binding is the index of the format.
+    pub vertex_buffers: Vec<VertexBufferDesc>,
+
+    /// Descriptions of attributes.
+    pub attributes: Vec<AttributeDesc>,
+}
+
+impl VertexInputDesc {
+    /// Describe vertex input consumed from buffers with specified formats.
+    pub fn new(
+        formats: &[VertexFormat],
+        locations: Locations,
+    ) -> Result<Self, VertexInputDescError> {
+        let vertex_buffers = formats
+            .iter()
+            .enumerate()
+            .map(|(binding, format)| VertexBufferDesc {
+                binding: binding as _,
+                stride: format.stride,
+                rate: format.rate,
+            })
+            .collect();
+
+        let find = |name: &str| {
+            formats
+                .iter()
+                .enumerate()
+                .filter_map(|(binding, format)| {
+                    format
+                        .attributes
+                        .iter()
+                        .find(|attribute| attribute.name == name)
+                        .map(|attribute| (binding, attribute.element))
+                })
+                .next()
+                .ok_or_else(|| VertexInputDescError::MissingAttribute {
+                    name: name.to_string(),
+                })
+        };
+
+        let mut attributes = Vec::new();
+        match locations {
+            Locations::Sequential => {
+                for (binding, format) in formats.iter().enumerate() {
+                    for attribute in format.attributes.iter() {
+                        attributes.push(AttributeDesc {
+                            location: attributes.len() as _,
+                            binding: binding as _,
+                            element: attribute.element,
+                        });
+                    }
+                }
+            }
+            Locations::Names(list) => {
+                for (location, &name) in list.iter().enumerate() {
+                    let (binding, element) = find(name)?;
+                    attributes.push(AttributeDesc {
+                        location: location as _,
+                        binding: binding as _,
+                        element,
+                    });
+                }
+            }
+            Locations::Explicit(list) => {
+                for (index, &(name, location)) in list.iter().enumerate() {
+                    if let Some(&(first, _)) = list[..index].iter().find(|&&(_, l)| l == location) {
+                        return Err(VertexInputDescError::LocationClash {
+                            location,
+                            first: first.to_string(),
+                            second: name.to_string(),
+                        });
+                    }
+                    let (binding, element) = find(name)?;
+                    attributes.push(AttributeDesc {
+                        location,
+                        binding: binding as _,
+                        element,
+                    });
+                }
+            }
+        }
+
+        Ok(VertexInputDesc {
+            vertex_buffers,
+            attributes,
+        })
+    }
+
+    /// Describe vertex input consumed from single buffer of vertex format `V`
+    /// bound at binding `0`.
+    /// Attributes from tuple `T` get locations in order of the tuple starting from `0`.
+    pub fn from_query<V, T>() -> Self
+    where
+        V: Query<T>,
+    {
+        VertexInputDesc {
+            vertex_buffers: vec![VertexBufferDesc {
+                binding: 0,
+                stride: V::VERTEX_FORMAT.stride,
+                rate: V::VERTEX_FORMAT.rate,
+            }],
+            attributes: V::QUERIED_ATTRIBUTES
+                .iter()
+                .enumerate()
+                .map(|(location, &(_, element))| AttributeDesc {
+                    location: location as _,
+                    binding: 0,
+                    element,
+                })
+                .collect(),
+        }
+    }
+
+    /// Replace vertex buffers and attributes of the pipeline description.
+    pub fn fill<B>(&self, desc: &mut GraphicsPipelineDesc<B>)
+    where
+        B: Backend,
+    {
+        desc.vertex_buffers = self.vertex_buffers.clone();
+        desc.attributes = self.attributes.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hal::format::Format;
+    use hal::pso::Element;
+    use vertex::{AsVertexFormat, Color, Normal, PosNorm, PosNormTex, Position, TexCoord};
+
+    /// Sorted formats as passed to `Mesh::bind`: `PosNorm` per vertex and `Color` per instance.
+    fn formats() -> Vec<VertexFormat<'static>> {
+        let mut formats = vec![PosNorm::VERTEX_FORMAT, Color::VERTEX_FORMAT.per_instance(1)];
+        formats.sort();
+        formats
+    }
+
+    fn binding(formats: &[VertexFormat], name: &str) -> u32 {
+        formats
+            .iter()
+            .position(|format| format.attribute(name).is_some())
+            .unwrap() as u32
+    }
+
+    fn attribute(formats: &[VertexFormat], location: Location, name: &str) -> AttributeDesc {
+        let binding = binding(formats, name);
+        AttributeDesc {
+            location,
+            binding,
+            element: formats[binding as usize].attribute(name).unwrap().element,
+        }
+    }
+
+    #[test]
+    fn vertex_buffers() {
+        let formats = formats();
+        let desc = VertexInputDesc::new(&formats, Locations::Sequential).unwrap();
+        assert_eq!(
+            desc.vertex_buffers,
+            formats
+                .iter()
+                .enumerate()
+                .map(|(binding, format)| VertexBufferDesc {
+                    binding: binding as _,
+                    stride: format.stride,
+                    rate: format.rate,
+                })
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            desc.vertex_buffers[binding(&formats, "color") as usize].rate,
+            1
+        );
+    }
+
+    #[test]
+    fn sequential() {
+        let formats = formats();
+        let desc = VertexInputDesc::new(&formats, Locations::Sequential).unwrap();
+        let names = formats
+            .iter()
+            .flat_map(|format| format.attributes.iter().map(|a| &*a.name))
+            .collect::<Vec<_>>();
+        assert_eq!(names.len(), 3);
+        assert_eq!(
+            desc.attributes,
+            names
+                .iter()
+                .enumerate()
+                .map(|(location, name)| attribute(&formats, location as _, name))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn names() {
+        let formats = formats();
+        let desc =
+            VertexInputDesc::new(&formats, Locations::Names(&["color", "position"])).unwrap();
+        assert_eq!(
+            desc.attributes,
+            vec![
+                attribute(&formats, 0, "color"),
+                attribute(&formats, 1, "position"),
+            ]
+        );
+
+        assert_eq!(
+            VertexInputDesc::new(&formats, Locations::Names(&["position", "tex_coord"])),
+            Err(VertexInputDescError::MissingAttribute {
+                name: "tex_coord".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn explicit() {
+        let formats = formats();
+        let desc = VertexInputDesc::new(
+            &formats,
+            Locations::Explicit(&[("normal", 3), ("color", 5)]),
+        )
+        .unwrap();
+        assert_eq!(
+            desc.attributes,
+            vec![
+                attribute(&formats, 3, "normal"),
+                attribute(&formats, 5, "color"),
+            ]
+        );
+
+        assert_eq!(
+            VertexInputDesc::new(
+                &formats,
+                Locations::Explicit(&[("position", 0), ("normal", 1), ("color", 0)]),
+            ),
+            Err(VertexInputDescError::LocationClash {
+                location: 0,
+                first: "position".into(),
+                second: "color".into(),
+            })
+        );
+        assert_eq!(
+            VertexInputDesc::new(&formats, Locations::Explicit(&[("weights", 0)])),
+            Err(VertexInputDescError::MissingAttribute {
+                name: "weights".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn from_query() {
+        let desc = VertexInputDesc::from_query::<PosNormTex, (Position, TexCoord)>();
+        assert_eq!(
+            desc.vertex_buffers,
+            vec![VertexBufferDesc {
+                binding: 0,
+                stride: 32,
+                rate: 0,
+            }]
+        );
+        assert_eq!(
+            desc.attributes,
+            vec![
+                AttributeDesc {
+                    location: 0,
+                    binding: 0,
+                    element: Element {
+                        format: Format::Rgb32Float,
+                        offset: 0,
+                    },
+                },
+                AttributeDesc {
+                    location: 1,
+                    binding: 0,
+                    element: Element {
+                        format: Format::Rg32Float,
+                        offset: 24,
+                    },
+                },
+            ]
+        );
+        assert_eq!(
+            VertexInputDesc::from_query::<PosNormTex, (Normal,)>().attributes[0]
+                .element
+                .offset,
+            12
+        );
+    }
+}
@@ -0,0 +1,721 @@
+//!
+//! Procedural generation of primitive shapes.
+//!
+//! All shapes are triangle lists of `PosNormTangTex` vertices centered at the origin
+//! with `+Y` as up axis and counter-clockwise front faces.
+//! Texture coordinates have origin at the top-left corner of the texture
+//! and tangents point in the direction of increasing `u` with handedness `1.0`
+//! as `MeshBuilder::set_tangents` would generate.
+//! Along UV seams `set_tangents` sees faces on one side only,
+//! so its tangents there tilt towards that side while generated ones don't.
+//! Vertices are duplicated along UV seams and hard edges.
+//! Indices are `u16` if all vertices can be addressed with them.
+//!
+
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use math::{add, dot, normalize, scale};
+use mesh::MeshBuilder;
+use utils::narrow_indices;
+use vertex::PosNormTangTex;
+
+/// Axis-aligned box with `size` extents.
+/// Each face is split into `segments` × `segments` quads and mapped to the whole texture.
+pub fn cuboid(size: [f32; 3], segments: u32) -> MeshBuilder<'static> {
+    let segments = segments.max(1);
+    let mut shape = Shape::default();
+    // Normal, tangent and down direction of the texture for each face.
+    let faces = [
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, -1.0, 0.0]),
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, -1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+    ];
+    let extent = |axis: [f32; 3]| dot(abs(axis), size);
+    for &(normal, tangent, down) in &faces {
+        let center = scale(normal, extent(normal) / 2.0);
+        let (width, height) = (extent(tangent), extent(down));
+        shape.grid(segments, segments, |column, row| {
+            let u = column as f32 / segments as f32;
+            let v = row as f32 / segments as f32;
+            vertex(
+                add(
+                    center,
+                    add(
+                        scale(tangent, (u - 0.5) * width),
+                        scale(down, (v - 0.5) * height),
+                    ),
+                ),
+                normal,
+                tangent,
+                [u, v],
+            )
+        });
+    }
+    shape.build()
+}
+
+/// Sphere with vertices placed on `sectors` meridians and `stacks` parallels.
+/// Texture is mapped with equirectangular projection.
+pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> MeshBuilder<'static> {
+    let sectors = sectors.max(3);
+    let stacks = stacks.max(2);
+    let mut shape = Shape::default();
+    shape.grid(sectors, stacks, |column, row| {
+        let (sin_theta, cos_theta) = polar(row, stacks, PI);
+        let u = pole_u(column, sectors, sin_theta);
+        let v = row as f32 / stacks as f32;
+        sphere_vertex([0.0; 3], radius, sin_theta, cos_theta, u, v)
+    });
+    shape.build()
+}
+
+/// Sphere made by subdividing faces of icosahedron `subdivisions` times.
+/// Triangles are almost equal in size, unlike `uv_sphere`.
+/// Texture is mapped with equirectangular projection.
+pub fn icosphere(radius: f32, subdivisions: u32) -> MeshBuilder<'static> {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut positions = vec![
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ];
+    for position in &mut positions {
+        *position = normalize(*position);
+    }
+    let mut triangles = vec![
+        [0, 11, 5],
+        [0, 5, 1],
+        [0, 1, 7],
+        [0, 7, 10],
+        [0, 10, 11],
+        [1, 5, 9],
+        [5, 11, 4],
+        [11, 10, 2],
+        [10, 7, 6],
+        [7, 1, 8],
+        [3, 9, 4],
+        [3, 4, 2],
+        [3, 2, 6],
+        [3, 6, 8],
+        [3, 8, 9],
+        [4, 9, 5],
+        [2, 4, 11],
+        [6, 2, 10],
+        [8, 6, 7],
+        [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = HashMap::new();
+        let mut midpoint = |a: usize, b: usize| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                positions.push(normalize(scale(add(positions[a], positions[b]), 0.5)));
+                positions.len() - 1
+            })
+        };
+        triangles = triangles
+            .iter()
+            .flat_map(|triangle| {
+                let (a, b, c) = (triangle[0], triangle[1], triangle[2]);
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    // Vertices are split by texture coordinate along the seam and at the poles.
+    let mut shape = Shape::default();
+    let mut split = HashMap::new();
+    for triangle in &triangles {
+        let mut us = [0.0; 3];
+        for (u, &index) in us.iter_mut().zip(triangle) {
+            let p = positions[index];
+            *u = p[0].atan2(p[2]) / (2.0 * PI);
+            if *u < 0.0 {
+                *u += 1.0;
+            }
+        }
+        let is_pole =
+            |i: usize| positions[triangle[i]][0] == 0.0 && positions[triangle[i]][2] == 0.0;
+        let regular = (0..3).filter(|&i| !is_pole(i)).collect::<Vec<_>>();
+        let max = regular.iter().map(|&i| us[i]).fold(0.0, f32::max);
+        for &i in &regular {
+            if max - us[i] > 0.5 {
+                us[i] += 1.0;
+            }
+        }
+        let mean = regular.iter().map(|&i| us[i]).sum::<f32>() / regular.len() as f32;
+
+        for i in 0..3 {
+            let u = if is_pole(i) { mean } else { us[i] };
+            let index = triangle[i];
+            let vertices = &mut shape.vertices;
+            let vertex = *split.entry((index, u.to_bits())).or_insert_with(|| {
+                let p = positions[index];
+                let sin_theta = (p[0] * p[0] + p[2] * p[2]).sqrt();
+                let v = p[1].clamp(-1.0, 1.0).acos() / PI;
+                vertices.push(sphere_vertex([0.0; 3], radius, sin_theta, p[1], u, v));
+                vertices.len() as u32 - 1
+            });
+            shape.indices.push(vertex);
+        }
+    }
+    shape.build()
+}
+
+/// Plane in XZ plane facing `+Y` with `size` extents.
+/// Plane is split into `subdivisions[0]` columns along X and `subdivisions[1]` rows along Z.
+/// Texture is mapped to the whole plane with `u` along `+X` and `v` along `+Z`.
+pub fn plane(size: [f32; 2], subdivisions: [u32; 2]) -> MeshBuilder<'static> {
+    let columns = subdivisions[0].max(1);
+    let rows = subdivisions[1].max(1);
+    let mut shape = Shape::default();
+    shape.grid(columns, rows, |column, row| {
+        let u = column as f32 / columns as f32;
+        let v = row as f32 / rows as f32;
+        vertex(
+            [(u - 0.5) * size[0], 0.0, (v - 0.5) * size[1]],
+            [0.0, 1.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [u, v],
+        )
+    });
+    shape.build()
+}
+
+/// Disc in XZ plane facing `+Y`
+/// split into `sectors` slices and `rings` concentric rings.
+/// Texture is mapped the same way as for `plane`.
+pub fn disc(radius: f32, sectors: u32, rings: u32) -> MeshBuilder<'static> {
+    let mut shape = Shape::default();
+    shape.disc(radius, 0.0, 1.0, sectors, rings);
+    shape.build()
+}
+
+/// Cylinder along Y axis split into `sectors` slices and `stacks` stacks.
+/// Side texture wraps around the cylinder.
+/// Caps are added if `caps` is `true` and are mapped as `disc`.
+pub fn cylinder(
+    radius: f32,
+    height: f32,
+    sectors: u32,
+    stacks: u32,
+    caps: bool,
+) -> MeshBuilder<'static> {
+    let sectors = sectors.max(3);
+    let stacks = stacks.max(1);
+    let mut shape = Shape::default();
+    shape.grid(sectors, stacks, |column, row| {
+        let u = column as f32 / sectors as f32;
+        let v = row as f32 / stacks as f32;
+        let (sin_phi, cos_phi) = (2.0 * PI * u).sin_cos();
+        vertex(
+            [radius * sin_phi, height * (0.5 - v), radius * cos_phi],
+            [sin_phi, 0.0, cos_phi],
+            [cos_phi, 0.0, -sin_phi],
+            [u, v],
+        )
+    });
+    if caps {
+        shape.disc(radius, height / 2.0, 1.0, sectors, 1);
+        shape.disc(radius, -height / 2.0, -1.0, sectors, 1);
+    }
+    shape.build()
+}
+
+/// Cone along Y axis with apex at `+Y` split into `sectors` slices and `stacks` stacks.
+/// Side texture wraps around the cone.
+/// Base is added if `cap` is `true` and is mapped as `disc`.
+pub fn cone(
+    radius: f32,
+    height: f32,
+    sectors: u32,
+    stacks: u32,
+    cap: bool,
+) -> MeshBuilder<'static> {
+    let sectors = sectors.max(3);
+    let stacks = stacks.max(1);
+    let slant = (radius * radius + height * height).sqrt();
+    let mut shape = Shape::default();
+    shape.grid(sectors, stacks, |column, row| {
+        let v = row as f32 / stacks as f32;
+        let u = pole_u(column, sectors, v);
+        let (sin_phi, cos_phi) = (2.0 * PI * u).sin_cos();
+        vertex(
+            [
+                radius * v * sin_phi,
+                height * (0.5 - v),
+                radius * v * cos_phi,
+            ],
+            [
+                height * sin_phi / slant,
+                radius / slant,
+                height * cos_phi / slant,
+            ],
+            [cos_phi, 0.0, -sin_phi],
+            [u, v],
+        )
+    });
+    if cap {
+        shape.disc(radius, -height / 2.0, -1.0, sectors, 1);
+    }
+    shape.build()
+}
+
+/// Torus around Y axis with `major_radius` from the center to the middle of the tube
+/// and `minor_radius` of the tube.
+/// Split into `sectors` slices around Y axis and `sides` slices around the tube.
+/// Texture wraps around both circles.
+pub fn torus(
+    major_radius: f32,
+    minor_radius: f32,
+    sectors: u32,
+    sides: u32,
+) -> MeshBuilder<'static> {
+    let sectors = sectors.max(3);
+    let sides = sides.max(3);
+    let mut shape = Shape::default();
+    shape.grid(sectors, sides, |column, row| {
+        let u = column as f32 / sectors as f32;
+        let v = row as f32 / sides as f32;
+        let (sin_phi, cos_phi) = (2.0 * PI * u).sin_cos();
+        let (sin_beta, cos_beta) = (2.0 * PI * v).sin_cos();
+        let distance = major_radius + minor_radius * cos_beta;
+        vertex(
+            [
+                distance * sin_phi,
+                -minor_radius * sin_beta,
+                distance * cos_phi,
+            ],
+            [cos_beta * sin_phi, -sin_beta, cos_beta * cos_phi],
+            [cos_phi, 0.0, -sin_phi],
+            [u, v],
+        )
+    });
+    shape.build()
+}
+
+/// Capsule along Y axis made of cylinder with `height` and two hemispheres.
+/// Split into `sectors` slices and `rings` parallels per hemisphere.
+/// Texture wraps around the capsule with `v` proportional to the length of the profile.
+pub fn capsule(radius: f32, height: f32, sectors: u32, rings: u32) -> MeshBuilder<'static> {
+    let sectors = sectors.max(3);
+    let rings = rings.max(1);
+    let length = PI * radius + height;
+    let mut shape = Shape::default();
+    shape.grid(sectors, 2 * rings + 1, |column, row| {
+        let top = row <= rings;
+        let ring = if top { row } else { row - rings - 1 };
+        let (sin_alpha, cos_alpha) = polar(ring, rings, PI / 2.0);
+        // Polar angle is `alpha` on top hemisphere and `π/2 + alpha` on bottom one.
+        let (sin_theta, cos_theta, y, arc) = if top {
+            (sin_alpha, cos_alpha, height / 2.0, 0.0)
+        } else {
+            (
+                cos_alpha,
+                -sin_alpha,
+                -height / 2.0,
+                PI / 2.0 * radius + height,
+            )
+        };
+        let u = pole_u(column, sectors, sin_theta);
+        let v = (arc + PI / 2.0 * radius * ring as f32 / rings as f32) / length;
+        sphere_vertex([0.0, y, 0.0], radius, sin_theta, cos_theta, u, v)
+    });
+    shape.build()
+}
+
+/// Vertices and indices of a shape under construction.
+#[derive(Default)]
+struct Shape {
+    vertices: Vec<PosNormTangTex>,
+    indices: Vec<u32>,
+}
+
+impl Shape {
+    /// Add `columns` × `rows` quads with vertices produced by `f(column, row)`.
+    /// Columns should go right and rows should go down when viewed from the front.
+    /// Triangles with coinciding corners are skipped.
+    /// Quads collapsed to a triangle keep the pole vertex of their own column.
+    fn grid<F>(&mut self, columns: u32, rows: u32, mut f: F)
+    where
+        F: FnMut(u32, u32) -> PosNormTangTex,
+    {
+        let base = self.vertices.len() as u32;
+        for row in 0..rows + 1 {
+            for column in 0..columns + 1 {
+                self.vertices.push(f(column, row));
+            }
+        }
+
+        let vertices = &self.vertices;
+        let indices = &mut self.indices;
+        let index = |column: u32, row: u32| base + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let a = index(column, row);
+                let b = index(column, row + 1);
+                let c = index(column + 1, row + 1);
+                let d = index(column + 1, row);
+                let triangles = if vertices[b as usize].position == vertices[c as usize].position {
+                    [[a, b, d], [b, c, d]]
+                } else {
+                    [[a, b, c], [a, c, d]]
+                };
+                for &triangle in &triangles {
+                    let position = |i: usize| vertices[triangle[i] as usize].position;
+                    if position(0) != position(1)
+                        && position(1) != position(2)
+                        && position(2) != position(0)
+                    {
+                        indices.extend_from_slice(&triangle);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Add horizontal disc at `y` facing `+Y` if `facing` is `1` or `-Y` if it is `-1`.
+    fn disc(&mut self, radius: f32, y: f32, facing: f32, sectors: u32, rings: u32) {
+        let sectors = sectors.max(3);
+        let rings = rings.max(1);
+        self.grid(sectors, rings, |column, row| {
+            let distance = radius * row as f32 / rings as f32;
+            let (sin_phi, cos_phi) = (2.0 * PI * column as f32 / sectors as f32).sin_cos();
+            let x = distance * sin_phi;
+            let z = facing * distance * cos_phi;
+            vertex(
+                [x, y, z],
+                [0.0, facing, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.5 + x / (2.0 * radius), 0.5 + facing * z / (2.0 * radius)],
+            )
+        });
+    }
+
+    fn build(self) -> MeshBuilder<'static> {
+        let mut builder = MeshBuilder::new();
+        builder
+            .push_vertices(self.vertices)
+            .set_indices(narrow_indices(self.indices));
+        builder
+    }
+}
+
+fn vertex(
+    position: [f32; 3],
+    normal: [f32; 3],
+    tangent: [f32; 3],
+    tex_coord: [f32; 2],
+) -> PosNormTangTex {
+    PosNormTangTex {
+        position: position.into(),
+        normal: normal.into(),
+        tangent: [tangent[0], tangent[1], tangent[2], 1.0].into(),
+        tex_coord: tex_coord.into(),
+    }
+}
+
+/// Vertex on sphere with polar angle `theta` measured from `+Y`
+/// and azimuth `2π u` measured from `+Z` towards `+X`.
+fn sphere_vertex(
+    center: [f32; 3],
+    radius: f32,
+    sin_theta: f32,
+    cos_theta: f32,
+    u: f32,
+    v: f32,
+) -> PosNormTangTex {
+    let (sin_phi, cos_phi) = (2.0 * PI * u).sin_cos();
+    let normal = [sin_theta * sin_phi, cos_theta, sin_theta * cos_phi];
+    vertex(
+        add(center, scale(normal, radius)),
+        normal,
+        [cos_phi, 0.0, -sin_phi],
+        [u, v],
+    )
+}
+
+/// Sine and cosine of `angle * step / steps`.
+/// Values close to zero are snapped so that vertices at the poles coincide.
+fn polar(step: u32, steps: u32, angle: f32) -> (f32, f32) {
+    let snap = |value: f32| if value.abs() < 1e-6 { 0.0 } else { value };
+    let (sin, cos) = (angle * step as f32 / steps as f32).sin_cos();
+    (snap(sin), snap(cos))
+}
+
+/// Vertices at poles get `u` in the middle of their sector.
+fn pole_u(column: u32, columns: u32, radius: f32) -> f32 {
+    if radius == 0.0 {
+        (column as f32 + 0.5).min(columns as f32) / columns as f32
+    } else {
+        column as f32 / columns as f32
+    }
+}
+
+fn abs(a: [f32; 3]) -> [f32; 3] {
+    [a[0].abs(), a[1].abs(), a[2].abs()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use math::{cross, sub};
+    use mesh::IndexWidth;
+    use vertex::{Normal, Position, Tangent, TexCoord};
+
+    /// Small tessellations of every shape.
+    fn shapes() -> Vec<(&'static str, MeshBuilder<'static>)> {
+        vec![
+            ("cuboid", cuboid([1.0, 2.0, 3.0], 2)),
+            ("uv_sphere", uv_sphere(1.5, 8, 6)),
+            ("icosphere", icosphere(1.5, 2)),
+            ("plane", plane([2.0, 3.0], [2, 3])),
+            ("disc", disc(1.5, 8, 2)),
+            ("cylinder", cylinder(1.5, 2.0, 8, 2, true)),
+            ("cone", cone(1.5, 2.0, 8, 2, true)),
+            ("torus", torus(2.0, 0.5, 8, 6)),
+            ("capsule", capsule(0.5, 1.0, 8, 3)),
+        ]
+    }
+
+    fn vertices(builder: &MeshBuilder) -> Vec<PosNormTangTex> {
+        let positions = builder.attribute::<Position>().unwrap();
+        let normals = builder.attribute::<Normal>().unwrap();
+        let tangents = builder.attribute::<Tangent>().unwrap();
+        let tex_coords = builder.attribute::<TexCoord>().unwrap();
+        positions
+            .zip(normals)
+            .zip(tangents.zip(tex_coords))
+            .map(
+                |((position, normal), (tangent, tex_coord))| PosNormTangTex {
+                    position,
+                    normal,
+                    tangent,
+                    tex_coord,
+                },
+            )
+            .collect()
+    }
+
+    fn triangles(builder: &MeshBuilder) -> Vec<[usize; 3]> {
+        let indices = builder.indices().unwrap().collect::<Vec<_>>();
+        assert_eq!(indices.len() % 3, 0);
+        indices
+            .chunks(3)
+            .map(|t| [t[0] as usize, t[1] as usize, t[2] as usize])
+            .collect()
+    }
+
+    fn xyz(tangent: Tangent) -> [f32; 3] {
+        [tangent.0[0], tangent.0[1], tangent.0[2]]
+    }
+
+    #[test]
+    fn counter_clockwise() {
+        for (name, builder) in shapes() {
+            let vertices = vertices(&builder);
+            for triangle in triangles(&builder) {
+                let [a, b, c] = triangle.map(|i| vertices[i].position.0);
+                let normal = cross(sub(b, a), sub(c, a));
+                // Triangles at poles and apexes are not degenerate.
+                assert!(dot(normal, normal) > 1e-8, "{} {:?}", name, triangle);
+                for &i in &triangle {
+                    assert!(
+                        dot(normal, vertices[i].normal.0) > 0.0,
+                        "{} {:?}",
+                        name,
+                        triangle
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tangent_frames() {
+        for (name, builder) in shapes() {
+            for vertex in vertices(&builder) {
+                let normal = vertex.normal.0;
+                let tangent = xyz(vertex.tangent);
+                assert!((dot(normal, normal) - 1.0).abs() < 1e-5, "{}", name);
+                assert!((dot(tangent, tangent) - 1.0).abs() < 1e-5, "{}", name);
+                assert!(dot(normal, tangent).abs() < 1e-5, "{}", name);
+                assert_eq!(vertex.tangent.0[3], 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn tangents_along_u() {
+        for (name, builder) in shapes() {
+            let vertices = vertices(&builder);
+            for triangle in triangles(&builder) {
+                let [a, b, c] = triangle.map(|i| vertices[i]);
+                let (e1, e2) = (
+                    sub(b.position.0, a.position.0),
+                    sub(c.position.0, a.position.0),
+                );
+                let (du1, dv1) = (
+                    b.tex_coord.0[0] - a.tex_coord.0[0],
+                    b.tex_coord.0[1] - a.tex_coord.0[1],
+                );
+                let (du2, dv2) = (
+                    c.tex_coord.0[0] - a.tex_coord.0[0],
+                    c.tex_coord.0[1] - a.tex_coord.0[1],
+                );
+                // Direction of increasing `u` within the triangle.
+                let du = scale(sub(scale(e1, dv2), scale(e2, dv1)), du1 * dv2 - du2 * dv1);
+                for &vertex in &[a, b, c] {
+                    assert!(
+                        dot(du, xyz(vertex.tangent)) > 0.0,
+                        "{} {:?}",
+                        name,
+                        triangle
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn seams() {
+        let shapes = vec![
+            ("uv_sphere", uv_sphere(1.5, 8, 6)),
+            ("cylinder", cylinder(1.5, 2.0, 8, 2, false)),
+            ("cone", cone(1.5, 2.0, 8, 2, false)),
+            ("torus", torus(2.0, 0.5, 8, 6)),
+            ("capsule", capsule(0.5, 1.0, 8, 3)),
+        ];
+        for (name, builder) in shapes {
+            let vertices = vertices(&builder);
+            let seam = |u: f32| {
+                let mut positions = vertices
+                    .iter()
+                    .filter(|vertex| vertex.tex_coord.0[0] == u)
+                    .map(|vertex| vertex.position.0)
+                    .collect::<Vec<_>>();
+                positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                positions
+            };
+            let start = seam(0.0);
+            assert!(!start.is_empty(), "{}", name);
+            // Vertices at poles are not on the seam and have `u` in the middle of their sectors,
+            // but the last one has `u` of `1`.
+            let end = seam(1.0);
+            for a in &start {
+                assert!(
+                    end.iter().any(|b| dot(sub(*a, *b), sub(*a, *b)) < 1e-10),
+                    "{} {:?}",
+                    name,
+                    a
+                );
+            }
+        }
+
+        // Torus has seam around the tube too.
+        let vertices = vertices(&torus(2.0, 0.5, 8, 6));
+        let count = |v: f32| {
+            vertices
+                .iter()
+                .filter(|vertex| vertex.tex_coord.0[1] == v)
+                .count()
+        };
+        assert_eq!(count(0.0), 9);
+        assert_eq!(count(1.0), 9);
+    }
+
+    #[test]
+    fn pole_tex_coords() {
+        let shapes = vec![
+            ("uv_sphere", uv_sphere(1.5, 8, 6)),
+            ("cone", cone(1.5, 2.0, 8, 2, false)),
+            ("capsule", capsule(0.5, 1.0, 8, 3)),
+        ];
+        for (name, builder) in shapes {
+            let vertices = vertices(&builder);
+            let mut poles = 0;
+            for triangle in triangles(&builder) {
+                let is_pole = |i: usize| {
+                    let p = vertices[i].position.0;
+                    p[0] == 0.0 && p[2] == 0.0
+                };
+                let (pole, others): (Vec<_>, Vec<_>) = triangle.iter().partition(|&&i| is_pole(i));
+                if let [pole] = pole[..] {
+                    // Pole vertex of the triangle has `u` in the middle of its sector.
+                    let u = |i: usize| vertices[i].tex_coord.0[0];
+                    let middle = (u(others[0]) + u(others[1])) / 2.0;
+                    assert!((u(pole) - middle).abs() < 1e-6, "{} {:?}", name, triangle);
+                    poles += 1;
+                }
+            }
+            assert!(poles >= 8, "{}", name);
+        }
+    }
+
+    #[test]
+    fn index_width() {
+        for (name, builder) in shapes() {
+            assert_eq!(
+                builder.indices.as_ref().unwrap().1,
+                IndexWidth::U16,
+                "{}",
+                name
+            );
+        }
+        let builder = plane([1.0, 1.0], [256, 256]);
+        assert_eq!(builder.indices.as_ref().unwrap().1, IndexWidth::U32);
+    }
+
+    #[test]
+    fn generated_tangents() {
+        let shapes = vec![
+            ("uv_sphere", uv_sphere(1.5, 8, 6)),
+            ("cylinder", cylinder(1.5, 2.0, 8, 2, false)),
+            ("torus", torus(2.0, 0.5, 8, 6)),
+        ];
+        for (name, mut builder) in shapes {
+            let expected = vertices(&builder);
+            builder
+                .attribute_mut::<Tangent>()
+                .unwrap()
+                .update(|_| Tangent([0.0; 4]));
+            builder.set_tangents().unwrap();
+            let generated = vertices(&builder);
+            assert_eq!(generated.len(), expected.len(), "{}", name);
+            for (generated, expected) in generated.iter().zip(&expected) {
+                let (a, b) = (generated.tangent.0, expected.tangent.0);
+                let u = expected.tex_coord.0[0];
+                if u == 0.0 || u == 1.0 {
+                    // Seam vertices get tangents of faces on one side.
+                    assert!(
+                        dot(xyz(generated.tangent), xyz(expected.tangent)) > 0.9,
+                        "{}",
+                        name
+                    );
+                } else {
+                    assert!(
+                        (0..4).all(|i| (a[i] - b[i]).abs() < 1e-4),
+                        "{} {:?} {:?}",
+                        name,
+                        a,
+                        b
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//!
+//! Validation of skinning attributes in `MeshBuilder`.
+//!
+
+use std::borrow::Cow;
+
+use hal::format::Format;
+
+use mesh::{MeshBuilder, MeshBuilderError};
+use pack::from_unorm;
+use utils::read_pod;
+use vertex::{indexed_name, Attribute, JointIndices, JointWeights};
+
+/// Maximum deviation of joint weights sum from `1.0` for float weights.
+const WEIGHTS_EPSILON: f32 = 1e-3;
+
+/// Error returned by `MeshBuilder::validate_skinning`.
+#[derive(Clone, Debug, Fail, PartialEq)]
+pub enum SkinningError {
+    /// Skinning attribute is not found in per-vertex buffers.
+    #[fail(display = "Attribute \"{}\" not found", name)]
+    MissingAttribute {
+        /// Name of the attribute.
+        name: Cow<'static, str>,
+    },
+
+    /// Skinning attribute has format that can't be validated.
+    #[fail(display = "Attribute \"{}\" has unsupported format {:?}", name, format)]
+    UnsupportedFormat {
+        /// Name of the attribute.
+        name: Cow<'static, str>,
+        /// Format of the attribute.
+        format: Format,
+    },
+
+    /// Vertex data of the builder is malformed.
+    #[fail(display = "Vertex data is invalid: {}", _0)]
+    InvalidVertices(#[cause] MeshBuilderError),
+
+    /// Joint weights of the vertex don't sum up to `1.0`.
+    #[fail(display = "Joint weights of vertex {} sum up to {}", vertex, sum)]
+    NotNormalized {
+        /// Index of the vertex.
+        vertex: usize,
+        /// Sum of the weights.
+        sum: f32,
+    },
+
+    /// Vertex is influenced by joint with index out of range.
+    #[fail(
+        display = "Vertex {} is influenced by joint {} while there are {} joints",
+        vertex, joint, joint_count
+    )]
+    JointOutOfRange {
+        /// Index of the vertex.
+        vertex: usize,
+        /// Index of the joint.
+        joint: u32,
+        /// Number of joints.
+        joint_count: u32,
+    },
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Check that joint weights of every vertex sum up to `1.0`
+    /// and that every joint with non-zero weight is less than `joint_count`.
+    /// All sets of joint indices and weights (`JointIndices1` and `JointWeights1` etc.)
+    /// are taken into account.
+    pub fn validate_skinning(&self, joint_count: u32) -> Result<(), SkinningError> {
+        // Formats must be valid and all per-vertex buffers must have same number of vertices.
+        self.validate().map_err(SkinningError::InvalidVertices)?;
+        let count = self
+            .vertex_count()
+            .map_err(SkinningError::InvalidVertices)?
+            .unwrap_or(0) as usize;
+
+        let mut sets = Vec::new();
+        for set in 0.. {
+            let indices_name = indexed_name(JointIndices::NAME, set);
+            let weights_name = indexed_name(JointWeights::NAME, set);
+            match (
+                self.find_attribute(&indices_name),
+                self.find_attribute(&weights_name),
+            ) {
+                (Some(indices), Some(weights)) => {
+                    sets.push((indices_name, indices, weights_name, weights))
+                }
+                (None, None) if set > 0 => break,
+                (None, _) => {
+                    return Err(SkinningError::MissingAttribute { name: indices_name });
+                }
+                (_, None) => {
+                    return Err(SkinningError::MissingAttribute { name: weights_name });
+                }
+            }
+        }
+
+        for vertex in 0..count {
+            let mut sum = 0.0;
+            let mut epsilon = 0.0f32;
+            for &(ref indices_name, indices, ref weights_name, weights) in &sets {
+                let (indices, indices_format, indices_attribute) = indices;
+                let offset = vertex * indices_format.stride as usize
+                    + indices_attribute.element.offset as usize;
+                let joints = read_joint_indices(indices, offset, indices_attribute.element.format)
+                    .map_err(|format| SkinningError::UnsupportedFormat {
+                        name: indices_name.clone(),
+                        format,
+                    })?;
+
+                let (weights, weights_format, weights_attribute) = weights;
+                let offset = vertex * weights_format.stride as usize
+                    + weights_attribute.element.offset as usize;
+                let (weights, set_epsilon) =
+                    read_joint_weights(weights, offset, weights_attribute.element.format).map_err(
+                        |format| SkinningError::UnsupportedFormat {
+                            name: weights_name.clone(),
+                            format,
+                        },
+                    )?;
+
+                sum += weights.iter().sum::<f32>();
+                epsilon = epsilon.max(set_epsilon);
+
+                for (&joint, &weight) in joints.iter().zip(weights.iter()) {
+                    if weight != 0.0 && joint >= joint_count {
+                        return Err(SkinningError::JointOutOfRange {
+                            vertex,
+                            joint,
+                            joint_count,
+                        });
+                    }
+                }
+            }
+
+            if (sum - 1.0).abs() > epsilon {
+                return Err(SkinningError::NotNormalized { vertex, sum });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Read joint indices of the vertex.
+/// Returns the format back if it's not supported.
+fn read_joint_indices(bytes: &[u8], offset: usize, format: Format) -> Result<[u32; 4], Format> {
+    match format {
+        Format::Rgba8Uint => {
+            let i: [u8; 4] = read_pod(bytes, offset);
+            Ok([i[0] as u32, i[1] as u32, i[2] as u32, i[3] as u32])
+        }
+        Format::Rgba16Uint => {
+            let i: [u16; 4] = read_pod(bytes, offset);
+            Ok([i[0] as u32, i[1] as u32, i[2] as u32, i[3] as u32])
+        }
+        Format::Rgba32Uint => Ok(read_pod(bytes, offset)),
+        format => Err(format),
+    }
+}
+
+/// Read joint weights of the vertex.
+/// Returns weights and allowed deviation of their sum from `1.0`
+/// or the format back if it's not supported.
+fn read_joint_weights(
+    bytes: &[u8],
+    offset: usize,
+    format: Format,
+) -> Result<([f32; 4], f32), Format> {
+    let unorm = |w: [u32; 4], bits| {
+        (
+            [
+                from_unorm(w[0], bits),
+                from_unorm(w[1], bits),
+                from_unorm(w[2], bits),
+                from_unorm(w[3], bits),
+            ],
+            // Allow rounding error of couple of quanta.
+            WEIGHTS_EPSILON + 2.0 * from_unorm(1, bits),
+        )
+    };
+
+    match format {
+        Format::Rgba32Float => Ok((read_pod(bytes, offset), WEIGHTS_EPSILON)),
+        Format::Rgba8Unorm => {
+            let w: [u8; 4] = read_pod(bytes, offset);
+            Ok(unorm(
+                [w[0] as u32, w[1] as u32, w[2] as u32, w[3] as u32],
+                8,
+            ))
+        }
+        Format::Rgba16Unorm => {
+            let w: [u16; 4] = read_pod(bytes, offset);
+            Ok(unorm(
+                [w[0] as u32, w[1] as u32, w[2] as u32, w[3] as u32],
+                16,
+            ))
+        }
+        format => Err(format),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vertex::{
+        AsVertexFormat, JointIndicesU8, JointWeightsUnorm8, PosNormTexSkin, VertexFormat,
+        VertexFormatError,
+    };
+
+    fn vertex(joints: [u16; 4], weights: [f32; 4]) -> PosNormTexSkin {
+        PosNormTexSkin {
+            position: [0.0; 3].into(),
+            normal: [0.0, 0.0, 1.0].into(),
+            tex_coord: [0.0; 2].into(),
+            joint_indices: JointIndices(joints),
+            joint_weights: JointWeights(weights),
+        }
+    }
+
+    #[test]
+    fn valid() {
+        let builder = MeshBuilder::new()
+            .with_vertices(vec![
+                vertex([0, 1, 0, 0], [0.5, 0.5, 0.0, 0.0]),
+                vertex([2, 0, 0, 0], [1.0, 0.0, 0.0, 0.0]),
+            ])
+            .unwrap();
+        assert_eq!(builder.validate_skinning(3), Ok(()));
+        assert_eq!(
+            builder.validate_skinning(2),
+            Err(SkinningError::JointOutOfRange {
+                vertex: 1,
+                joint: 2,
+                joint_count: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn not_normalized() {
+        let builder = MeshBuilder::new()
+            .with_vertices(vec![vertex([0, 1, 0, 0], [0.5, 0.25, 0.0, 0.0])])
+            .unwrap();
+        assert_eq!(
+            builder.validate_skinning(2),
+            Err(SkinningError::NotNormalized {
+                vertex: 0,
+                sum: 0.75,
+            })
+        );
+    }
+
+    #[test]
+    fn packed_weights() {
+        let builder = MeshBuilder::new()
+            .with_vertices(vec![JointIndicesU8([0, 1, 2, 3])])
+            .unwrap()
+            .with_vertices(vec![JointWeightsUnorm8([85, 85, 85, 0])])
+            .unwrap();
+        assert_eq!(builder.validate_skinning(4), Ok(()));
+    }
+
+    #[test]
+    fn missing_weights() {
+        let builder = MeshBuilder::new()
+            .with_vertices(vec![JointIndices([0; 4])])
+            .unwrap();
+        assert_eq!(
+            builder.validate_skinning(1),
+            Err(SkinningError::MissingAttribute {
+                name: Cow::Borrowed("joint_weights"),
+            })
+        );
+    }
+
+    #[test]
+    fn zero_stride() {
+        let mut builder = MeshBuilder::new()
+            .with_vertices(vec![vertex([0; 4], [1.0, 0.0, 0.0, 0.0])])
+            .unwrap();
+        builder.vertices[0].1 = VertexFormat {
+            stride: 0,
+            ..PosNormTexSkin::VERTEX_FORMAT
+        };
+        assert_eq!(
+            builder.validate_skinning(1),
+            Err(SkinningError::InvalidVertices(
+                MeshBuilderError::InvalidFormat {
+                    index: 0,
+                    error: VertexFormatError::ZeroStride,
+                }
+            ))
+        );
+    }
+}
@@ -0,0 +1,862 @@
+//!
+//! Reflection of vertex shader inputs from SPIR-V modules.
+//!
+//! Only the parts of SPIR-V required to find vertex inputs are parsed:
+//! entry points, names, decorations, numeric types and input variables.
+//!
+
+use std::collections::{HashMap, HashSet};
+
+use hal::format::Format;
+use hal::Backend;
+
+use convert::{element_layout, ChannelKind};
+use mesh::Mesh;
+use vertex::{AsVertexFormat, DynamicAttribute, VertexFormat};
+
+const MAGIC: u32 = 0x0723_0203;
+const HEADER_SIZE: usize = 5;
+
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_VECTOR: u32 = 23;
+const OP_TYPE_MATRIX: u32 = 24;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_CONSTANT: u32 = 43;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+
+/// Maximum number of locations occupied by single input.
+const MAX_LOCATIONS: u32 = 64;
+
+const EXECUTION_MODEL_VERTEX: u32 = 0;
+const STORAGE_CLASS_INPUT: u32 = 1;
+const DECORATION_BUILT_IN: u32 = 11;
+const DECORATION_LOCATION: u32 = 30;
+
+/// Error returned when SPIR-V module can't be parsed.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum SpirvError {
+    /// Module doesn't start with SPIR-V header.
+    #[fail(display = "Invalid SPIR-V header")]
+    InvalidHeader,
+
+    /// Instruction is cut off or malformed.
+    #[fail(display = "Malformed SPIR-V instruction at word {}", offset)]
+    Malformed {
+        /// Offset of the instruction in words.
+        offset: usize,
+    },
+
+    /// Module has no vertex entry point with specified name.
+    #[fail(display = "Vertex entry point \"{}\" not found", name)]
+    EntryPointNotFound {
+        /// Name of the entry point.
+        name: String,
+    },
+
+    /// Input variable has no `Location` decoration.
+    #[fail(display = "Input \"{}\" has no location", name)]
+    MissingLocation {
+        /// Name of the input.
+        name: String,
+    },
+
+    /// Input variable has type that can't be fed from vertex buffer,
+    /// refers to itself or occupies more than 64 locations.
+    #[fail(display = "Input \"{}\" has unsupported type", name)]
+    UnsupportedType {
+        /// Name of the input.
+        name: String,
+    },
+}
+
+/// Error returned when vertex buffers don't satisfy shader inputs.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum VertexInputError {
+    /// No vertex buffer has attribute with the input's name.
+    #[fail(
+        display = "Attribute \"{}\" required at location {} is missing",
+        name, location
+    )]
+    MissingAttribute {
+        /// Location of the input.
+        location: u32,
+        /// Name of the input.
+        name: String,
+    },
+
+    /// Attribute can't be read by the input.
+    #[fail(
+        display = "Attribute \"{}\" at location {} has format {:?} while shader expects {:?}",
+        name, location, found, expected
+    )]
+    TypeMismatch {
+        /// Location of the input.
+        location: u32,
+        /// Name of the input.
+        name: String,
+        /// Format matching the input's type.
+        expected: Format,
+        /// Format of the attribute.
+        found: Format,
+    },
+
+    /// Two inputs occupy the same location.
+    /// 64-bit three- and four-component inputs occupy two locations each.
+    #[fail(
+        display = "Inputs \"{}\" and \"{}\" both occupy location {}",
+        first, second, location
+    )]
+    LocationClash {
+        /// Location of the inputs.
+        location: u32,
+        /// Name of the first input.
+        first: String,
+        /// Name of the second input.
+        second: String,
+    },
+}
+
+/// Single vertex input location of a shader.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexInput {
+    /// Location of the input.
+    pub location: u32,
+
+    /// Name of the input.
+    /// Matrices and arrays occupy several locations
+    /// which are named as attribute sets (`model`, `model_1` etc.).
+    /// Unnamed inputs are named `location_N`.
+    pub name: String,
+
+    /// Format matching the input's type.
+    pub format: Format,
+}
+
+/// Vertex inputs of a shader entry point sorted by location.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VertexInputs {
+    inputs: Vec<VertexInput>,
+}
+
+impl VertexInputs {
+    /// Parse inputs of vertex entry point with specified name from SPIR-V module.
+    /// Module may be in either byte order.
+    pub fn parse(spirv: &[u8], entry_point: &str) -> Result<Self, SpirvError> {
+        if spirv.len() % 4 != 0 {
+            return Err(SpirvError::InvalidHeader);
+        }
+        let mut words = spirv
+            .chunks(4)
+            .map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+            .collect::<Vec<_>>();
+        if words.first().map(|w| w.swap_bytes()) == Some(MAGIC) {
+            for word in &mut words {
+                *word = word.swap_bytes();
+            }
+        }
+        Self::parse_words(&words, entry_point)
+    }
+
+    /// Parse inputs of vertex entry point with specified name from SPIR-V module words.
+    pub fn parse_words(words: &[u32], entry_point: &str) -> Result<Self, SpirvError> {
+        if words.len() < HEADER_SIZE || words[0] != MAGIC {
+            return Err(SpirvError::InvalidHeader);
+        }
+
+        let mut names = HashMap::new();
+        let mut locations = HashMap::new();
+        let mut built_ins = HashSet::new();
+        let mut types = HashMap::new();
+        let mut constants = HashMap::new();
+        let mut variables = HashMap::new();
+        let mut interface = None;
+
+        let mut offset = HEADER_SIZE;
+        while offset < words.len() {
+            let count = (words[offset] >> 16) as usize;
+            let opcode = words[offset] & 0xffff;
+            if count == 0 || offset + count > words.len() {
+                return Err(SpirvError::Malformed { offset });
+            }
+            let operands = &words[offset + 1..offset + count];
+            let malformed = SpirvError::Malformed { offset };
+            offset += count;
+
+            let operand = |index: usize| operands.get(index).cloned().ok_or(malformed.clone());
+
+            match opcode {
+                OP_NAME => {
+                    names.insert(operand(0)?, parse_string(&operands[1..]).0);
+                }
+                OP_ENTRY_POINT => {
+                    operand(1)?;
+                    let (name, size) = parse_string(&operands[2..]);
+                    if operand(0)? == EXECUTION_MODEL_VERTEX && name == entry_point {
+                        interface = Some(operands[2 + size..].to_vec());
+                    }
+                }
+                OP_DECORATE => match operand(1)? {
+                    DECORATION_LOCATION => {
+                        locations.insert(operand(0)?, operand(2)?);
+                    }
+                    DECORATION_BUILT_IN => {
+                        built_ins.insert(operand(0)?);
+                    }
+                    _ => {}
+                },
+                OP_TYPE_INT => {
+                    let kind = if operand(2)? != 0 {
+                        ScalarKind::Int
+                    } else {
+                        ScalarKind::Uint
+                    };
+                    types.insert(operand(0)?, Type::Scalar(kind, operand(1)?));
+                }
+                OP_TYPE_FLOAT => {
+                    types.insert(operand(0)?, Type::Scalar(ScalarKind::Float, operand(1)?));
+                }
+                OP_TYPE_VECTOR => {
+                    types.insert(operand(0)?, Type::Vector(operand(1)?, operand(2)?));
+                }
+                OP_TYPE_MATRIX => {
+                    types.insert(operand(0)?, Type::Matrix(operand(1)?, operand(2)?));
+                }
+                OP_TYPE_ARRAY => {
+                    types.insert(operand(0)?, Type::Array(operand(1)?, operand(2)?));
+                }
+                OP_TYPE_POINTER => {
+                    types.insert(operand(0)?, Type::Pointer(operand(2)?));
+                }
+                OP_CONSTANT => {
+                    constants.insert(operand(1)?, operand(2)?);
+                }
+                OP_VARIABLE if operand(2)? == STORAGE_CLASS_INPUT => {
+                    variables.insert(operand(1)?, operand(0)?);
+                }
+                _ => {}
+            }
+        }
+
+        let interface = interface.ok_or_else(|| SpirvError::EntryPointNotFound {
+            name: entry_point.to_string(),
+        })?;
+
+        let mut inputs = Vec::new();
+        for id in interface {
+            let pointer = match variables.get(&id) {
+                Some(pointer) if !built_ins.contains(&id) => pointer,
+                _ => continue,
+            };
+            let name = names
+                .get(&id)
+                .cloned()
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| match locations.get(&id) {
+                    Some(location) => format!("location_{}", location),
+                    None => format!("%{}", id),
+                });
+            let location = *locations
+                .get(&id)
+                .ok_or_else(|| SpirvError::MissingLocation { name: name.clone() })?;
+
+            let unsupported = || SpirvError::UnsupportedType { name: name.clone() };
+            let ty = match types.get(pointer) {
+                Some(&Type::Pointer(ty)) => ty,
+                _ => return Err(unsupported()),
+            };
+            let mut formats = Vec::new();
+            collect_formats(&types, &constants, ty, &mut Vec::new(), &mut formats)
+                .ok_or_else(unsupported)?;
+
+            let mut next = location;
+            for (index, (format, size)) in formats.into_iter().enumerate() {
+                // Locations past the input must be representable too.
+                let end = next.checked_add(size).ok_or_else(unsupported)?;
+                inputs.push(VertexInput {
+                    location: next,
+                    name: if index == 0 {
+                        name.clone()
+                    } else {
+                        format!("{}_{}", name, index)
+                    },
+                    format,
+                });
+                next = end;
+            }
+        }
+
+        inputs.sort_by_key(|input| input.location);
+        Ok(VertexInputs { inputs })
+    }
+
+    /// Get inputs sorted by location.
+    pub fn inputs(&self) -> &[VertexInput] {
+        &self.inputs
+    }
+
+    /// Rename input at specified location.
+    /// Useful for stripped shaders and for inputs named differently than attributes.
+    pub fn set_name<N: Into<String>>(&mut self, location: u32, name: N) {
+        let name = name.into();
+        for input in &mut self.inputs {
+            if input.location == location {
+                input.name = name.clone();
+            }
+        }
+    }
+
+    /// Names and locations of the inputs.
+    /// Can be used as `Locations::Explicit` to describe pipeline vertex input.
+    pub fn locations(&self) -> Vec<(&str, u32)> {
+        self.inputs
+            .iter()
+            .map(|input| (&*input.name, input.location))
+            .collect()
+    }
+
+    /// Build sorted vertex formats that satisfy the inputs.
+    /// Each input gets separate per-vertex buffer with format matching its type.
+    pub fn vertex_formats(&self) -> Vec<VertexFormat<'static>> {
+        let mut formats = self
+            .inputs
+            .iter()
+            .map(|input| {
+                VertexFormat::from_attributes(Some(DynamicAttribute::new(
+                    input.name.clone(),
+                    input.format,
+                )))
+            })
+            .collect::<Vec<_>>();
+        formats.sort();
+        formats
+    }
+
+    /// Check that vertex format provides all inputs.
+    pub fn check_vertex_format<V>(&self) -> Result<(), VertexInputError>
+    where
+        V: AsVertexFormat,
+    {
+        self.check_formats(&[V::VERTEX_FORMAT])
+    }
+
+    /// Check that buffers of the mesh provide all inputs.
+    pub fn check_mesh<B>(&self, mesh: &Mesh<B>) -> Result<(), VertexInputError>
+    where
+        B: Backend,
+    {
+        self.check(mesh.vertex_formats())
+    }
+
+    /// Check that vertex formats provide all inputs.
+    /// Inputs are matched with attributes by name.
+    /// Attribute must have same numeric type (float, signed or unsigned integer)
+    /// as the input. Missing components are filled by the pipeline.
+    pub fn check_formats(&self, formats: &[VertexFormat]) -> Result<(), VertexInputError> {
+        self.check(formats.iter())
+    }
+
+    fn check<'b, 'c: 'b, I>(&self, formats: I) -> Result<(), VertexInputError>
+    where
+        I: Iterator<Item = &'b VertexFormat<'c>> + Clone,
+    {
+        let mut occupied = HashMap::new();
+        for input in &self.inputs {
+            // `parse` rejects inputs whose locations overflow.
+            let end = input
+                .location
+                .checked_add(location_count(input.format))
+                .expect("Input locations overflow");
+            for location in input.location..end {
+                if let Some(first) = occupied.insert(location, &input.name) {
+                    return Err(VertexInputError::LocationClash {
+                        location,
+                        first: first.clone(),
+                        second: input.name.clone(),
+                    });
+                }
+            }
+        }
+
+        for input in &self.inputs {
+            let attribute = formats
+                .clone()
+                .flat_map(|format| format.attributes.iter())
+                .find(|attribute| attribute.name == *input.name)
+                .ok_or_else(|| VertexInputError::MissingAttribute {
+                    location: input.location,
+                    name: input.name.clone(),
+                })?;
+
+            let found = attribute.element.format;
+            let compatible = found == input.format
+                || match (numeric_kind(found), numeric_kind(input.format)) {
+                    (Some(found), Some(expected)) => found == expected,
+                    _ => false,
+                };
+            if !compatible {
+                return Err(VertexInputError::TypeMismatch {
+                    location: input.location,
+                    name: input.name.clone(),
+                    expected: input.format,
+                    found,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScalarKind {
+    Float,
+    Int,
+    Uint,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Type {
+    /// Kind and width.
+    Scalar(ScalarKind, u32),
+    /// Component type and count.
+    Vector(u32, u32),
+    /// Column type and count.
+    Matrix(u32, u32),
+    /// Element type and length constant.
+    Array(u32, u32),
+    /// Pointee type.
+    Pointer(u32),
+}
+
+/// Collect formats and number of occupied locations for each location-sized part of the type.
+/// `parents` are the types being collected, which the type must not refer to.
+/// Fails if the type occupies more than `MAX_LOCATIONS` locations.
+fn collect_formats(
+    types: &HashMap<u32, Type>,
+    constants: &HashMap<u32, u32>,
+    ty: u32,
+    parents: &mut Vec<u32>,
+    formats: &mut Vec<(Format, u32)>,
+) -> Option<()> {
+    if parents.contains(&ty) {
+        return None;
+    }
+    let (element, count) = match *types.get(&ty)? {
+        Type::Scalar(kind, width) => return push_format(formats, vector_format(kind, width, 1)?),
+        Type::Vector(component, count) => match *types.get(&component)? {
+            Type::Scalar(kind, width) => {
+                return push_format(formats, vector_format(kind, width, count)?)
+            }
+            _ => return None,
+        },
+        Type::Matrix(column, count) => (column, count),
+        Type::Array(element, length) => (element, *constants.get(&length)?),
+        Type::Pointer(_) => return None,
+    };
+    if count > MAX_LOCATIONS {
+        return None;
+    }
+
+    parents.push(ty);
+    for _ in 0..count {
+        collect_formats(types, constants, element, parents, formats)?;
+    }
+    parents.pop();
+    Some(())
+}
+
+/// Push format unless total number of locations exceeds `MAX_LOCATIONS`.
+fn push_format(formats: &mut Vec<(Format, u32)>, format: (Format, u32)) -> Option<()> {
+    formats.push(format);
+    let locations = formats.iter().map(|&(_, size)| size).sum::<u32>();
+    if locations > MAX_LOCATIONS {
+        None
+    } else {
+        Some(())
+    }
+}
+
+/// Get format of the vector and number of locations it occupies.
+fn vector_format(kind: ScalarKind, width: u32, count: u32) -> Option<(Format, u32)> {
+    use self::ScalarKind::*;
+
+    let format = match (kind, width, count) {
+        (Float, 16, 1) => Format::R16Float,
+        (Float, 16, 2) => Format::Rg16Float,
+        (Float, 16, 3) => Format::Rgb16Float,
+        (Float, 16, 4) => Format::Rgba16Float,
+        (Float, 32, 1) => Format::R32Float,
+        (Float, 32, 2) => Format::Rg32Float,
+        (Float, 32, 3) => Format::Rgb32Float,
+        (Float, 32, 4) => Format::Rgba32Float,
+        (Float, 64, 1) => Format::R64Float,
+        (Float, 64, 2) => Format::Rg64Float,
+        (Float, 64, 3) => Format::Rgb64Float,
+        (Float, 64, 4) => Format::Rgba64Float,
+        (Int, 8, 1) => Format::R8Int,
+        (Int, 8, 2) => Format::Rg8Int,
+        (Int, 8, 3) => Format::Rgb8Int,
+        (Int, 8, 4) => Format::Rgba8Int,
+        (Int, 16, 1) => Format::R16Int,
+        (Int, 16, 2) => Format::Rg16Int,
+        (Int, 16, 3) => Format::Rgb16Int,
+        (Int, 16, 4) => Format::Rgba16Int,
+        (Int, 32, 1) => Format::R32Int,
+        (Int, 32, 2) => Format::Rg32Int,
+        (Int, 32, 3) => Format::Rgb32Int,
+        (Int, 32, 4) => Format::Rgba32Int,
+        (Uint, 8, 1) => Format::R8Uint,
+        (Uint, 8, 2) => Format::Rg8Uint,
+        (Uint, 8, 3) => Format::Rgb8Uint,
+        (Uint, 8, 4) => Format::Rgba8Uint,
+        (Uint, 16, 1) => Format::R16Uint,
+        (Uint, 16, 2) => Format::Rg16Uint,
+        (Uint, 16, 3) => Format::Rgb16Uint,
+        (Uint, 16, 4) => Format::Rgba16Uint,
+        (Uint, 32, 1) => Format::R32Uint,
+        (Uint, 32, 2) => Format::Rg32Uint,
+        (Uint, 32, 3) => Format::Rgb32Uint,
+        (Uint, 32, 4) => Format::Rgba32Uint,
+        _ => return None,
+    };
+
+    Some((format, location_count(format)))
+}
+
+/// Number of locations occupied by input of the format.
+/// 64-bit three- and four-component vectors occupy two locations.
+fn location_count(format: Format) -> u32 {
+    match format {
+        Format::Rgb64Float | Format::Rgba64Float => 2,
+        _ => 1,
+    }
+}
+
+/// Numeric type the format is read as in shader.
+/// 64-bit formats and formats unknown to the converter return `None`.
+fn numeric_kind(format: Format) -> Option<ScalarKind> {
+    element_layout(format).map(|layout| match layout.kind {
+        ChannelKind::Unorm | ChannelKind::Inorm | ChannelKind::Float => ScalarKind::Float,
+        ChannelKind::Int => ScalarKind::Int,
+        ChannelKind::Uint => ScalarKind::Uint,
+    })
+}
+
+/// Parse null-terminated string literal.
+/// Returns the string and number of words it occupies.
+fn parse_string(words: &[u32]) -> (String, usize) {
+    let mut bytes = Vec::new();
+    for (index, &word) in words.iter().enumerate() {
+        for shift in &[0, 8, 16, 24] {
+            let byte = (word >> shift) as u8;
+            if byte == 0 {
+                return (String::from_utf8_lossy(&bytes).into_owned(), index + 1);
+            }
+            bytes.push(byte);
+        }
+    }
+    (String::from_utf8_lossy(&bytes).into_owned(), words.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vertex::{Normal, PosNorm, Position};
+
+    const OP_TYPE_VOID: u32 = 19;
+    const OP_TYPE_FUNCTION: u32 = 33;
+    const STORAGE_CLASS_OUTPUT: u32 = 3;
+
+    fn instruction(words: &mut Vec<u32>, opcode: u32, operands: &[u32]) {
+        words.push((operands.len() as u32 + 1) << 16 | opcode);
+        words.extend_from_slice(operands);
+    }
+
+    fn string(value: &str) -> Vec<u32> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(value.len() / 4 * 4 + 4, 0);
+        bytes
+            .chunks(4)
+            .map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24)
+            .collect()
+    }
+
+    /// Build module with vertex entry point `main`
+    /// and float vector inputs given as (name, location, width, components).
+    fn module(inputs: &[(&str, u32, u32, u32)]) -> Vec<u32> {
+        const VOID: u32 = 1;
+        const MAIN_TYPE: u32 = 2;
+        const MAIN: u32 = 3;
+        const OUT_POSITION: u32 = 4;
+        const VEC4_OUT: u32 = 5;
+        // Ids of types and variable of each input.
+        let ids = |index: usize| {
+            let base = 10 + index as u32 * 4;
+            (base, base + 1, base + 2, base + 3)
+        };
+
+        let mut words = vec![MAGIC, 0x0001_0000, 0, ids(inputs.len() + 1).0, 0];
+
+        let mut operands = vec![EXECUTION_MODEL_VERTEX, MAIN];
+        operands.extend(string("main"));
+        operands.push(OUT_POSITION);
+        operands.extend((0..inputs.len()).map(|index| ids(index).3));
+        instruction(&mut words, OP_ENTRY_POINT, &operands);
+
+        for (index, &(name, location, _, _)) in inputs.iter().enumerate() {
+            let variable = ids(index).3;
+            let mut operands = vec![variable];
+            operands.extend(string(name));
+            instruction(&mut words, OP_NAME, &operands);
+            instruction(
+                &mut words,
+                OP_DECORATE,
+                &[variable, DECORATION_LOCATION, location],
+            );
+        }
+        instruction(
+            &mut words,
+            OP_DECORATE,
+            &[OUT_POSITION, DECORATION_BUILT_IN, 0],
+        );
+
+        instruction(&mut words, OP_TYPE_VOID, &[VOID]);
+        instruction(&mut words, OP_TYPE_FUNCTION, &[MAIN_TYPE, VOID]);
+        for (index, &(_, _, width, components)) in inputs.iter().enumerate() {
+            let (scalar, vector, pointer, variable) = ids(index);
+            instruction(&mut words, OP_TYPE_FLOAT, &[scalar, width]);
+            instruction(&mut words, OP_TYPE_VECTOR, &[vector, scalar, components]);
+            instruction(
+                &mut words,
+                OP_TYPE_POINTER,
+                &[pointer, STORAGE_CLASS_INPUT, vector],
+            );
+            instruction(
+                &mut words,
+                OP_VARIABLE,
+                &[pointer, variable, STORAGE_CLASS_INPUT],
+            );
+        }
+        let (scalar, vector, _, _) = ids(inputs.len());
+        instruction(&mut words, OP_TYPE_FLOAT, &[scalar, 32]);
+        instruction(&mut words, OP_TYPE_VECTOR, &[vector, scalar, 4]);
+        instruction(
+            &mut words,
+            OP_TYPE_POINTER,
+            &[VEC4_OUT, STORAGE_CLASS_OUTPUT, vector],
+        );
+        instruction(
+            &mut words,
+            OP_VARIABLE,
+            &[VEC4_OUT, OUT_POSITION, STORAGE_CLASS_OUTPUT],
+        );
+        words
+    }
+
+    fn bytes(words: &[u32]) -> Vec<u8> {
+        words
+            .iter()
+            .flat_map(|w| (0..4).map(move |i| (w >> (i * 8)) as u8))
+            .collect()
+    }
+
+    #[test]
+    fn parse() {
+        let words = module(&[("normal", 1, 32, 3), ("position", 0, 32, 3)]);
+        let inputs = VertexInputs::parse_words(&words, "main").unwrap();
+        assert_eq!(
+            inputs.inputs(),
+            &[
+                VertexInput {
+                    location: 0,
+                    name: "position".into(),
+                    format: Format::Rgb32Float,
+                },
+                VertexInput {
+                    location: 1,
+                    name: "normal".into(),
+                    format: Format::Rgb32Float,
+                },
+            ]
+        );
+        assert_eq!(inputs.check_vertex_format::<PosNorm>(), Ok(()));
+        assert_eq!(
+            inputs.check_vertex_format::<Position>(),
+            Err(VertexInputError::MissingAttribute {
+                location: 1,
+                name: "normal".into(),
+            })
+        );
+
+        // Both byte orders are accepted.
+        let swapped = words.iter().map(|w| w.swap_bytes()).collect::<Vec<_>>();
+        assert_eq!(
+            VertexInputs::parse(&bytes(&words), "main"),
+            Ok(inputs.clone())
+        );
+        assert_eq!(VertexInputs::parse(&bytes(&swapped), "main"), Ok(inputs));
+
+        assert_eq!(
+            VertexInputs::parse_words(&words, "other"),
+            Err(SpirvError::EntryPointNotFound {
+                name: "other".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn bad_magic() {
+        let mut words = module(&[("position", 0, 32, 3)]);
+        words[0] = 0x0203_0723;
+        assert_eq!(
+            VertexInputs::parse_words(&words, "main"),
+            Err(SpirvError::InvalidHeader)
+        );
+        assert_eq!(
+            VertexInputs::parse_words(&words[..HEADER_SIZE - 1], "main"),
+            Err(SpirvError::InvalidHeader)
+        );
+        assert_eq!(
+            VertexInputs::parse(&bytes(&words)[..3], "main"),
+            Err(SpirvError::InvalidHeader)
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let words = module(&[("position", 0, 32, 3)]);
+        // Last instruction is `OpVariable` of four words.
+        let last = words.len() - 4;
+        assert_eq!(
+            VertexInputs::parse_words(&words[..words.len() - 1], "main"),
+            Err(SpirvError::Malformed { offset: last })
+        );
+
+        // Instruction with zero word count.
+        let mut words = words;
+        words[HEADER_SIZE] &= 0xffff;
+        assert_eq!(
+            VertexInputs::parse_words(&words, "main"),
+            Err(SpirvError::Malformed {
+                offset: HEADER_SIZE,
+            })
+        );
+    }
+
+    #[test]
+    fn location_clash() {
+        let words = module(&[("position", 0, 32, 3), ("normal", 0, 32, 3)]);
+        let inputs = VertexInputs::parse_words(&words, "main").unwrap();
+        assert_eq!(
+            inputs.check_vertex_format::<PosNorm>(),
+            Err(VertexInputError::LocationClash {
+                location: 0,
+                first: "position".into(),
+                second: "normal".into(),
+            })
+        );
+    }
+
+    /// Module with single `position` input at the location retyped as array
+    /// of `length` elements. Elements are `vec3` or the array itself if `recursive`.
+    fn array_module(location: u32, length: u32, recursive: bool) -> Vec<u32> {
+        // Ids of the vector and pointer types of the input in `module`.
+        const VECTOR: u32 = 11;
+        const POINTER: u32 = 12;
+        const ARRAY: u32 = 100;
+        const UINT: u32 = 101;
+        const LENGTH: u32 = 102;
+
+        let mut words = module(&[("position", location, 32, 3)]);
+        let element = if recursive { ARRAY } else { VECTOR };
+        instruction(&mut words, OP_TYPE_INT, &[UINT, 32, 0]);
+        instruction(&mut words, OP_CONSTANT, &[UINT, LENGTH, length]);
+        instruction(&mut words, OP_TYPE_ARRAY, &[ARRAY, element, LENGTH]);
+        instruction(
+            &mut words,
+            OP_TYPE_POINTER,
+            &[POINTER, STORAGE_CLASS_INPUT, ARRAY],
+        );
+        words
+    }
+
+    #[test]
+    fn arrays() {
+        let inputs = VertexInputs::parse_words(&array_module(1, 3, false), "main").unwrap();
+        assert_eq!(
+            inputs.locations(),
+            vec![("position", 1), ("position_1", 2), ("position_2", 3)]
+        );
+
+        let words = array_module(0, MAX_LOCATIONS, false);
+        let inputs = VertexInputs::parse_words(&words, "main").unwrap();
+        assert_eq!(inputs.inputs().len(), MAX_LOCATIONS as usize);
+
+        let unsupported = Err(SpirvError::UnsupportedType {
+            name: "position".into(),
+        });
+        for &(location, length, recursive) in &[
+            // Too many locations.
+            (0, MAX_LOCATIONS + 1, false),
+            (0, u32::MAX, false),
+            // Locations past `u32::MAX`.
+            (u32::MAX - 1, 3, false),
+            // Self-referential array.
+            (0, 1, true),
+        ] {
+            let words = array_module(location, length, recursive);
+            assert_eq!(VertexInputs::parse_words(&words, "main"), unsupported);
+        }
+
+        // Last location is representable.
+        let words = array_module(u32::MAX - 2, 2, false);
+        let inputs = VertexInputs::parse_words(&words, "main").unwrap();
+        assert_eq!(inputs.inputs()[1].location, u32::MAX - 1);
+    }
+
+    #[test]
+    fn double_vector_locations() {
+        let formats = [VertexFormat::from_attributes(vec![
+            DynamicAttribute::new("position", Format::Rgb64Float),
+            DynamicAttribute::new("normal", Format::Rgb32Float),
+        ])];
+
+        // `dvec3` at location 0 occupies locations 0 and 1.
+        let words = module(&[("position", 0, 64, 3), ("normal", 1, 32, 3)]);
+        let inputs = VertexInputs::parse_words(&words, "main").unwrap();
+        assert_eq!(inputs.inputs()[0].format, Format::Rgb64Float);
+        assert_eq!(
+            inputs.check_formats(&formats),
+            Err(VertexInputError::LocationClash {
+                location: 1,
+                first: "position".into(),
+                second: "normal".into(),
+            })
+        );
+
+        let words = module(&[("position", 0, 64, 3), ("normal", 2, 32, 3)]);
+        let inputs = VertexInputs::parse_words(&words, "main").unwrap();
+        assert_eq!(inputs.check_formats(&formats), Ok(()));
+        assert_eq!(
+            inputs.check_vertex_format::<Normal>(),
+            Err(VertexInputError::MissingAttribute {
+                location: 0,
+                name: "position".into(),
+            })
+        );
+
+        // `dvec2` occupies single location.
+        let words = module(&[("position", 0, 64, 2), ("normal", 1, 32, 3)]);
+        let inputs = VertexInputs::parse_words(&words, "main").unwrap();
+        let formats = [VertexFormat::from_attributes(vec![
+            DynamicAttribute::new("position", Format::Rg64Float),
+            DynamicAttribute::new("normal", Format::Rgb32Float),
+        ])];
+        assert_eq!(inputs.check_formats(&formats), Ok(()));
+    }
+}
@@ -0,0 +1,486 @@
+//!
+//! Generation of MikkTSpace tangents for `MeshBuilder`.
+//!
+
+use std::collections::HashMap;
+
+use hal::Primitive;
+
+use access::AccessError;
+use math::{add, bits, dot, normalize, scale, sub};
+use mesh::{MeshBuilder, MeshBuilderError};
+use vertex::{Normal, Position, Tangent, TexCoord};
+
+/// Error returned when tangents can't be generated.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum TangentError {
+    /// Vertex data of the builder is malformed.
+    #[fail(display = "Vertex data is invalid: {}", _0)]
+    InvalidVertices(#[cause] MeshBuilderError),
+
+    /// Tangents can be generated only for triangle lists.
+    #[fail(display = "Primitive {:?} is not a triangle list", _0)]
+    UnsupportedPrimitive(Primitive),
+
+    /// Positions can't be read.
+    #[fail(display = "Positions are not accessible: {}", _0)]
+    InvalidPositions(#[cause] AccessError),
+
+    /// Normals can't be read.
+    #[fail(display = "Normals are not accessible: {}", _0)]
+    InvalidNormals(#[cause] AccessError),
+
+    /// Texture coordinates can't be read.
+    #[fail(display = "Texture coordinates are not accessible: {}", _0)]
+    InvalidTexCoords(#[cause] AccessError),
+
+    /// Builder already has tangents in format other than `Tangent`.
+    #[fail(display = "Tangents can't be replaced: {}", _0)]
+    InvalidTangents(#[cause] AccessError),
+
+    /// Index refers to vertex that doesn't exist.
+    #[fail(
+        display = "Index {} is out of range for {} vertices",
+        index, vertex_count
+    )]
+    IndexOutOfRange {
+        /// Value of the index.
+        index: u32,
+        /// Number of vertices.
+        vertex_count: usize,
+    },
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Generate tangents.
+    /// See `set_tangents`.
+    pub fn with_tangents(mut self) -> Result<Self, TangentError> {
+        self.set_tangents()?;
+        Ok(self)
+    }
+
+    /// Generate tangents from `Position`, `Normal` and `TexCoord` attributes
+    /// the same way as MikkTSpace reference implementation with default settings,
+    /// so normal maps baked by Blender, Substance and other MikkTSpace tools
+    /// are reproduced without seams.
+    ///
+    /// Texture coordinates of this crate have origin at the top-left corner
+    /// while bakers use bottom-left one, so `v` is flipped
+    /// before tangent space is computed.
+    /// That matches tangents expected by glTF.
+    ///
+    /// Vertices shared by faces with different tangent spaces
+    /// (e.g. at mirrored UV seams) are split and indices are updated.
+    /// Existing `Tangent` attribute is overwritten in place,
+    /// otherwise new buffer with `Tangent` attribute is added.
+    pub fn set_tangents(&mut self) -> Result<&mut Self, TangentError> {
+        self.vertex_count().map_err(TangentError::InvalidVertices)?;
+        if self.prim != Primitive::TriangleList {
+            return Err(TangentError::UnsupportedPrimitive(self.prim));
+        }
+        match self.attribute::<Tangent>() {
+            Ok(_) | Err(AccessError::MissingAttribute { .. }) => {}
+            Err(error) => return Err(TangentError::InvalidTangents(error)),
+        }
+        let positions = self
+            .attribute::<Position>()
+            .map_err(TangentError::InvalidPositions)?
+            .map(|position| position.0)
+            .collect::<Vec<_>>();
+        let normals = self
+            .attribute::<Normal>()
+            .map_err(TangentError::InvalidNormals)?
+            .map(|normal| normal.0)
+            .collect::<Vec<_>>();
+        let tex_coords = self
+            .attribute::<TexCoord>()
+            .map_err(TangentError::InvalidTexCoords)?
+            .map(|tex_coord| [tex_coord.0[0], 1.0 - tex_coord.0[1]])
+            .collect::<Vec<_>>();
+        let vertex_count = positions.len();
+        let indices = self
+            .read_indices()
+            .unwrap_or_else(|| (0..vertex_count as u32).collect());
+        if let Some(&index) = indices
+            .iter()
+            .find(|&&index| index as usize >= vertex_count)
+        {
+            return Err(TangentError::IndexOutOfRange {
+                index,
+                vertex_count,
+            });
+        }
+
+        let corner_count = indices.len() / 3 * 3;
+        let vertex = |corner: usize| indices[corner] as usize;
+
+        // Identical vertices are welded so adjacency doesn't depend on indexing.
+        let mut ids = HashMap::new();
+        let welded = indices[..corner_count]
+            .iter()
+            .map(|&index| {
+                let index = index as usize;
+                let t = tex_coords[index];
+                let key = (
+                    bits(positions[index]),
+                    bits(normals[index]),
+                    bits([t[0], t[1], 0.0]),
+                );
+                let id = ids.len();
+                *ids.entry(key).or_insert(id)
+            })
+            .collect::<Vec<_>>();
+
+        let mut faces = (0..corner_count / 3)
+            .map(|face| {
+                let corner = face * 3;
+                Face::new(
+                    [
+                        positions[vertex(corner)],
+                        positions[vertex(corner + 1)],
+                        positions[vertex(corner + 2)],
+                    ],
+                    [
+                        tex_coords[vertex(corner)],
+                        tex_coords[vertex(corner + 1)],
+                        tex_coords[vertex(corner + 2)],
+                    ],
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Neighbor across edge from corner `i` to the next one.
+        let mut edges = HashMap::new();
+        for corner in 0..corner_count {
+            edges
+                .entry((welded[corner], welded[next(corner)]))
+                .or_insert(corner / 3);
+        }
+        let neighbors = (0..corner_count)
+            .map(|corner| {
+                edges
+                    .get(&(welded[next(corner)], welded[corner]))
+                    .cloned()
+                    .filter(|&face| face != corner / 3)
+            })
+            .collect::<Vec<_>>();
+
+        // Group faces around each vertex that are connected through edges
+        // and have the same orientation in texture space.
+        let mut groups: Vec<Group> = Vec::new();
+        let mut corner_groups = vec![None; corner_count];
+        for seed in 0..corner_count {
+            if faces[seed / 3].any_group || corner_groups[seed].is_some() {
+                continue;
+            }
+            let group = groups.len();
+            groups.push(Group {
+                orientation: faces[seed / 3].orientation,
+                corners: Vec::new(),
+            });
+
+            let mut stack = vec![seed];
+            while let Some(corner) = stack.pop() {
+                if corner_groups[corner].is_some() {
+                    continue;
+                }
+                let face = corner / 3;
+                let first = face * 3;
+                if faces[face].any_group && (first..first + 3).all(|c| corner_groups[c].is_none()) {
+                    // First group to reach such face determines its orientation.
+                    faces[face].orientation = groups[group].orientation;
+                }
+                if faces[face].orientation != groups[group].orientation {
+                    continue;
+                }
+                corner_groups[corner] = Some(group);
+                groups[group].corners.push(corner);
+
+                for &neighbor in &[neighbors[prev(corner)], neighbors[corner]] {
+                    if let Some(neighbor) = neighbor {
+                        let first = neighbor * 3;
+                        if let Some(c) = (first..first + 3).find(|&c| welded[c] == welded[corner]) {
+                            stack.push(c);
+                        }
+                    }
+                }
+            }
+        }
+
+        // Average tangents of corners in each group weighted by corner angles.
+        let tangents = groups
+            .iter()
+            .map(|group| {
+                let mut sum = [0.0; 3];
+                for &corner in &group.corners {
+                    let normal = normals[vertex(corner)];
+                    let project = |v: [f32; 3]| normalize(sub(v, scale(normal, dot(normal, v))));
+                    let tangent = project(faces[corner / 3].tangent);
+                    let position = positions[vertex(corner)];
+                    let a = project(sub(positions[vertex(prev(corner))], position));
+                    let b = project(sub(positions[vertex(next(corner))], position));
+                    let angle = dot(a, b).clamp(-1.0, 1.0).acos();
+                    sum = add(sum, scale(tangent, angle));
+                }
+                let t = normalize(sum);
+                let w = if group.orientation { 1.0 } else { -1.0 };
+                [t[0], t[1], t[2], w]
+            })
+            .collect::<Vec<_>>();
+
+        // Corners of faces with degenerate texture coordinates that no group reached
+        // take tangent space of another corner of the same vertex.
+        let mut vertex_groups = vec![None; vertex_count];
+        for corner in 0..corner_count {
+            if let Some(group) = corner_groups[corner] {
+                vertex_groups[vertex(corner)].get_or_insert(group);
+            }
+        }
+        // Groups that ended up with equal tangents don't require vertex split.
+        let mut unique = HashMap::new();
+        let mut values = Vec::new();
+        let corner_groups = (0..corner_count)
+            .map(|corner| {
+                let tangent = corner_groups[corner]
+                    .or(vertex_groups[vertex(corner)])
+                    .map_or(DEFAULT_TANGENT, |group| tangents[group]);
+                let key = (bits([tangent[0], tangent[1], tangent[2]]), tangent[3] > 0.0);
+                *unique.entry(key).or_insert_with(|| {
+                    values.push(tangent);
+                    values.len() - 1
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let vertex_groups = self.split_vertices(vertex_count, indices, &corner_groups);
+        let tangents = vertex_groups
+            .into_iter()
+            .map(|group| Tangent(group.map_or(DEFAULT_TANGENT, |group| values[group])))
+            .collect();
+        self.replace_attribute(tangents)
+            .map_err(TangentError::InvalidTangents)?;
+        Ok(self)
+    }
+}
+
+/// Tangent of vertices without tangent space, as in MikkTSpace.
+const DEFAULT_TANGENT: [f32; 4] = [1.0, 0.0, 0.0, -1.0];
+
+/// Texture space of triangle.
+struct Face {
+    /// Unit direction of increasing `u`.
+    tangent: [f32; 3],
+    /// Whether texture space has the same winding as the triangle.
+    orientation: bool,
+    /// Degenerate face that takes tangent space of whatever group reaches it.
+    any_group: bool,
+}
+
+impl Face {
+    fn new(positions: [[f32; 3]; 3], tex_coords: [[f32; 2]; 3]) -> Self {
+        let d1 = sub(positions[1], positions[0]);
+        let d2 = sub(positions[2], positions[0]);
+        let t21 = [
+            tex_coords[1][0] - tex_coords[0][0],
+            tex_coords[1][1] - tex_coords[0][1],
+        ];
+        let t31 = [
+            tex_coords[2][0] - tex_coords[0][0],
+            tex_coords[2][1] - tex_coords[0][1],
+        ];
+        let signed_area = t21[0] * t31[1] - t21[1] * t31[0];
+        let os = sub(scale(d1, t31[1]), scale(d2, t21[1]));
+        let ot = add(scale(d1, -t31[0]), scale(d2, t21[0]));
+        let orientation = signed_area > 0.0;
+
+        let mut face = Face {
+            tangent: [0.0; 3],
+            orientation,
+            any_group: true,
+        };
+        if signed_area != 0.0 {
+            let sign = if orientation { 1.0 } else { -1.0 };
+            face.tangent = scale(normalize(os), sign);
+            face.any_group = dot(os, os) == 0.0 || dot(ot, ot) == 0.0;
+        }
+        face
+    }
+}
+
+/// Corners around single vertex sharing tangent space.
+struct Group {
+    orientation: bool,
+    corners: Vec<usize>,
+}
+
+fn next(corner: usize) -> usize {
+    corner / 3 * 3 + (corner + 1) % 3
+}
+
+fn prev(corner: usize) -> usize {
+    corner / 3 * 3 + (corner + 2) % 3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shapes::cuboid;
+    use vertex::{PosNorm, PosNormTex};
+
+    fn approx(a: [f32; 4], b: [f32; 4]) -> bool {
+        (0..4).all(|i| (a[i] - b[i]).abs() < 1e-5)
+    }
+
+    /// Cube with tangents overwritten by zeros and its original tangents.
+    fn cube() -> (MeshBuilder<'static>, Vec<[f32; 4]>) {
+        let mut builder = cuboid([2.0, 2.0, 2.0], 1);
+        let tangents = builder
+            .attribute::<Tangent>()
+            .unwrap()
+            .map(|tangent| tangent.0)
+            .collect();
+        builder
+            .attribute_mut::<Tangent>()
+            .unwrap()
+            .update(|_| Tangent([0.0; 4]));
+        (builder, tangents)
+    }
+
+    fn tangents(builder: &MeshBuilder) -> Vec<[f32; 4]> {
+        builder
+            .attribute::<Tangent>()
+            .unwrap()
+            .map(|tangent| tangent.0)
+            .collect()
+    }
+
+    #[test]
+    fn cube_tangents() {
+        let (mut builder, expected) = cube();
+        builder.set_tangents().unwrap();
+        assert_eq!(builder.vertex_count(), Ok(Some(24)));
+        let tangents = tangents(&builder);
+        for (tangent, expected) in tangents.iter().zip(&expected) {
+            assert!(approx(*tangent, *expected));
+        }
+        // Every face has its own tangent along one of the axes.
+        assert!(tangents
+            .iter()
+            .all(|t| t[3] == 1.0 && dot([t[0], t[1], t[2]], [t[0], t[1], t[2]]) > 0.999));
+    }
+
+    #[test]
+    fn mirrored_face() {
+        let (mut builder, expected) = cube();
+        // First 4 vertices form `+Z` face. Mirror its texture horizontally.
+        let mut index = 0;
+        builder.attribute_mut::<TexCoord>().unwrap().update(|uv| {
+            index += 1;
+            if index <= 4 {
+                TexCoord([1.0 - uv.0[0], uv.0[1]])
+            } else {
+                uv
+            }
+        });
+        builder.set_tangents().unwrap();
+        assert_eq!(builder.vertex_count(), Ok(Some(24)));
+
+        for (i, (tangent, expected)) in tangents(&builder).iter().zip(&expected).enumerate() {
+            let expected = if i < 4 {
+                [-expected[0], -expected[1], -expected[2], -1.0]
+            } else {
+                *expected
+            };
+            assert!(approx(*tangent, expected));
+        }
+    }
+
+    #[test]
+    fn reference_tangents() {
+        // Curved patch with shared smooth normals, rotated texture and triangle `3 2 5`
+        // with zero area in texture space.
+        let positions = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.2],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.1],
+            [0.5, 0.5, 0.3],
+            [0.5, 1.6, 0.0],
+        ];
+        let normals = [
+            [-0.19245009, -0.19245009, 0.9622505],
+            [0.19245009, -0.19245009, 0.9622505],
+            [0.19245009, 0.19245009, 0.9622505],
+            [-0.19245009, 0.19245009, 0.9622505],
+            [0.0, 0.0, 1.0],
+            [0.0, 0.28734788, 0.95782626],
+        ];
+        let tex_coords = [
+            [0.1, 0.3],
+            [0.79282033, 0.70000005],
+            [0.3928203, 1.3928204],
+            [-0.3, 0.9928203],
+            [0.24641016, 0.8464102],
+            [0.3928203, 1.3928204],
+        ];
+        let indices = vec![0u16, 1, 4, 1, 2, 4, 2, 3, 4, 3, 0, 4, 3, 2, 5];
+        // Tangents of each corner generated by a Rust port of the reference implementation
+        // (`mikktspace` crate) with `v` flipped. Vertex 5 has no tangent space there.
+        let expected = [
+            [0.87597585, -0.4756641, 0.08006235, -1.0],
+            [0.8402612, -0.4741839, -0.26288903, -1.0],
+            [0.8660254, -0.50000006, 0.0, -1.0],
+            [0.8402612, -0.4741839, -0.26288903, -1.0],
+            [0.85913575, -0.50687486, -0.070452176, -1.0],
+            [0.8660254, -0.50000006, 0.0, -1.0],
+            [0.85913575, -0.50687486, -0.070452176, -1.0],
+            [0.835512, -0.48215157, 0.26353273, -1.0],
+            [0.8660254, -0.50000006, 0.0, -1.0],
+            [0.835512, -0.48215157, 0.26353273, -1.0],
+            [0.87597585, -0.4756641, 0.08006235, -1.0],
+            [0.8660254, -0.50000006, 0.0, -1.0],
+            [0.835512, -0.48215157, 0.26353273, -1.0],
+            [0.85913575, -0.50687486, -0.070452176, -1.0],
+            [1.0, 0.0, 0.0, -1.0],
+        ];
+
+        let mut builder = MeshBuilder::new();
+        builder
+            .push_vertices(
+                (0..positions.len())
+                    .map(|i| PosNormTex {
+                        position: positions[i].into(),
+                        normal: normals[i].into(),
+                        tex_coord: tex_coords[i].into(),
+                    })
+                    .collect::<Vec<_>>(),
+            )
+            .set_indices(indices);
+        builder.set_tangents().unwrap();
+
+        let tangents = tangents(&builder);
+        let corners = builder.indices().unwrap().collect::<Vec<_>>();
+        for (corner, expected) in corners.iter().zip(&expected) {
+            let tangent = tangents[*corner as usize];
+            assert!(approx(tangent, *expected), "{:?} {:?}", tangent, expected);
+        }
+    }
+
+    #[test]
+    fn missing_tex_coords() {
+        let mut builder = MeshBuilder::new();
+        builder.push_vertices(vec![
+            PosNorm {
+                position: [0.0; 3].into(),
+                normal: [0.0, 0.0, 1.0].into(),
+            };
+            3
+        ]);
+        assert_eq!(
+            builder.set_tangents().err(),
+            Some(TangentError::InvalidTexCoords(
+                AccessError::MissingAttribute { name: "tex_coord" }
+            ))
+        );
+    }
+}
@@ -1,5 +1,15 @@
 use std::borrow::Cow;
 
+use std::mem::{align_of, size_of, size_of_val};
+use std::ptr::{read_unaligned, write_unaligned};
+use std::slice::from_raw_parts;
+
+use hal::format::Format;
+use hal::memory::Pod;
+use hal::pso::ElemStride;
+
+use mesh::Indices;
+
 pub fn is_slice_sorted<T: Ord>(slice: &[T]) -> bool {
     is_slice_sorted_by_key(slice, |i| i)
 }
@@ -18,36 +28,257 @@ pub fn is_slice_sorted_by_key<'a, T, K: Ord, F: Fn(&'a T) -> K>(slice: &'a [T],
     true
 }
 
-pub fn cast_vec<T>(mut vec: Vec<T>) -> Vec<u8> {
-    use std::mem;
+/// View slice of plain data as bytes.
+pub fn cast_slice<T>(slice: &[T]) -> &[u8]
+where
+    T: Pod,
+{
+    // `Pod` guarantees there is no padding or other uninitialized bytes.
+    unsafe { from_raw_parts(slice.as_ptr() as *const u8, size_of_val(slice)) }
+}
+
+/// Convert vector of plain data into bytes.
+/// Data is copied because `Vec<u8>` can't own allocation made with alignment of `T`.
+pub fn cast_vec<T>(vec: Vec<T>) -> Vec<u8>
+where
+    T: Pod,
+{
+    cast_slice(&vec).to_vec()
+}
+
+/// Convert borrowed or owned plain data into bytes.
+/// Borrowed data stays borrowed.
+pub fn cast_cow<T>(cow: Cow<[T]>) -> Cow<[u8]>
+where
+    T: Pod,
+{
+    match cow {
+        Cow::Borrowed(slice) => Cow::Borrowed(cast_slice(slice)),
+        Cow::Owned(vec) => Cow::Owned(cast_vec(vec)),
+    }
+}
+
+/// Error returned by `from_bytes`.
+#[derive(Clone, Copy, Debug, Fail, PartialEq, Eq)]
+pub enum CastError {
+    /// Size of bytes is not multiple of the type's size.
+    #[fail(display = "Size {} is not multiple of {}", size, type_size)]
+    Size {
+        /// Size of the bytes.
+        size: usize,
+        /// Size of the type.
+        type_size: usize,
+    },
 
-    let raw_len = mem::size_of::<T>() * vec.len();
-    let len = raw_len;
+    /// Bytes are not aligned for the type.
+    #[fail(display = "Address {:#x} is not aligned to {}", address, align)]
+    Misaligned {
+        /// Address of the bytes.
+        address: usize,
+        /// Required alignment.
+        align: usize,
+    },
+}
 
-    let cap = mem::size_of::<T>() * vec.capacity();
+/// View bytes as slice of plain data.
+/// Fails if size of bytes is not multiple of `T`'s size
+/// or non-empty bytes are not aligned for `T`.
+/// Use `read_pod` to read values from unaligned bytes.
+pub fn from_bytes<T>(bytes: &[u8]) -> Result<&[T], CastError>
+where
+    T: Pod,
+{
+    let type_size = size_of::<T>();
+    if type_size == 0 || bytes.len() % type_size != 0 {
+        return Err(CastError::Size {
+            size: bytes.len(),
+            type_size,
+        });
+    }
+    if bytes.is_empty() {
+        // Empty slices may have dangling address that is not aligned for `T`.
+        return Ok(&[]);
+    }
+    let address = bytes.as_ptr() as usize;
+    let align = align_of::<T>();
+    if address % align != 0 {
+        return Err(CastError::Misaligned { address, align });
+    }
+    // Any bytes are valid `Pod` values. Size and alignment are checked above.
+    Ok(unsafe { from_raw_parts(bytes.as_ptr() as *const T, bytes.len() / type_size) })
+}
 
-    let ptr = vec.as_mut_ptr();
-    mem::forget(vec);
-    unsafe { Vec::from_raw_parts(ptr as _, len, cap) }
+/// Size of single element of the format in bytes.
+pub fn format_size(format: Format) -> ElemStride {
+    format.surface_desc().bits as ElemStride / 8
 }
 
-pub fn cast_slice<T>(slice: &[T]) -> &[u8] {
-    use std::{mem, slice::from_raw_parts};
+/// Required alignment of element's offset.
+/// Largest power of two that divides element size, but not greater than 4.
+pub fn format_align(format: Format) -> ElemStride {
+    let size = format_size(format);
+    if size % 4 == 0 {
+        4
+    } else if size % 2 == 0 {
+        2
+    } else {
+        1
+    }
+}
 
-    let raw_len = mem::size_of::<T>() * slice.len();
-    let len = raw_len;
+/// Round `value` up to multiple of `align`.
+pub fn align_up(value: ElemStride, align: ElemStride) -> ElemStride {
+    (value + align - 1) / align * align
+}
 
-    let ptr = slice.as_ptr();
-    mem::forget(slice);
-    unsafe { from_raw_parts(ptr as _, len) }
+/// Read value from bytes at specified offset.
+/// Offset doesn't have to be aligned.
+pub fn read_pod<T: Pod>(bytes: &[u8], offset: usize) -> T {
+    assert!(offset + size_of::<T>() <= bytes.len());
+    unsafe { read_unaligned(bytes[offset..].as_ptr() as *const T) }
 }
 
-pub fn cast_cow<T>(cow: Cow<[T]>) -> Cow<[u8]>
+/// Write value into bytes at specified offset.
+/// Offset doesn't have to be aligned.
+pub fn write_pod<T: Pod>(bytes: &mut [u8], offset: usize, value: T) {
+    assert!(offset + size_of::<T>() <= bytes.len());
+    unsafe { write_unaligned(bytes[offset..].as_mut_ptr() as *mut T, value) }
+}
+
+/// Keep indices as `u16` if they fit.
+/// See `narrow_to_u16`.
+/// Indices are never narrowed to `u8` because `gfx-hal` can't upload them.
+pub fn narrow_indices(indices: Vec<u32>) -> Indices<'static> {
+    match narrow_to_u16(indices.iter().cloned()) {
+        Some(narrowed) => Indices::U16(Cow::Owned(narrowed)),
+        None => Indices::U32(Cow::Owned(indices)),
+    }
+}
+
+/// Convert indices to `u16`.
+/// Returns `None` if any index doesn't fit.
+/// `0xFFFF` is kept as `u32` too because it's the `u16` primitive restart index.
+pub fn narrow_to_u16<I>(indices: I) -> Option<Vec<u16>>
 where
-    T: Clone,
+    I: IntoIterator<Item = u32>,
 {
-    match cow {
-        Cow::Borrowed(slice) => Cow::Borrowed(cast_slice(slice)),
-        Cow::Owned(vec) => Cow::Owned(cast_vec(vec)),
+    indices
+        .into_iter()
+        .map(|index| {
+            if index < u16::MAX as u32 {
+                Some(index as u16)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cast_vec_owned() {
+        let bytes = cast_vec(vec![0x0403_0201u32, 0x0807_0605]);
+        let expected = [0x0403_0201u32.to_ne_bytes(), 0x0807_0605u32.to_ne_bytes()].concat();
+        assert_eq!(bytes, expected);
+        assert!(cast_vec(Vec::<[f32; 3]>::new()).is_empty());
+    }
+
+    #[test]
+    fn cast_cow_borrowed() {
+        let values = [[1.0f32, 2.0], [3.0, 4.0]];
+        match cast_cow(Cow::Borrowed(&values[..])) {
+            Cow::Borrowed(bytes) => {
+                assert_eq!(bytes.as_ptr(), values.as_ptr() as *const u8);
+                assert_eq!(bytes.len(), 16);
+            }
+            Cow::Owned(_) => panic!("Borrowed data must stay borrowed"),
+        }
+        match cast_cow(Cow::Owned(values.to_vec())) {
+            Cow::Owned(bytes) => assert_eq!(&bytes[..], cast_slice(&values)),
+            Cow::Borrowed(_) => panic!("Owned data must stay owned"),
+        }
+    }
+
+    #[test]
+    fn cast_slice_pod() {
+        let values = [1u16, 0xffff, 0x1234];
+        let bytes = cast_slice(&values);
+        assert_eq!(bytes.len(), 6);
+        assert_eq!(&bytes[2..4], &0xffffu16.to_ne_bytes());
+        assert_eq!(from_bytes::<u16>(bytes), Ok(&values[..]));
+
+        let arrays = [[1.5f32; 3], [-2.0; 3]];
+        assert_eq!(cast_slice(&arrays).len(), 24);
+        assert_eq!(from_bytes::<[f32; 3]>(cast_slice(&arrays)), Ok(&arrays[..]));
+        assert!(cast_slice::<u64>(&[]).is_empty());
+    }
+
+    #[test]
+    fn from_bytes_misaligned() {
+        let values = [0u32; 3];
+        let bytes = &cast_slice(&values)[1..9];
+        assert_eq!(
+            from_bytes::<u32>(bytes),
+            Err(CastError::Misaligned {
+                address: bytes.as_ptr() as usize,
+                align: 4,
+            })
+        );
+        // Bytes have no alignment requirement.
+        assert_eq!(from_bytes::<u8>(bytes), Ok(bytes));
+    }
+
+    #[test]
+    fn from_bytes_size() {
+        let values = [0u32; 2];
+        assert_eq!(
+            from_bytes::<u32>(&cast_slice(&values)[..6]),
+            Err(CastError::Size {
+                size: 6,
+                type_size: 4,
+            })
+        );
+        assert_eq!(
+            from_bytes::<[u16; 3]>(cast_slice(&values)),
+            Err(CastError::Size {
+                size: 8,
+                type_size: 6,
+            })
+        );
+    }
+
+    #[test]
+    fn from_bytes_empty() {
+        assert_eq!(from_bytes::<u32>(&[]), Ok(&[][..]));
+        let values = [0u32; 1];
+        assert_eq!(from_bytes::<u32>(&cast_slice(&values)[..0]), Ok(&[][..]));
+    }
+
+    #[test]
+    fn unaligned_pod() {
+        let mut bytes = [0u8; 9];
+        write_pod(&mut bytes, 1, 0x0403_0201u32);
+        write_pod(&mut bytes, 5, [1.5f32]);
+        assert_eq!(read_pod::<u32>(&bytes, 1), 0x0403_0201);
+        assert_eq!(read_pod::<[f32; 1]>(&bytes, 5), [1.5]);
+    }
+
+    #[test]
+    fn narrow() {
+        match narrow_indices(vec![0, 255, 65534]) {
+            Indices::U16(indices) => assert_eq!(&*indices, &[0, 255, 65534]),
+            indices => panic!("Expected u16 indices, got {:?}", indices),
+        }
+        for &index in &[65535, 65536] {
+            match narrow_indices(vec![0, index]) {
+                Indices::U32(indices) => assert_eq!(&*indices, &[0, index]),
+                indices => panic!("Expected u32 indices, got {:?}", indices),
+            }
+        }
+        assert_eq!(narrow_to_u16(vec![]), Some(vec![]));
+        assert_eq!(narrow_to_u16(vec![u32::MAX]), None);
     }
 }
@@ -5,7 +5,13 @@ use std::fmt::Debug;
 
 use hal::format::{AsFormat, Format};
 use hal::memory::Pod;
-use hal::pso::{ElemStride, Element};
+use hal::pso::{ElemOffset, ElemStride, Element, InstanceRate};
+
+use pack::{
+    f16_to_f32, f32_to_f16, from_snorm, from_unorm, oct_decode, oct_encode, pack_snorm_a2b10g10r10,
+    quantize_weights, to_snorm, to_unorm, unpack_snorm_a2b10g10r10,
+};
+use utils::{align_up, format_align, format_size};
 
 /// Trait for vertex attributes to implement
 pub trait Attribute: AsFormat + Debug + PartialEq + Pod + Send + Sync {
@@ -86,26 +92,29 @@ impl Attribute for Normal {
     const SIZE: ElemStride = 12;
 }
 
-/// Type for tangent attribute of vertex
+/// Type for tangent attribute of vertex.
+/// First three components are the tangent vector
+/// and the last one is handedness of the tangent space, `1.0` or `-1.0`.
+/// Bitangent is `cross(normal, tangent.xyz) * tangent.w`.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct Tangent(pub [f32; 3]);
+pub struct Tangent(pub [f32; 4]);
 impl<T> From<T> for Tangent
 where
-    T: Into<[f32; 3]>,
+    T: Into<[f32; 4]>,
 {
     fn from(from: T) -> Self {
         Tangent(from.into())
     }
 }
 impl AsFormat for Tangent {
-    const SELF: Format = <[f32; 3] as AsFormat>::SELF;
+    const SELF: Format = <[f32; 4] as AsFormat>::SELF;
 }
 unsafe impl Pod for Tangent {}
 impl Attribute for Tangent {
     const NAME: &'static str = "tangent";
-    const SIZE: ElemStride = 12;
+    const SIZE: ElemStride = 16;
 }
 
 /// Type for texture coord attribute of vertex
@@ -130,16 +139,687 @@ impl Attribute for TexCoord {
     const SIZE: ElemStride = 8;
 }
 
+/// Type for color attribute of vertex packed as `Rgba8Unorm`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PackedColor(pub [u8; 4]);
+impl From<Color> for PackedColor {
+    fn from(color: Color) -> Self {
+        let c = color.0;
+        PackedColor([
+            to_unorm(c[0], 8) as u8,
+            to_unorm(c[1], 8) as u8,
+            to_unorm(c[2], 8) as u8,
+            to_unorm(c[3], 8) as u8,
+        ])
+    }
+}
+impl From<PackedColor> for [f32; 4] {
+    fn from(color: PackedColor) -> Self {
+        let c = color.0;
+        [
+            from_unorm(c[0] as u32, 8),
+            from_unorm(c[1] as u32, 8),
+            from_unorm(c[2] as u32, 8),
+            from_unorm(c[3] as u32, 8),
+        ]
+    }
+}
+impl AsFormat for PackedColor {
+    const SELF: Format = Format::Rgba8Unorm;
+}
+unsafe impl Pod for PackedColor {}
+impl Attribute for PackedColor {
+    const NAME: &'static str = "color";
+    const SIZE: ElemStride = 4;
+}
+
+/// Type for normal attribute of vertex packed as `A2B10G10R10` signed normalized.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PackedNormal(pub u32);
+impl From<Normal> for PackedNormal {
+    fn from(normal: Normal) -> Self {
+        let n = normal.0;
+        PackedNormal(pack_snorm_a2b10g10r10([n[0], n[1], n[2], 0.0]))
+    }
+}
+impl From<PackedNormal> for [f32; 3] {
+    fn from(normal: PackedNormal) -> Self {
+        let n = unpack_snorm_a2b10g10r10(normal.0);
+        [n[0], n[1], n[2]]
+    }
+}
+impl AsFormat for PackedNormal {
+    const SELF: Format = Format::A2b10g10r10Inorm;
+}
+unsafe impl Pod for PackedNormal {}
+impl Attribute for PackedNormal {
+    const NAME: &'static str = "normal";
+    const SIZE: ElemStride = 4;
+}
+
+/// Type for normal attribute of vertex encoded with octahedral mapping
+/// and packed as `Rg16` signed normalized.
+/// Shader must decode it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OctNormal(pub [i16; 2]);
+impl From<Normal> for OctNormal {
+    fn from(normal: Normal) -> Self {
+        let oct = oct_encode(normal.0);
+        OctNormal([to_snorm(oct[0], 16) as i16, to_snorm(oct[1], 16) as i16])
+    }
+}
+impl From<OctNormal> for [f32; 3] {
+    fn from(normal: OctNormal) -> Self {
+        let n = normal.0;
+        oct_decode([from_snorm(n[0] as i32, 16), from_snorm(n[1] as i32, 16)])
+    }
+}
+impl AsFormat for OctNormal {
+    const SELF: Format = Format::Rg16Inorm;
+}
+unsafe impl Pod for OctNormal {}
+impl Attribute for OctNormal {
+    const NAME: &'static str = "normal";
+    const SIZE: ElemStride = 4;
+}
+
+/// Type for tangent attribute of vertex packed as `A2B10G10R10` signed normalized.
+/// Handedness is stored in the 2-bit alpha channel.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PackedTangent(pub u32);
+impl From<Tangent> for PackedTangent {
+    fn from(tangent: Tangent) -> Self {
+        PackedTangent(pack_snorm_a2b10g10r10(tangent.0))
+    }
+}
+impl From<PackedTangent> for [f32; 4] {
+    fn from(tangent: PackedTangent) -> Self {
+        unpack_snorm_a2b10g10r10(tangent.0)
+    }
+}
+impl AsFormat for PackedTangent {
+    const SELF: Format = Format::A2b10g10r10Inorm;
+}
+unsafe impl Pod for PackedTangent {}
+impl Attribute for PackedTangent {
+    const NAME: &'static str = "tangent";
+    const SIZE: ElemStride = 4;
+}
+
+/// Type for tangent attribute of vertex encoded with octahedral mapping
+/// and packed as `Rg16` signed normalized.
+/// Shader must decode it. Handedness is not stored.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OctTangent(pub [i16; 2]);
+impl From<Tangent> for OctTangent {
+    fn from(tangent: Tangent) -> Self {
+        let t = tangent.0;
+        let oct = oct_encode([t[0], t[1], t[2]]);
+        OctTangent([to_snorm(oct[0], 16) as i16, to_snorm(oct[1], 16) as i16])
+    }
+}
+impl From<OctTangent> for [f32; 3] {
+    fn from(tangent: OctTangent) -> Self {
+        let t = tangent.0;
+        oct_decode([from_snorm(t[0] as i32, 16), from_snorm(t[1] as i32, 16)])
+    }
+}
+impl AsFormat for OctTangent {
+    const SELF: Format = Format::Rg16Inorm;
+}
+unsafe impl Pod for OctTangent {}
+impl Attribute for OctTangent {
+    const NAME: &'static str = "tangent";
+    const SIZE: ElemStride = 4;
+}
+
+/// Type for texture coord attribute of vertex packed as two half-precision floats.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HalfTexCoord(pub [u16; 2]);
+impl From<TexCoord> for HalfTexCoord {
+    fn from(tex_coord: TexCoord) -> Self {
+        let t = tex_coord.0;
+        HalfTexCoord([f32_to_f16(t[0]), f32_to_f16(t[1])])
+    }
+}
+impl From<HalfTexCoord> for [f32; 2] {
+    fn from(tex_coord: HalfTexCoord) -> Self {
+        let t = tex_coord.0;
+        [f16_to_f32(t[0]), f16_to_f32(t[1])]
+    }
+}
+impl AsFormat for HalfTexCoord {
+    const SELF: Format = Format::Rg16Float;
+}
+unsafe impl Pod for HalfTexCoord {}
+impl Attribute for HalfTexCoord {
+    const NAME: &'static str = "tex_coord";
+    const SIZE: ElemStride = 4;
+}
+
+/// Type for joint indices attribute of vertex.
+/// Indices of up to four joints that influence the vertex.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct JointIndices(pub [u16; 4]);
+impl<T> From<T> for JointIndices
+where
+    T: Into<[u16; 4]>,
+{
+    fn from(from: T) -> Self {
+        JointIndices(from.into())
+    }
+}
+impl AsFormat for JointIndices {
+    const SELF: Format = Format::Rgba16Uint;
+}
+unsafe impl Pod for JointIndices {}
+impl Attribute for JointIndices {
+    const NAME: &'static str = "joint_indices";
+    const SIZE: ElemStride = 8;
+}
+
+/// Type for joint indices attribute of vertex with `u8` per index.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct JointIndicesU8(pub [u8; 4]);
+impl From<JointIndicesU8> for [u16; 4] {
+    fn from(indices: JointIndicesU8) -> Self {
+        let i = indices.0;
+        [i[0] as u16, i[1] as u16, i[2] as u16, i[3] as u16]
+    }
+}
+impl AsFormat for JointIndicesU8 {
+    const SELF: Format = Format::Rgba8Uint;
+}
+unsafe impl Pod for JointIndicesU8 {}
+impl Attribute for JointIndicesU8 {
+    const NAME: &'static str = "joint_indices";
+    const SIZE: ElemStride = 4;
+}
+
+/// Type for joint weights attribute of vertex.
+/// Weights of joints from `JointIndices` attribute. Must sum up to `1.0`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct JointWeights(pub [f32; 4]);
+impl<T> From<T> for JointWeights
+where
+    T: Into<[f32; 4]>,
+{
+    fn from(from: T) -> Self {
+        JointWeights(from.into())
+    }
+}
+impl AsFormat for JointWeights {
+    const SELF: Format = Format::Rgba32Float;
+}
+unsafe impl Pod for JointWeights {}
+impl Attribute for JointWeights {
+    const NAME: &'static str = "joint_weights";
+    const SIZE: ElemStride = 16;
+}
+
+/// Type for joint weights attribute of vertex packed as `Rgba8Unorm`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct JointWeightsUnorm8(pub [u8; 4]);
+impl From<JointWeights> for JointWeightsUnorm8 {
+    fn from(weights: JointWeights) -> Self {
+        let w = quantize_weights(weights.0, 8);
+        JointWeightsUnorm8([w[0] as u8, w[1] as u8, w[2] as u8, w[3] as u8])
+    }
+}
+impl From<JointWeightsUnorm8> for [f32; 4] {
+    fn from(weights: JointWeightsUnorm8) -> Self {
+        let w = weights.0;
+        [
+            from_unorm(w[0] as u32, 8),
+            from_unorm(w[1] as u32, 8),
+            from_unorm(w[2] as u32, 8),
+            from_unorm(w[3] as u32, 8),
+        ]
+    }
+}
+impl AsFormat for JointWeightsUnorm8 {
+    const SELF: Format = Format::Rgba8Unorm;
+}
+unsafe impl Pod for JointWeightsUnorm8 {}
+impl Attribute for JointWeightsUnorm8 {
+    const NAME: &'static str = "joint_weights";
+    const SIZE: ElemStride = 4;
+}
+
+/// Type for joint weights attribute of vertex packed as `Rgba16Unorm`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct JointWeightsUnorm16(pub [u16; 4]);
+impl From<JointWeights> for JointWeightsUnorm16 {
+    fn from(weights: JointWeights) -> Self {
+        let w = quantize_weights(weights.0, 16);
+        JointWeightsUnorm16([w[0] as u16, w[1] as u16, w[2] as u16, w[3] as u16])
+    }
+}
+impl From<JointWeightsUnorm16> for [f32; 4] {
+    fn from(weights: JointWeightsUnorm16) -> Self {
+        let w = weights.0;
+        [
+            from_unorm(w[0] as u32, 16),
+            from_unorm(w[1] as u32, 16),
+            from_unorm(w[2] as u32, 16),
+            from_unorm(w[3] as u32, 16),
+        ]
+    }
+}
+impl AsFormat for JointWeightsUnorm16 {
+    const SELF: Format = Format::Rgba16Unorm;
+}
+unsafe impl Pod for JointWeightsUnorm16 {}
+impl Attribute for JointWeightsUnorm16 {
+    const NAME: &'static str = "joint_weights";
+    const SIZE: ElemStride = 8;
+}
+
+/// Semantic name of the attribute from set `index`.
+/// Set `0` has base name, other sets have index appended.
+/// E.g. `indexed_name("tex_coord", 1)` is `"tex_coord_1"`.
+///
+/// Attribute types are provided for sets up to `3` of texture coordinates and colors
+/// and up to `1` of joint indices and weights.
+/// Further sets can be described with `DynamicAttribute` named by this function.
+pub fn indexed_name(base: &'static str, index: u32) -> Cow<'static, str> {
+    if index == 0 {
+        Cow::Borrowed(base)
+    } else {
+        Cow::Owned(format!("{}_{}", base, index))
+    }
+}
+
+/// Define attribute type for additional set of the attribute.
+macro_rules! indexed_attribute {
+    ($(#[$meta:meta])* $name:ident($repr:ty): $base:ident, $attr_name:expr) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        pub struct $name(pub $repr);
+        impl<T> From<T> for $name
+        where
+            T: Into<$repr>,
+        {
+            fn from(from: T) -> Self {
+                $name(from.into())
+            }
+        }
+        impl AsFormat for $name {
+            const SELF: Format = <$base as AsFormat>::SELF;
+        }
+        unsafe impl Pod for $name {}
+        impl Attribute for $name {
+            const NAME: &'static str = $attr_name;
+            const SIZE: ElemStride = <$base as Attribute>::SIZE;
+        }
+    };
+}
+
+indexed_attribute!(
+    /// Type for second set of texture coordinates. E.g. lightmap UV.
+    TexCoord1([f32; 2]): TexCoord, "tex_coord_1"
+);
+indexed_attribute!(
+    /// Type for third set of texture coordinates.
+    TexCoord2([f32; 2]): TexCoord, "tex_coord_2"
+);
+indexed_attribute!(
+    /// Type for fourth set of texture coordinates.
+    TexCoord3([f32; 2]): TexCoord, "tex_coord_3"
+);
+indexed_attribute!(
+    /// Type for second set of vertex colors.
+    Color1([f32; 4]): Color, "color_1"
+);
+indexed_attribute!(
+    /// Type for third set of vertex colors.
+    Color2([f32; 4]): Color, "color_2"
+);
+indexed_attribute!(
+    /// Type for fourth set of vertex colors.
+    Color3([f32; 4]): Color, "color_3"
+);
+indexed_attribute!(
+    /// Type for second set of joint indices.
+    /// Allows up to eight joints to influence the vertex.
+    JointIndices1([u16; 4]): JointIndices, "joint_indices_1"
+);
+indexed_attribute!(
+    /// Type for second set of joint weights.
+    /// Weights of both sets together must sum up to `1.0`.
+    JointWeights1([f32; 4]): JointWeights, "joint_weights_1"
+);
+
+/// Define packed attribute type for additional set of the attribute.
+/// Packed type converts from unpacked type of the same set
+/// the same way `$base` converts from `$set_base`.
+macro_rules! indexed_packed_attribute {
+    (
+        $(#[$meta:meta])*
+        $name:ident($repr:ty): $base:ident, $attr_name:expr, $set:ident: $set_base:ident => $unpacked:ty
+    ) => {
+        indexed_packed_attribute!($(#[$meta])* $name($repr): $base, $attr_name => $unpacked);
+        impl From<$set> for $name {
+            fn from(value: $set) -> Self {
+                $name($base::from($set_base(value.0)).0)
+            }
+        }
+    };
+    ($(#[$meta:meta])* $name:ident($repr:ty): $base:ident, $attr_name:expr => $unpacked:ty) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Clone, Copy, Debug, PartialEq)]
+        #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+        pub struct $name(pub $repr);
+        impl From<$name> for $unpacked {
+            fn from(value: $name) -> Self {
+                $base(value.0).into()
+            }
+        }
+        impl AsFormat for $name {
+            const SELF: Format = <$base as AsFormat>::SELF;
+        }
+        unsafe impl Pod for $name {}
+        impl Attribute for $name {
+            const NAME: &'static str = $attr_name;
+            const SIZE: ElemStride = <$base as Attribute>::SIZE;
+        }
+    };
+}
+
+indexed_packed_attribute!(
+    /// Type for second set of texture coordinates packed as two half-precision floats.
+    HalfTexCoord1([u16; 2]): HalfTexCoord, "tex_coord_1", TexCoord1: TexCoord => [f32; 2]
+);
+indexed_packed_attribute!(
+    /// Type for third set of texture coordinates packed as two half-precision floats.
+    HalfTexCoord2([u16; 2]): HalfTexCoord, "tex_coord_2", TexCoord2: TexCoord => [f32; 2]
+);
+indexed_packed_attribute!(
+    /// Type for fourth set of texture coordinates packed as two half-precision floats.
+    HalfTexCoord3([u16; 2]): HalfTexCoord, "tex_coord_3", TexCoord3: TexCoord => [f32; 2]
+);
+indexed_packed_attribute!(
+    /// Type for second set of vertex colors packed as `Rgba8Unorm`.
+    PackedColor1([u8; 4]): PackedColor, "color_1", Color1: Color => [f32; 4]
+);
+indexed_packed_attribute!(
+    /// Type for third set of vertex colors packed as `Rgba8Unorm`.
+    PackedColor2([u8; 4]): PackedColor, "color_2", Color2: Color => [f32; 4]
+);
+indexed_packed_attribute!(
+    /// Type for fourth set of vertex colors packed as `Rgba8Unorm`.
+    PackedColor3([u8; 4]): PackedColor, "color_3", Color3: Color => [f32; 4]
+);
+indexed_packed_attribute!(
+    /// Type for second set of joint indices stored as `u8`.
+    JointIndices1U8([u8; 4]): JointIndicesU8, "joint_indices_1" => [u16; 4]
+);
+indexed_packed_attribute!(
+    /// Type for second set of joint weights packed as `Rgba8Unorm`.
+    JointWeights1Unorm8([u8; 4]): JointWeightsUnorm8, "joint_weights_1",
+    JointWeights1: JointWeights => [f32; 4]
+);
+indexed_packed_attribute!(
+    /// Type for second set of joint weights packed as `Rgba16Unorm`.
+    JointWeights1Unorm16([u16; 4]): JointWeightsUnorm16, "joint_weights_1",
+    JointWeights1: JointWeights => [f32; 4]
+);
+
+/// Attribute of vertex format with semantic name.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct VertexAttribute<'a> {
+    /// Semantic name of the attribute.
+    /// Same as `Attribute::NAME` for built-in attributes.
+    pub name: Cow<'a, str>,
+
+    /// Format and offset of the attribute.
+    pub element: Element<Format>,
+}
+
+/// Attribute described at runtime by semantic name and format.
+/// Counterpart of `Attribute` types for attributes without Rust type,
+/// e.g. glTF custom attributes like `_BATCHID` or attributes from data files.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DynamicAttribute {
+    /// Semantic name of the attribute.
+    pub name: Cow<'static, str>,
+
+    /// Format of the attribute.
+    pub format: Format,
+}
+
+impl DynamicAttribute {
+    /// Create attribute with specified name and format.
+    pub fn new<N>(name: N, format: Format) -> Self
+    where
+        N: Into<Cow<'static, str>>,
+    {
+        DynamicAttribute {
+            name: name.into(),
+            format,
+        }
+    }
+
+    /// Describe static attribute type.
+    pub fn of<A>() -> Self
+    where
+        A: Attribute,
+    {
+        DynamicAttribute::new(A::NAME, A::SELF)
+    }
+
+    /// Size of the attribute.
+    pub fn size(&self) -> ElemStride {
+        format_size(self.format)
+    }
+}
+
 /// Vertex format contains information to initialize graphics pipeline
 /// Attributes must be sorted by offset.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct VertexFormat<'a> {
     /// Attributes for format.
-    pub attributes: Cow<'a, [Element<Format>]>,
+    pub attributes: Cow<'a, [VertexAttribute<'a>]>,
 
     /// Size of single vertex.
     pub stride: ElemStride,
+
+    /// Input rate of the data.
+    /// `0` for per-vertex data.
+    /// `N` for per-instance data that advances every `N` instances.
+    pub rate: InstanceRate,
+}
+
+impl VertexFormat<'static> {
+    /// Build per-vertex format with attributes placed one after another in specified order.
+    /// Each attribute is aligned as `VertexFormat::validate` requires
+    /// and stride is padded to the largest alignment.
+    pub fn from_attributes<I>(attributes: I) -> Self
+    where
+        I: IntoIterator<Item = DynamicAttribute>,
+    {
+        let mut stride = 0;
+        let mut max_align = 1;
+        let attributes = attributes
+            .into_iter()
+            .map(|attribute| {
+                let align = format_align(attribute.format);
+                max_align = max_align.max(align);
+                let offset = align_up(stride, align);
+                stride = offset + attribute.size();
+                VertexAttribute {
+                    name: attribute.name,
+                    element: Element {
+                        format: attribute.format,
+                        offset,
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+
+        VertexFormat {
+            attributes: Cow::Owned(attributes),
+            stride: align_up(stride, max_align),
+            rate: 0,
+        }
+    }
+}
+
+impl<'a> VertexFormat<'a> {
+    /// Find attribute with specified name.
+    pub fn attribute(&self, name: &str) -> Option<&VertexAttribute<'a>> {
+        self.attributes
+            .iter()
+            .find(|attribute| attribute.name == name)
+    }
+
+    /// Make per-instance format that advances every `rate` instances.
+    pub fn per_instance(self, rate: InstanceRate) -> Self {
+        assert_ne!(rate, 0, "Instance rate must be non-zero");
+        VertexFormat { rate, ..self }
+    }
+
+    /// Check if data in this format is per-instance.
+    pub fn is_per_instance(&self) -> bool {
+        self.rate != 0
+    }
+
+    /// Check that format is valid.
+    /// Stride must be non-zero.
+    /// Attributes must be sorted by offset, aligned, must not overlap
+    /// and must fit into stride.
+    pub fn validate(&self) -> Result<(), VertexFormatError> {
+        if self.stride == 0 {
+            return Err(VertexFormatError::ZeroStride);
+        }
+
+        // Index and end of the previous attribute.
+        let mut last: Option<(usize, ElemOffset)> = None;
+        for (index, attribute) in self.attributes.iter().enumerate() {
+            let element = &attribute.element;
+            let size = format_size(element.format);
+            let align = format_align(element.format);
+            let end = element
+                .offset
+                .checked_add(size)
+                .ok_or(VertexFormatError::OffsetOverflow { index })?;
+
+            if index > 0 && self.attributes[index - 1].element.offset > element.offset {
+                return Err(VertexFormatError::Unsorted { index });
+            }
+            if element.offset % align != 0 {
+                return Err(VertexFormatError::Misaligned {
+                    index,
+                    offset: element.offset,
+                    align,
+                });
+            }
+            if end > self.stride {
+                return Err(VertexFormatError::OutOfStride {
+                    index,
+                    end,
+                    stride: self.stride,
+                });
+            }
+            if let Some((first, last_end)) = last {
+                if last_end > element.offset {
+                    return Err(VertexFormatError::Overlap {
+                        first,
+                        second: index,
+                    });
+                }
+            }
+            last = Some((index, end));
+        }
+        Ok(())
+    }
+}
+
+/// Error returned by `VertexFormat::validate` for malformed vertex format.
+#[derive(Clone, Copy, Debug, Fail, PartialEq, Eq)]
+pub enum VertexFormatError {
+    /// Stride of the vertex format is zero.
+    #[fail(display = "Vertex format has zero stride")]
+    ZeroStride,
+
+    /// Attributes are not sorted by offset.
+    #[fail(display = "Attribute {} has smaller offset than previous one", index)]
+    Unsorted {
+        /// Index of the first attribute that is out of order.
+        index: usize,
+    },
+
+    /// Two attributes occupy same bytes.
+    #[fail(display = "Attributes {} and {} overlap", first, second)]
+    Overlap {
+        /// Index of the first attribute.
+        first: usize,
+        /// Index of the second attribute.
+        second: usize,
+    },
+
+    /// Attribute doesn't fit into stride.
+    #[fail(
+        display = "Attribute {} ends at {} which is beyond stride {}",
+        index, end, stride
+    )]
+    OutOfStride {
+        /// Index of the attribute.
+        index: usize,
+        /// Offset of the attribute's end.
+        end: ElemOffset,
+        /// Stride of the vertex format.
+        stride: ElemStride,
+    },
+
+    /// Attribute's end doesn't fit into `ElemOffset`.
+    #[fail(display = "Attribute {} ends beyond maximum offset", index)]
+    OffsetOverflow {
+        /// Index of the attribute.
+        index: usize,
+    },
+
+    /// Attribute's offset is not properly aligned.
+    #[fail(
+        display = "Attribute {} has offset {} which is not aligned to {}",
+        index, offset, align
+    )]
+    Misaligned {
+        /// Index of the attribute.
+        index: usize,
+        /// Offset of the attribute.
+        offset: ElemOffset,
+        /// Required alignment.
+        align: ElemStride,
+    },
 }
 
 /// Trait implemented by all valid vertex formats.
@@ -163,11 +843,15 @@ where
     T: Attribute,
 {
     const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
-        attributes: Cow::Borrowed(&[Element {
-            format: T::SELF,
-            offset: 0,
+        attributes: Cow::Borrowed(&[VertexAttribute {
+            name: Cow::Borrowed(T::NAME),
+            element: Element {
+                format: T::SELF,
+                offset: 0,
+            },
         }]),
         stride: T::SIZE,
+        rate: 0,
     };
 }
 
@@ -187,7 +871,7 @@ where
     };
 }
 
-/// Vertex format with position and RGBA8 color attributes.
+/// Vertex format with position and RGBA color attributes.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -203,10 +887,17 @@ unsafe impl Pod for PosColor {}
 impl AsVertexFormat for PosColor {
     const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
         attributes: Cow::Borrowed(&[
-            <Self as WithAttribute<Position>>::ELEMENT,
-            <Self as WithAttribute<Color>>::ELEMENT,
+            VertexAttribute {
+                name: Cow::Borrowed(Position::NAME),
+                element: <Self as WithAttribute<Position>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(Color::NAME),
+                element: <Self as WithAttribute<Color>>::ELEMENT,
+            },
         ]),
         stride: Position::SIZE + Color::SIZE,
+        rate: 0,
     };
 }
 
@@ -224,7 +915,7 @@ impl WithAttribute<Color> for PosColor {
     };
 }
 
-/// Vertex format with position, normal, and UV texture coordinate attributes.
+/// Vertex format with position and normal attributes.
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -240,10 +931,17 @@ unsafe impl Pod for PosNorm {}
 impl AsVertexFormat for PosNorm {
     const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
         attributes: Cow::Borrowed(&[
-            <Self as WithAttribute<Position>>::ELEMENT,
-            <Self as WithAttribute<Normal>>::ELEMENT,
+            VertexAttribute {
+                name: Cow::Borrowed(Position::NAME),
+                element: <Self as WithAttribute<Position>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(Normal::NAME),
+                element: <Self as WithAttribute<Normal>>::ELEMENT,
+            },
         ]),
-        stride: Position::SIZE + Normal::SIZE + TexCoord::SIZE,
+        stride: Position::SIZE + Normal::SIZE,
+        rate: 0,
     };
 }
 
@@ -277,10 +975,17 @@ unsafe impl Pod for PosTex {}
 impl AsVertexFormat for PosTex {
     const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
         attributes: Cow::Borrowed(&[
-            <Self as WithAttribute<Position>>::ELEMENT,
-            <Self as WithAttribute<TexCoord>>::ELEMENT,
+            VertexAttribute {
+                name: Cow::Borrowed(Position::NAME),
+                element: <Self as WithAttribute<Position>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(TexCoord::NAME),
+                element: <Self as WithAttribute<TexCoord>>::ELEMENT,
+            },
         ]),
         stride: Position::SIZE + TexCoord::SIZE,
+        rate: 0,
     };
 }
 
@@ -316,11 +1021,21 @@ unsafe impl Pod for PosNormTex {}
 impl AsVertexFormat for PosNormTex {
     const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
         attributes: Cow::Borrowed(&[
-            <Self as WithAttribute<Position>>::ELEMENT,
-            <Self as WithAttribute<Normal>>::ELEMENT,
-            <Self as WithAttribute<TexCoord>>::ELEMENT,
+            VertexAttribute {
+                name: Cow::Borrowed(Position::NAME),
+                element: <Self as WithAttribute<Position>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(Normal::NAME),
+                element: <Self as WithAttribute<Normal>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(TexCoord::NAME),
+                element: <Self as WithAttribute<TexCoord>>::ELEMENT,
+            },
         ]),
         stride: Position::SIZE + Normal::SIZE + TexCoord::SIZE,
+        rate: 0,
     };
 }
 
@@ -365,12 +1080,25 @@ unsafe impl Pod for PosNormTangTex {}
 impl AsVertexFormat for PosNormTangTex {
     const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
         attributes: Cow::Borrowed(&[
-            <Self as WithAttribute<Position>>::ELEMENT,
-            <Self as WithAttribute<Normal>>::ELEMENT,
-            <Self as WithAttribute<Tangent>>::ELEMENT,
-            <Self as WithAttribute<TexCoord>>::ELEMENT,
+            VertexAttribute {
+                name: Cow::Borrowed(Position::NAME),
+                element: <Self as WithAttribute<Position>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(Normal::NAME),
+                element: <Self as WithAttribute<Normal>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(Tangent::NAME),
+                element: <Self as WithAttribute<Tangent>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(TexCoord::NAME),
+                element: <Self as WithAttribute<TexCoord>>::ELEMENT,
+            },
         ]),
         stride: Position::SIZE + Normal::SIZE + Tangent::SIZE + TexCoord::SIZE,
+        rate: 0,
     };
 }
 
@@ -402,6 +1130,398 @@ impl WithAttribute<TexCoord> for PosNormTangTex {
     };
 }
 
+/// Vertex format with position and packed RGBA8 color attributes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PosColorPacked {
+    /// Position of the vertex in 3D space.
+    pub position: Position,
+    /// RGBA color value of the vertex.
+    pub color: PackedColor,
+}
+
+unsafe impl Pod for PosColorPacked {}
+
+impl AsVertexFormat for PosColorPacked {
+    const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
+        attributes: Cow::Borrowed(&[
+            VertexAttribute {
+                name: Cow::Borrowed(Position::NAME),
+                element: <Self as WithAttribute<Position>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(PackedColor::NAME),
+                element: <Self as WithAttribute<PackedColor>>::ELEMENT,
+            },
+        ]),
+        stride: Position::SIZE + PackedColor::SIZE,
+        rate: 0,
+    };
+}
+
+impl WithAttribute<Position> for PosColorPacked {
+    const ELEMENT: Element<Format> = Element {
+        offset: 0,
+        format: Position::SELF,
+    };
+}
+
+impl WithAttribute<PackedColor> for PosColorPacked {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE,
+        format: PackedColor::SELF,
+    };
+}
+
+impl From<PosColor> for PosColorPacked {
+    fn from(vertex: PosColor) -> Self {
+        PosColorPacked {
+            position: vertex.position,
+            color: vertex.color.into(),
+        }
+    }
+}
+
+/// Vertex format with position, packed normal, and half-precision UV texture coordinate attributes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PosNormTexPacked {
+    /// Position of the vertex in 3D space.
+    pub position: Position,
+    /// Normal vector of the vertex.
+    pub normal: PackedNormal,
+    /// UV texture coordinates used by the vertex.
+    pub tex_coord: HalfTexCoord,
+}
+
+unsafe impl Pod for PosNormTexPacked {}
+
+impl AsVertexFormat for PosNormTexPacked {
+    const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
+        attributes: Cow::Borrowed(&[
+            VertexAttribute {
+                name: Cow::Borrowed(Position::NAME),
+                element: <Self as WithAttribute<Position>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(PackedNormal::NAME),
+                element: <Self as WithAttribute<PackedNormal>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(HalfTexCoord::NAME),
+                element: <Self as WithAttribute<HalfTexCoord>>::ELEMENT,
+            },
+        ]),
+        stride: Position::SIZE + PackedNormal::SIZE + HalfTexCoord::SIZE,
+        rate: 0,
+    };
+}
+
+impl WithAttribute<Position> for PosNormTexPacked {
+    const ELEMENT: Element<Format> = Element {
+        offset: 0,
+        format: Position::SELF,
+    };
+}
+
+impl WithAttribute<PackedNormal> for PosNormTexPacked {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE,
+        format: PackedNormal::SELF,
+    };
+}
+
+impl WithAttribute<HalfTexCoord> for PosNormTexPacked {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE + PackedNormal::SIZE,
+        format: HalfTexCoord::SELF,
+    };
+}
+
+impl From<PosNormTex> for PosNormTexPacked {
+    fn from(vertex: PosNormTex) -> Self {
+        PosNormTexPacked {
+            position: vertex.position,
+            normal: vertex.normal.into(),
+            tex_coord: vertex.tex_coord.into(),
+        }
+    }
+}
+
+/// Vertex format with position, packed normal, packed tangent,
+/// and half-precision UV texture coordinate attributes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PosNormTangTexPacked {
+    /// Position of the vertex in 3D space.
+    pub position: Position,
+    /// Normal vector of the vertex.
+    pub normal: PackedNormal,
+    /// Tangent vector of the vertex.
+    pub tangent: PackedTangent,
+    /// UV texture coordinates used by the vertex.
+    pub tex_coord: HalfTexCoord,
+}
+
+unsafe impl Pod for PosNormTangTexPacked {}
+
+impl AsVertexFormat for PosNormTangTexPacked {
+    const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
+        attributes: Cow::Borrowed(&[
+            VertexAttribute {
+                name: Cow::Borrowed(Position::NAME),
+                element: <Self as WithAttribute<Position>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(PackedNormal::NAME),
+                element: <Self as WithAttribute<PackedNormal>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(PackedTangent::NAME),
+                element: <Self as WithAttribute<PackedTangent>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(HalfTexCoord::NAME),
+                element: <Self as WithAttribute<HalfTexCoord>>::ELEMENT,
+            },
+        ]),
+        stride: Position::SIZE + PackedNormal::SIZE + PackedTangent::SIZE + HalfTexCoord::SIZE,
+        rate: 0,
+    };
+}
+
+impl WithAttribute<Position> for PosNormTangTexPacked {
+    const ELEMENT: Element<Format> = Element {
+        offset: 0,
+        format: Position::SELF,
+    };
+}
+
+impl WithAttribute<PackedNormal> for PosNormTangTexPacked {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE,
+        format: PackedNormal::SELF,
+    };
+}
+
+impl WithAttribute<PackedTangent> for PosNormTangTexPacked {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE + PackedNormal::SIZE,
+        format: PackedTangent::SELF,
+    };
+}
+
+impl WithAttribute<HalfTexCoord> for PosNormTangTexPacked {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE + PackedNormal::SIZE + PackedTangent::SIZE,
+        format: HalfTexCoord::SELF,
+    };
+}
+
+impl From<PosNormTangTex> for PosNormTangTexPacked {
+    fn from(vertex: PosNormTangTex) -> Self {
+        PosNormTangTexPacked {
+            position: vertex.position,
+            normal: vertex.normal.into(),
+            tangent: vertex.tangent.into(),
+            tex_coord: vertex.tex_coord.into(),
+        }
+    }
+}
+
+/// Vertex format with position, normal, UV texture coordinate,
+/// joint indices and joint weights attributes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PosNormTexSkin {
+    /// Position of the vertex in 3D space.
+    pub position: Position,
+    /// Normal vector of the vertex.
+    pub normal: Normal,
+    /// UV texture coordinates used by the vertex.
+    pub tex_coord: TexCoord,
+    /// Indices of joints that influence the vertex.
+    pub joint_indices: JointIndices,
+    /// Weights of joints that influence the vertex.
+    pub joint_weights: JointWeights,
+}
+
+unsafe impl Pod for PosNormTexSkin {}
+
+impl AsVertexFormat for PosNormTexSkin {
+    const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
+        attributes: Cow::Borrowed(&[
+            VertexAttribute {
+                name: Cow::Borrowed(Position::NAME),
+                element: <Self as WithAttribute<Position>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(Normal::NAME),
+                element: <Self as WithAttribute<Normal>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(TexCoord::NAME),
+                element: <Self as WithAttribute<TexCoord>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(JointIndices::NAME),
+                element: <Self as WithAttribute<JointIndices>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(JointWeights::NAME),
+                element: <Self as WithAttribute<JointWeights>>::ELEMENT,
+            },
+        ]),
+        stride: Position::SIZE
+            + Normal::SIZE
+            + TexCoord::SIZE
+            + JointIndices::SIZE
+            + JointWeights::SIZE,
+        rate: 0,
+    };
+}
+
+impl WithAttribute<Position> for PosNormTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: 0,
+        format: Position::SELF,
+    };
+}
+
+impl WithAttribute<Normal> for PosNormTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE,
+        format: Normal::SELF,
+    };
+}
+
+impl WithAttribute<TexCoord> for PosNormTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE + Normal::SIZE,
+        format: TexCoord::SELF,
+    };
+}
+
+impl WithAttribute<JointIndices> for PosNormTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE + Normal::SIZE + TexCoord::SIZE,
+        format: JointIndices::SELF,
+    };
+}
+
+impl WithAttribute<JointWeights> for PosNormTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE + Normal::SIZE + TexCoord::SIZE + JointIndices::SIZE,
+        format: JointWeights::SELF,
+    };
+}
+
+/// Vertex format with position, normal, tangent, UV texture coordinate,
+/// joint indices and joint weights attributes.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PosNormTangTexSkin {
+    /// Position of the vertex in 3D space.
+    pub position: Position,
+    /// Normal vector of the vertex.
+    pub normal: Normal,
+    /// Tangent vector of the vertex.
+    pub tangent: Tangent,
+    /// UV texture coordinates used by the vertex.
+    pub tex_coord: TexCoord,
+    /// Indices of joints that influence the vertex.
+    pub joint_indices: JointIndices,
+    /// Weights of joints that influence the vertex.
+    pub joint_weights: JointWeights,
+}
+
+unsafe impl Pod for PosNormTangTexSkin {}
+
+impl AsVertexFormat for PosNormTangTexSkin {
+    const VERTEX_FORMAT: VertexFormat<'static> = VertexFormat {
+        attributes: Cow::Borrowed(&[
+            VertexAttribute {
+                name: Cow::Borrowed(Position::NAME),
+                element: <Self as WithAttribute<Position>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(Normal::NAME),
+                element: <Self as WithAttribute<Normal>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(Tangent::NAME),
+                element: <Self as WithAttribute<Tangent>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(TexCoord::NAME),
+                element: <Self as WithAttribute<TexCoord>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(JointIndices::NAME),
+                element: <Self as WithAttribute<JointIndices>>::ELEMENT,
+            },
+            VertexAttribute {
+                name: Cow::Borrowed(JointWeights::NAME),
+                element: <Self as WithAttribute<JointWeights>>::ELEMENT,
+            },
+        ]),
+        stride: Position::SIZE
+            + Normal::SIZE
+            + Tangent::SIZE
+            + TexCoord::SIZE
+            + JointIndices::SIZE
+            + JointWeights::SIZE,
+        rate: 0,
+    };
+}
+
+impl WithAttribute<Position> for PosNormTangTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: 0,
+        format: Position::SELF,
+    };
+}
+
+impl WithAttribute<Normal> for PosNormTangTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE,
+        format: Normal::SELF,
+    };
+}
+
+impl WithAttribute<Tangent> for PosNormTangTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE + Normal::SIZE,
+        format: Tangent::SELF,
+    };
+}
+
+impl WithAttribute<TexCoord> for PosNormTangTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE + Normal::SIZE + Tangent::SIZE,
+        format: TexCoord::SELF,
+    };
+}
+
+impl WithAttribute<JointIndices> for PosNormTangTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE + Normal::SIZE + Tangent::SIZE + TexCoord::SIZE,
+        format: JointIndices::SELF,
+    };
+}
+
+impl WithAttribute<JointWeights> for PosNormTangTexSkin {
+    const ELEMENT: Element<Format> = Element {
+        offset: Position::SIZE + Normal::SIZE + Tangent::SIZE + TexCoord::SIZE + JointIndices::SIZE,
+        format: JointWeights::SELF,
+    };
+}
+
 /// Allows to query specific `Attribute`s of `AsVertexFormat`
 pub trait Query<T>: AsVertexFormat {
     /// Attributes from tuple `T`
@@ -433,3 +1553,103 @@ macro_rules! impl_query {
 }
 
 impl_query!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(offsets: &[ElemOffset], stride: ElemStride) -> VertexFormat<'static> {
+        VertexFormat {
+            attributes: Cow::Owned(
+                offsets
+                    .iter()
+                    .map(|&offset| VertexAttribute {
+                        name: Cow::Borrowed("value"),
+                        element: Element {
+                            format: Format::R32Float,
+                            offset,
+                        },
+                    })
+                    .collect(),
+            ),
+            stride,
+            rate: 0,
+        }
+    }
+
+    #[test]
+    fn validate() {
+        assert_eq!(format(&[0, 4], 8).validate(), Ok(()));
+        assert_eq!(PosNormTangTex::VERTEX_FORMAT.validate(), Ok(()));
+        assert_eq!(
+            format(&[0], 0).validate(),
+            Err(VertexFormatError::ZeroStride)
+        );
+        assert_eq!(
+            format(&[4, 0], 8).validate(),
+            Err(VertexFormatError::Unsorted { index: 1 })
+        );
+        assert_eq!(
+            format(&[0, 2], 8).validate(),
+            Err(VertexFormatError::Misaligned {
+                index: 1,
+                offset: 2,
+                align: 4,
+            })
+        );
+        assert_eq!(
+            format(&[0, 4], 6).validate(),
+            Err(VertexFormatError::OutOfStride {
+                index: 1,
+                end: 8,
+                stride: 6,
+            })
+        );
+    }
+
+    #[test]
+    fn validate_offset_overflow() {
+        assert_eq!(
+            format(&[ElemOffset::MAX & !3], ElemStride::MAX).validate(),
+            Err(VertexFormatError::OffsetOverflow { index: 0 })
+        );
+    }
+
+    #[test]
+    fn packed_tangent_keeps_handedness() {
+        for &w in &[1.0, -1.0] {
+            let tangent = <[f32; 4]>::from(PackedTangent::from(Tangent([0.0, 1.0, 0.0, w])));
+            assert_eq!(tangent, [0.0, 1.0, 0.0, w]);
+        }
+    }
+
+    #[test]
+    fn indexed_packed_attributes() {
+        assert_eq!(HalfTexCoord2::NAME, "tex_coord_2");
+        assert_eq!(HalfTexCoord2::SELF, HalfTexCoord::SELF);
+        let tex_coord = TexCoord2([0.5, -2.0]);
+        assert_eq!(
+            HalfTexCoord2::from(tex_coord).0,
+            HalfTexCoord::from(TexCoord(tex_coord.0)).0
+        );
+        assert_eq!(
+            <[f32; 2]>::from(HalfTexCoord2::from(tex_coord)),
+            tex_coord.0
+        );
+
+        assert_eq!(PackedColor1::NAME, indexed_name(Color::NAME, 1));
+        assert_eq!(
+            PackedColor1::from(Color1([1.0, 0.0, 1.0, 0.0])).0,
+            [255, 0, 255, 0]
+        );
+        assert_eq!(
+            <[u16; 4]>::from(JointIndices1U8([1, 2, 3, 4])),
+            [1, 2, 3, 4]
+        );
+        assert_eq!(
+            JointWeights1Unorm16::from(JointWeights1([1.0, 0.0, 0.0, 0.0])).0,
+            [65535, 0, 0, 0]
+        );
+        assert_eq!(JointWeights1Unorm8::SIZE, 4);
+    }
+}
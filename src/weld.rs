@@ -0,0 +1,427 @@
+//!
+//! Welding of duplicate vertices and removal of unused ones for `MeshBuilder`.
+//!
+
+use std::collections::HashMap;
+
+use hal::format::Format;
+
+use convert::{decode_element, element_layout, ElementLayout};
+use mesh::{MeshBuilder, MeshBuilderError};
+use utils::narrow_indices;
+
+/// Error returned when vertices can't be welded or compacted.
+#[derive(Clone, Debug, Fail, PartialEq, Eq)]
+pub enum WeldError {
+    /// Vertex data of the builder is malformed.
+    #[fail(display = "Vertex data is invalid: {}", _0)]
+    InvalidVertices(#[cause] MeshBuilderError),
+
+    /// Tolerance is specified for attribute that no per-vertex buffer has.
+    #[fail(display = "Attribute \"{}\" not found", _0)]
+    MissingAttribute(String),
+
+    /// Tolerance is specified for attribute with format that can't be decoded.
+    #[fail(display = "Attribute \"{}\" has unsupported format {:?}", name, format)]
+    UnsupportedFormat {
+        /// Name of the attribute.
+        name: String,
+        /// Format of the attribute.
+        format: Format,
+    },
+
+    /// Index refers to vertex that doesn't exist.
+    #[fail(
+        display = "Index {} is out of range for {} vertices",
+        index, vertex_count
+    )]
+    IndexOutOfRange {
+        /// Value of the index.
+        index: u32,
+        /// Number of vertices.
+        vertex_count: usize,
+    },
+}
+
+impl<'a> MeshBuilder<'a> {
+    /// Weld duplicate vertices.
+    /// See `weld_vertices`.
+    pub fn with_welded_vertices(mut self, tolerances: &[(&str, f64)]) -> Result<Self, WeldError> {
+        self.weld_vertices(tolerances)?;
+        Ok(self)
+    }
+
+    /// Merge vertices that are equal in all per-vertex buffers
+    /// and replace them with indices.
+    ///
+    /// Attributes listed in `tolerances` are decoded and their components
+    /// are considered equal if they differ by no more than the given epsilon.
+    /// Normalized integers are compared in their normalized range.
+    /// Other attributes must be equal bit for bit.
+    /// Vertex is merged into the first preceding unique vertex within tolerance,
+    /// so chains of close vertices are not collapsed transitively.
+    ///
+    /// Existing indices are remapped, otherwise index buffer is created.
+    /// Indices are stored as `u16` if unique vertices fit.
+    pub fn weld_vertices(&mut self, tolerances: &[(&str, f64)]) -> Result<&mut Self, WeldError> {
+        let vertex_count = self
+            .vertex_count()
+            .map_err(WeldError::InvalidVertices)?
+            .unwrap_or(0) as usize;
+        let indices = read_indices(self, vertex_count)?;
+
+        let (sources, welded) = {
+            let streams = self
+                .vertices
+                .iter()
+                .filter(|(_, format)| !format.is_per_instance() && format.stride != 0)
+                .map(|(vertices, format)| (&**vertices, format))
+                .collect::<Vec<_>>();
+
+            // Attributes compared with tolerance.
+            let mut fuzzy = Vec::new();
+            for &(name, epsilon) in tolerances {
+                let (stream, attribute) = streams
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(stream, &(_, format))| {
+                        format
+                            .attributes
+                            .iter()
+                            .find(|attribute| attribute.name == name)
+                            .map(|attribute| (stream, attribute))
+                    })
+                    .next()
+                    .ok_or_else(|| WeldError::MissingAttribute(name.to_string()))?;
+                let format = attribute.element.format;
+                let layout =
+                    element_layout(format).ok_or_else(|| WeldError::UnsupportedFormat {
+                        name: name.to_string(),
+                        format,
+                    })?;
+                if epsilon > 0.0 {
+                    fuzzy.push(Fuzzy {
+                        stream,
+                        offset: attribute.element.offset as usize,
+                        layout,
+                        epsilon,
+                    });
+                }
+            }
+            let decode = |vertex: usize, fuzzy: &Fuzzy| {
+                let (bytes, format) = streams[fuzzy.stream];
+                let offset = vertex * format.stride as usize + fuzzy.offset;
+                decode_element(bytes, offset, fuzzy.layout)
+            };
+
+            // Unique vertices grouped by bytes compared exactly
+            // and by cell of the first attribute with tolerance.
+            let mut unique: HashMap<Vec<u8>, HashMap<Vec<i64>, Vec<u32>>> = HashMap::new();
+            let mut sources = Vec::new();
+            let mut welded = Vec::with_capacity(vertex_count);
+            for vertex in 0..vertex_count {
+                let mut exact = Vec::new();
+                for (stream, &(bytes, format)) in streams.iter().enumerate() {
+                    let stride = format.stride as usize;
+                    let start = exact.len();
+                    exact.extend_from_slice(&bytes[vertex * stride..(vertex + 1) * stride]);
+                    for fuzzy in fuzzy.iter().filter(|fuzzy| fuzzy.stream == stream) {
+                        let offset = start + fuzzy.offset;
+                        for byte in &mut exact[offset..offset + fuzzy.layout_size()] {
+                            *byte = 0;
+                        }
+                    }
+                }
+
+                let cell = fuzzy.first().map_or(Vec::new(), |first| {
+                    let value = decode(vertex, first);
+                    (0..first.layout.channels)
+                        .map(|channel| (value[channel] / first.epsilon).floor() as i64)
+                        .collect()
+                });
+
+                let cells = unique.entry(exact).or_default();
+                let mut matching = None;
+                for neighbor in 0..3usize.pow(cell.len() as u32) {
+                    let mut key = cell.clone();
+                    let mut digits = neighbor;
+                    for component in &mut key {
+                        *component += (digits % 3) as i64 - 1;
+                        digits /= 3;
+                    }
+                    if let Some(candidates) = cells.get(&key) {
+                        for &candidate in candidates {
+                            let source = sources[candidate as usize] as usize;
+                            let within = fuzzy.iter().all(|fuzzy| {
+                                let (a, b) = (decode(vertex, fuzzy), decode(source, fuzzy));
+                                (0..fuzzy.layout.channels)
+                                    .all(|channel| (a[channel] - b[channel]).abs() <= fuzzy.epsilon)
+                            });
+                            if within && matching.map_or(true, |best| candidate < best) {
+                                matching = Some(candidate);
+                            }
+                        }
+                    }
+                }
+
+                welded.push(matching.unwrap_or_else(|| {
+                    sources.push(vertex as u32);
+                    let index = sources.len() as u32 - 1;
+                    cells.entry(cell).or_default().push(index);
+                    index
+                }));
+            }
+            (sources, welded)
+        };
+
+        let indices = indices
+            .into_iter()
+            .map(|index| welded[index as usize])
+            .collect();
+        if sources.len() < vertex_count {
+            self.remap_vertices(&sources);
+        }
+        self.set_indices(narrow_indices(indices));
+        Ok(self)
+    }
+
+    /// Remove unreferenced vertices.
+    /// See `compact_vertices`.
+    pub fn with_compacted_vertices(mut self) -> Result<Self, WeldError> {
+        self.compact_vertices()?;
+        Ok(self)
+    }
+
+    /// Remove vertices that are not referenced by the index buffer
+    /// keeping order of the rest, and remap indices.
+    /// Indices are stored as `u16` if remaining vertices fit.
+    /// Mesh without index buffer is left unchanged.
+    pub fn compact_vertices(&mut self) -> Result<&mut Self, WeldError> {
+        let vertex_count = self
+            .vertex_count()
+            .map_err(WeldError::InvalidVertices)?
+            .unwrap_or(0) as usize;
+        if self.indices.is_none() {
+            return Ok(self);
+        }
+        let mut indices = read_indices(self, vertex_count)?;
+
+        let mut remap = vec![None; vertex_count];
+        for &index in &indices {
+            remap[index as usize] = Some(0);
+        }
+        let mut sources = Vec::with_capacity(vertex_count);
+        for (vertex, new) in remap.iter_mut().enumerate() {
+            if new.is_some() {
+                *new = Some(sources.len() as u32);
+                sources.push(vertex as u32);
+            }
+        }
+
+        if sources.len() < vertex_count {
+            for index in &mut indices {
+                *index = remap[*index as usize].unwrap();
+            }
+            self.remap_vertices(&sources);
+        }
+        self.set_indices(narrow_indices(indices));
+        Ok(self)
+    }
+}
+
+/// Attribute compared with tolerance.
+struct Fuzzy {
+    stream: usize,
+    offset: usize,
+    layout: ElementLayout,
+    epsilon: f64,
+}
+
+impl Fuzzy {
+    fn layout_size(&self) -> usize {
+        if self.layout.packed {
+            4
+        } else {
+            self.layout.bits as usize / 8 * self.layout.channels
+        }
+    }
+}
+
+/// Read indices or generate sequential ones for mesh without index buffer.
+fn read_indices(builder: &MeshBuilder, vertex_count: usize) -> Result<Vec<u32>, WeldError> {
+    let indices = builder
+        .read_indices()
+        .unwrap_or_else(|| (0..vertex_count as u32).collect());
+    if let Some(&index) = indices
+        .iter()
+        .find(|&&index| index as usize >= vertex_count)
+    {
+        return Err(WeldError::IndexOutOfRange {
+            index,
+            vertex_count,
+        });
+    }
+    Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mesh::IndexWidth;
+    use vertex::{Normal, Position, TexCoord};
+
+    fn positions(positions: &[[f32; 3]]) -> MeshBuilder<'static> {
+        MeshBuilder::new()
+            .with_vertices(positions.iter().cloned().map(Position).collect::<Vec<_>>())
+            .unwrap()
+    }
+
+    fn collect_positions(builder: &MeshBuilder) -> Vec<[f32; 3]> {
+        builder
+            .attribute::<Position>()
+            .unwrap()
+            .map(|Position(p)| p)
+            .collect()
+    }
+
+    #[test]
+    fn duplicates() {
+        // Two triangles of a quad as a plain triangle list.
+        let corners = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let mut builder = positions(&corners);
+        builder.weld_vertices(&[]).unwrap();
+        assert_eq!(
+            collect_positions(&builder),
+            vec![corners[0], corners[1], corners[2], corners[5]]
+        );
+        assert_eq!(builder.read_indices(), Some(vec![0, 1, 2, 0, 2, 3]));
+        assert_eq!(builder.indices.as_ref().unwrap().1, IndexWidth::U16);
+    }
+
+    #[test]
+    fn remap_indices() {
+        let mut builder = positions(&[
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ])
+        .with_indices(vec![3u32, 2, 1, 0, 1, 3]);
+        builder.weld_vertices(&[]).unwrap();
+        assert_eq!(
+            collect_positions(&builder),
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+        assert_eq!(builder.read_indices(), Some(vec![2, 0, 1, 0, 1, 2]));
+    }
+
+    #[test]
+    fn all_buffers_compared() {
+        // Equal positions with different normals stay apart.
+        let mut builder = positions(&[[0.0; 3], [0.0; 3], [0.0; 3]])
+            .with_vertices(vec![
+                Normal([0.0, 0.0, 1.0]),
+                Normal([0.0, 1.0, 0.0]),
+                Normal([0.0, 0.0, 1.0]),
+            ])
+            .unwrap();
+        builder.weld_vertices(&[]).unwrap();
+        assert_eq!(builder.vertex_count(), Ok(Some(2)));
+        assert_eq!(builder.read_indices(), Some(vec![0, 1, 0]));
+        assert_eq!(
+            builder
+                .attribute::<Normal>()
+                .unwrap()
+                .map(|Normal(n)| n)
+                .collect::<Vec<_>>(),
+            vec![[0.0, 0.0, 1.0], [0.0, 1.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn tolerance() {
+        let corners = [
+            [0.0, 0.0, 0.0],
+            [0.0005, 0.0, -0.0005],
+            [0.0009, 0.0, 0.0],
+            [0.5, 0.0, 0.0],
+        ];
+
+        let mut exact = positions(&corners);
+        exact.weld_vertices(&[]).unwrap();
+        assert_eq!(exact.vertex_count(), Ok(Some(4)));
+
+        let mut welded = positions(&corners);
+        welded.weld_vertices(&[("position", 0.001)]).unwrap();
+        assert_eq!(collect_positions(&welded), vec![corners[0], corners[3]]);
+        assert_eq!(welded.read_indices(), Some(vec![0, 0, 0, 1]));
+
+        // Vertices are merged into the first unique one, not chained.
+        let mut chained = positions(&[[0.0; 3], [0.0008, 0.0, 0.0], [0.0016, 0.0, 0.0]]);
+        chained.weld_vertices(&[("position", 0.001)]).unwrap();
+        assert_eq!(chained.read_indices(), Some(vec![0, 0, 1]));
+    }
+
+    #[test]
+    fn errors() {
+        let mut builder = positions(&[[0.0; 3]]);
+        assert_eq!(
+            builder.weld_vertices(&[("tex_coord", 0.1)]).err(),
+            Some(WeldError::MissingAttribute("tex_coord".to_string()))
+        );
+
+        let mut builder = positions(&[[0.0; 3]]).with_indices(vec![0u16, 1]);
+        assert_eq!(
+            builder.weld_vertices(&[]).err(),
+            Some(WeldError::IndexOutOfRange {
+                index: 1,
+                vertex_count: 1,
+            })
+        );
+        assert_eq!(
+            builder.compact_vertices().err(),
+            Some(WeldError::IndexOutOfRange {
+                index: 1,
+                vertex_count: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn compact() {
+        let mut builder = positions(&[[0.0; 3], [1.0; 3], [2.0; 3], [3.0; 3]])
+            .with_vertices(vec![
+                TexCoord([0.0, 0.0]),
+                TexCoord([1.0, 0.0]),
+                TexCoord([2.0, 0.0]),
+                TexCoord([3.0, 0.0]),
+            ])
+            .unwrap()
+            .with_indices(vec![3u32, 1, 3]);
+        builder.compact_vertices().unwrap();
+        assert_eq!(collect_positions(&builder), vec![[1.0; 3], [3.0; 3]]);
+        assert_eq!(
+            builder
+                .attribute::<TexCoord>()
+                .unwrap()
+                .map(|TexCoord(t)| t)
+                .collect::<Vec<_>>(),
+            vec![[1.0, 0.0], [3.0, 0.0]]
+        );
+        assert_eq!(builder.read_indices(), Some(vec![1, 0, 1]));
+        assert_eq!(builder.indices.as_ref().unwrap().1, IndexWidth::U16);
+
+        // Without index buffer every vertex is used.
+        let mut builder = positions(&[[0.0; 3], [1.0; 3]]);
+        builder.compact_vertices().unwrap();
+        assert_eq!(builder.vertex_count(), Ok(Some(2)));
+        assert!(builder.indices.is_none());
+    }
+}